@@ -1,17 +1,97 @@
-use clap::{Arg, Command};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use std::error::Error;
+use std::ffi::OsString;
 
 static VERSION: &str = env!("CARGO_PKG_VERSION");
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum Commands {
+    Models {
+        probe: bool,
+        bench: Option<usize>,
+    },
+    Secrets(SecretsCommands),
+    Config(ConfigCommands),
+    Init {
+        dir: String,
+        interactive: bool,
+    },
+    RunTask {
+        name: String,
+        vars: Vec<String>,
+        input: Option<String>,
+    },
+    Report {
+        run_dir: String,
+        output: Option<String>,
+    },
+    Logs(LogsQuery),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogsQuery {
+    pub path: String,
+    pub method: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub id: Option<String>,
+    pub grep: Option<String>,
+    pub slowest: Option<usize>,
+    pub stats: bool,
+    // table or jsonl
+    pub format: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigCommands {
+    Validate,
+    Show {
+        origin: bool,
+    },
+    Convert {
+        to: Option<String>,
+        output: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SecretsCommands {
+    Set { name: String },
+    Get { name: String },
+    List,
+    Rm { name: String },
+}
+
 #[derive(Clone, Default)]
 pub struct Argument {
     pub config_file: String,
+    pub config_format: String,
+    pub profile: String,
+    pub overrides: Vec<String>,
+    pub run_id: String,
+    // -v raises and -q lowers the log level, one step per flag
+    pub verbosity: i8,
     pub version_info: String,
+    pub command: Option<Commands>,
 }
 
 impl Argument {
     pub fn parse(&mut self) -> Result<(), Box<dyn Error>> {
-        let matches = Command::new("vecx")
+        let matches = Self::build().get_matches();
+        self.load(&matches)
+    }
+
+    pub fn parse_from<I, T>(&mut self, args: I) -> Result<(), Box<dyn Error>>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::build().try_get_matches_from(args)?;
+        self.load(&matches)
+    }
+
+    fn build() -> Command {
+        Command::new("promptx")
             .version(VERSION)
             .arg(
                 Arg::new("config_file")
@@ -20,15 +100,316 @@ impl Argument {
                     .value_name("FILE")
                     .help("Config file")
                     .default_value("config.yml")
-                    .required(true),
+                    .global(true),
+            )
+            .arg(
+                Arg::new("config_format")
+                    .long("config-format")
+                    .value_name("FORMAT")
+                    .help("Config file format, detected from the extension by default")
+                    .value_parser(["yaml", "json", "toml"])
+                    .global(true),
+            )
+            .arg(
+                Arg::new("profile")
+                    .long("profile")
+                    .value_name("NAME")
+                    .help("Config profile overlaid on the base config, e.g. prod")
+                    .env("PROMPTX_PROFILE")
+                    .global(true),
+            )
+            .arg(
+                Arg::new("set")
+                    .long("set")
+                    .value_name("KEY=VALUE")
+                    .help("Override a config value, e.g. optimizer.top_n=3")
+                    .action(ArgAction::Append)
+                    .global(true),
+            )
+            .arg(
+                Arg::new("verbose")
+                    .short('v')
+                    .long("verbose")
+                    .help("Log more, repeat for more detail")
+                    .action(ArgAction::Count)
+                    .global(true),
             )
-            .get_matches();
+            .arg(
+                Arg::new("quiet")
+                    .short('q')
+                    .long("quiet")
+                    .help("Log less, repeat to silence")
+                    .action(ArgAction::Count)
+                    .global(true),
+            )
+            .arg(
+                Arg::new("run_id")
+                    .long("run-id")
+                    .value_name("ID")
+                    .help("Name of the run directory, a timestamp by default"),
+            )
+            .subcommand(
+                Command::new("models")
+                    .about("List configured LLMs, optionally probe or benchmark them")
+                    .arg(
+                        Arg::new("probe")
+                            .long("probe")
+                            .help("Send a tiny request to each LLM")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("bench")
+                            .long("bench")
+                            .value_name("N")
+                            .help("Measure latency and throughput over N calls")
+                            .value_parser(value_parser!(usize)),
+                    ),
+            )
+            .subcommand(
+                Command::new("secrets")
+                    .about("Manage the encrypted secrets store")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("set")
+                            .about("Store a secret, asked on the terminal or read from stdin")
+                            .arg(Arg::new("name").required(true)),
+                    )
+                    .subcommand(
+                        Command::new("get")
+                            .about("Print a secret")
+                            .arg(Arg::new("name").required(true)),
+                    )
+                    .subcommand(Command::new("list").about("List secret names"))
+                    .subcommand(
+                        Command::new("rm")
+                            .about("Remove a secret")
+                            .arg(Arg::new("name").required(true)),
+                    ),
+            )
+            .subcommand(
+                Command::new("config")
+                    .about("Inspect the config file")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("validate")
+                            .about("Check the config file and list every problem found"),
+                    )
+                    .subcommand(
+                        Command::new("show")
+                            .about("Print the config merged from every layer")
+                            .arg(
+                                Arg::new("origin")
+                                    .long("origin")
+                                    .help("Show the layer which set each value")
+                                    .action(ArgAction::SetTrue),
+                            ),
+                    )
+                    .subcommand(
+                        Command::new("convert")
+                            .about("Translate the config file between yaml, json and toml")
+                            .arg(
+                                Arg::new("to")
+                                    .long("to")
+                                    .value_name("FORMAT")
+                                    .help("Target format, detected from the output file by default")
+                                    .value_parser(["yaml", "json", "toml"]),
+                            )
+                            .arg(
+                                Arg::new("output")
+                                    .short('o')
+                                    .long("output")
+                                    .value_name("FILE")
+                                    .help("Write to FILE instead of stdout"),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("init")
+                    .about("Create a starter project")
+                    .arg(Arg::new("dir").default_value("."))
+                    .arg(
+                        Arg::new("interactive")
+                            .short('i')
+                            .long("interactive")
+                            .help("Ask for the provider and the API key variable")
+                            .action(ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("run-task")
+                    .about("Run a task of the prompt library")
+                    .arg(Arg::new("name").required(true))
+                    .arg(
+                        Arg::new("var")
+                            .long("var")
+                            .value_name("KEY=VALUE")
+                            .help("Template variable, overrides the input")
+                            .action(ArgAction::Append),
+                    )
+                    .arg(
+                        Arg::new("input")
+                            .long("input")
+                            .value_name("FILE")
+                            .help("JSON object with the template variables, - for stdin"),
+                    ),
+            )
+            .subcommand(
+                Command::new("report")
+                    .about("Render an HTML and a Markdown report of a run")
+                    .arg(Arg::new("run_dir").value_name("RUN_DIR").required(true))
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("DIR")
+                            .help("Write the reports to DIR instead of the outputs of the run"),
+                    ),
+            )
+            .subcommand(
+                Command::new("logs")
+                    .about("Query the IO logs of a file or a run directory")
+                    .arg(Arg::new("path").value_name("PATH").required(true))
+                    .arg(
+                        Arg::new("method")
+                            .long("method")
+                            .value_name("NAME")
+                            .help("Only calls of this method"),
+                    )
+                    .arg(
+                        Arg::new("since")
+                            .long("since")
+                            .value_name("TIME")
+                            .help("Only calls from TIME on, RFC 3339, YYYY-MM-DD or e.g. 30m ago"),
+                    )
+                    .arg(
+                        Arg::new("until")
+                            .long("until")
+                            .value_name("TIME")
+                            .help("Only calls up to TIME"),
+                    )
+                    .arg(
+                        Arg::new("id")
+                            .long("id")
+                            .value_name("ID")
+                            .help("Only records whose id starts with ID"),
+                    )
+                    .arg(
+                        Arg::new("grep")
+                            .long("grep")
+                            .value_name("TEXT")
+                            .help("Only calls whose inputs or outputs contain TEXT"),
+                    )
+                    .arg(
+                        Arg::new("slowest")
+                            .long("slowest")
+                            .value_name("N")
+                            .help("Show the N longest calls")
+                            .value_parser(value_parser!(usize)),
+                    )
+                    .arg(
+                        Arg::new("stats")
+                            .long("stats")
+                            .help("Show latency per method")
+                            .action(ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_name("FORMAT")
+                            .help("Print records as a table or as JSONL")
+                            .value_parser(["table", "jsonl"])
+                            .default_value("table"),
+                    ),
+            )
+    }
 
+    fn load(&mut self, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
         let config_file = matches.get_one::<String>("config_file").unwrap();
         self.config_file = config_file.to_string();
 
+        self.config_format = matches
+            .get_one::<String>("config_format")
+            .cloned()
+            .unwrap_or_default();
+
+        self.profile = matches
+            .get_one::<String>("profile")
+            .cloned()
+            .unwrap_or_default();
+
+        self.overrides = matches
+            .get_many::<String>("set")
+            .map(|x| x.cloned().collect())
+            .unwrap_or_default();
+
+        self.run_id = matches
+            .get_one::<String>("run_id")
+            .cloned()
+            .unwrap_or_default();
+
+        self.verbosity = matches.get_count("verbose") as i8 - matches.get_count("quiet") as i8;
+
+        self.command = Self::command(matches);
         self.version_info = VERSION.to_string();
 
         Ok(())
     }
+
+    fn command(matches: &ArgMatches) -> Option<Commands> {
+        match matches.subcommand() {
+            Some(("models", m)) => Some(Commands::Models {
+                probe: m.get_flag("probe"),
+                bench: m.get_one::<usize>("bench").copied(),
+            }),
+            Some(("secrets", m)) => {
+                let name = |m: &ArgMatches| m.get_one::<String>("name").unwrap().to_string();
+                let command = match m.subcommand() {
+                    Some(("set", m)) => SecretsCommands::Set { name: name(m) },
+                    Some(("get", m)) => SecretsCommands::Get { name: name(m) },
+                    Some(("rm", m)) => SecretsCommands::Rm { name: name(m) },
+                    _ => SecretsCommands::List,
+                };
+                Some(Commands::Secrets(command))
+            }
+            Some(("config", m)) => match m.subcommand() {
+                Some(("validate", _)) => Some(Commands::Config(ConfigCommands::Validate)),
+                Some(("show", m)) => Some(Commands::Config(ConfigCommands::Show {
+                    origin: m.get_flag("origin"),
+                })),
+                Some(("convert", m)) => Some(Commands::Config(ConfigCommands::Convert {
+                    to: m.get_one::<String>("to").cloned(),
+                    output: m.get_one::<String>("output").cloned(),
+                })),
+                _ => None,
+            },
+            Some(("init", m)) => Some(Commands::Init {
+                dir: m.get_one::<String>("dir").unwrap().to_string(),
+                interactive: m.get_flag("interactive"),
+            }),
+            Some(("run-task", m)) => Some(Commands::RunTask {
+                name: m.get_one::<String>("name").unwrap().to_string(),
+                vars: m
+                    .get_many::<String>("var")
+                    .map(|x| x.cloned().collect())
+                    .unwrap_or_default(),
+                input: m.get_one::<String>("input").cloned(),
+            }),
+            Some(("report", m)) => Some(Commands::Report {
+                run_dir: m.get_one::<String>("run_dir").unwrap().to_string(),
+                output: m.get_one::<String>("output").cloned(),
+            }),
+            Some(("logs", m)) => Some(Commands::Logs(LogsQuery {
+                path: m.get_one::<String>("path").unwrap().to_string(),
+                method: m.get_one::<String>("method").cloned(),
+                since: m.get_one::<String>("since").cloned(),
+                until: m.get_one::<String>("until").cloned(),
+                id: m.get_one::<String>("id").cloned(),
+                grep: m.get_one::<String>("grep").cloned(),
+                slowest: m.get_one::<usize>("slowest").copied(),
+                stats: m.get_flag("stats"),
+                format: m.get_one::<String>("format").unwrap().to_string(),
+            })),
+            _ => None,
+        }
+    }
 }
@@ -7,3 +7,294 @@ fn test_argument() {
     assert_eq!(args.config_file.is_empty(), true);
     assert_eq!(args.version_info.is_empty(), true);
 }
+
+#[test]
+fn test_parse_from() {
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args.parse_from(["promptx"]).is_ok());
+    assert_eq!(args.config_file, "config.yml");
+    assert_eq!(args.command, None);
+
+    assert!(args
+        .parse_from(["promptx", "models", "--probe", "-c", "prod.yml"])
+        .is_ok());
+    assert_eq!(args.config_file, "prod.yml");
+    assert_eq!(
+        args.command,
+        Some(super::arg::Commands::Models {
+            probe: true,
+            bench: None
+        })
+    );
+
+    assert!(args
+        .parse_from(["promptx", "models", "--bench", "5"])
+        .is_ok());
+    assert_eq!(
+        args.command,
+        Some(super::arg::Commands::Models {
+            probe: false,
+            bench: Some(5)
+        })
+    );
+
+    assert!(args
+        .parse_from(["promptx", "models", "--bench", "x"])
+        .is_err());
+}
+
+#[test]
+fn test_parse_secrets() {
+    use super::arg::{Commands, SecretsCommands};
+
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args
+        .parse_from(["promptx", "secrets", "set", "openai_prod"])
+        .is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::Secrets(SecretsCommands::Set {
+            name: "openai_prod".to_string()
+        }))
+    );
+
+    assert!(args
+        .parse_from(["promptx", "secrets", "rm", "openai_prod"])
+        .is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::Secrets(SecretsCommands::Rm {
+            name: "openai_prod".to_string()
+        }))
+    );
+
+    assert!(args.parse_from(["promptx", "secrets", "list"]).is_ok());
+    assert_eq!(args.command, Some(Commands::Secrets(SecretsCommands::List)));
+
+    assert!(args.parse_from(["promptx", "secrets", "get"]).is_err());
+    assert!(args
+        .parse_from(["promptx", "secrets", "set", "openai_prod", "sk-abc"])
+        .is_err());
+    assert!(args.parse_from(["promptx", "secrets"]).is_err());
+}
+
+#[test]
+fn test_parse_config() {
+    use super::arg::{Commands, ConfigCommands};
+
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args
+        .parse_from(["promptx", "config", "validate", "-c", "prod.yml"])
+        .is_ok());
+    assert_eq!(args.config_file, "prod.yml");
+    assert_eq!(
+        args.command,
+        Some(Commands::Config(ConfigCommands::Validate))
+    );
+
+    assert!(args
+        .parse_from([
+            "promptx",
+            "config",
+            "show",
+            "--origin",
+            "--set",
+            "optimizer.top_n=3",
+            "--set",
+            "llm[0].timeout=5",
+        ])
+        .is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::Config(ConfigCommands::Show { origin: true }))
+    );
+    assert_eq!(
+        args.overrides,
+        vec!["optimizer.top_n=3", "llm[0].timeout=5"]
+    );
+
+    assert!(args
+        .parse_from([
+            "promptx",
+            "config",
+            "convert",
+            "-c",
+            "config.json",
+            "--config-format",
+            "json",
+            "--to",
+            "toml",
+        ])
+        .is_ok());
+    assert_eq!(args.config_format, "json");
+    assert_eq!(
+        args.command,
+        Some(Commands::Config(ConfigCommands::Convert {
+            to: Some("toml".to_string()),
+            output: None
+        }))
+    );
+
+    assert!(args
+        .parse_from(["promptx", "config", "convert", "--to", "ini"])
+        .is_err());
+    assert!(args.parse_from(["promptx", "config"]).is_err());
+}
+
+#[test]
+fn test_parse_run_task() {
+    use super::arg::Commands;
+
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args
+        .parse_from([
+            "promptx",
+            "run-task",
+            "math",
+            "--var",
+            "question=1+1",
+            "--var",
+            "level=easy",
+            "--input",
+            "vars.json",
+        ])
+        .is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::RunTask {
+            name: "math".to_string(),
+            vars: vec!["question=1+1".to_string(), "level=easy".to_string()],
+            input: Some("vars.json".to_string()),
+        })
+    );
+
+    assert!(args.parse_from(["promptx", "run-task"]).is_err());
+}
+
+#[test]
+fn test_parse_init() {
+    use super::arg::Commands;
+
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args.parse_from(["promptx", "init"]).is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::Init {
+            dir: ".".to_string(),
+            interactive: false,
+        })
+    );
+
+    assert!(args.parse_from(["promptx", "init", "demo", "-i"]).is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::Init {
+            dir: "demo".to_string(),
+            interactive: true,
+        })
+    );
+}
+
+#[test]
+fn test_parse_run_id() {
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args.parse_from(["promptx", "--run-id", "baseline"]).is_ok());
+    assert_eq!(args.run_id, "baseline");
+
+    assert!(args.parse_from(["promptx"]).is_ok());
+    assert!(args.run_id.is_empty());
+}
+
+#[test]
+fn test_parse_verbosity() {
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args.parse_from(["promptx", "-vv", "models"]).is_ok());
+    assert_eq!(args.verbosity, 2);
+
+    assert!(args.parse_from(["promptx", "models", "-q"]).is_ok());
+    assert_eq!(args.verbosity, -1);
+
+    assert!(args.parse_from(["promptx"]).is_ok());
+    assert_eq!(args.verbosity, 0);
+}
+
+#[test]
+fn test_parse_report() {
+    use super::arg::Commands;
+
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args
+        .parse_from(["promptx", "report", "runs/gsm8k/run-1", "-o", "out"])
+        .is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::Report {
+            run_dir: "runs/gsm8k/run-1".to_string(),
+            output: Some("out".to_string()),
+        })
+    );
+
+    assert!(args.parse_from(["promptx", "report"]).is_err());
+}
+
+#[test]
+fn test_parse_logs() {
+    use super::arg::{Commands, LogsQuery};
+
+    let mut args = super::arg::Argument {
+        ..Default::default()
+    };
+
+    assert!(args
+        .parse_from([
+            "promptx",
+            "logs",
+            "runs/r1",
+            "--method",
+            "chat",
+            "--since",
+            "2h",
+            "--slowest",
+            "5",
+        ])
+        .is_ok());
+    assert_eq!(
+        args.command,
+        Some(Commands::Logs(LogsQuery {
+            path: "runs/r1".to_string(),
+            method: Some("chat".to_string()),
+            since: Some("2h".to_string()),
+            slowest: Some(5),
+            format: "table".to_string(),
+            ..Default::default()
+        }))
+    );
+
+    assert!(args
+        .parse_from(["promptx", "logs", "calls.jsonl", "--format", "csv"])
+        .is_err());
+}
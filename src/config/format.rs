@@ -0,0 +1,104 @@
+use super::validate::{Diagnostic, Positions};
+use serde_yaml::Value;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Yaml => "yaml",
+            Format::Json => "json",
+            Format::Toml => "toml",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "yaml" | "yml" => Some(Format::Yaml),
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref()
+            .extension()
+            .and_then(|x| x.to_str())
+            .and_then(Self::from_name)
+    }
+
+    // Every format is read into the same document tree, so merging and validation
+    // do not depend on the format
+    pub fn parse(&self, source: &str) -> Result<Value, Diagnostic> {
+        match self {
+            Format::Yaml => serde_yaml::from_str(source).map_err(|e| Diagnostic {
+                line: e.location().map(|x| x.line()),
+                column: e.location().map(|x| x.column()),
+                message: strip_location(&e.to_string()),
+                ..Default::default()
+            }),
+            Format::Json => serde_json::from_str(source).map_err(|e| Diagnostic {
+                line: Some(e.line()),
+                column: Some(e.column()),
+                message: strip_location(&e.to_string()),
+                ..Default::default()
+            }),
+            Format::Toml => toml::from_str(source).map_err(|e| {
+                let position = e.span().map(|x| position_of(source, x.start));
+                Diagnostic {
+                    line: position.map(|x| x.0),
+                    column: position.map(|x| x.1),
+                    message: e.message().to_string(),
+                    ..Default::default()
+                }
+            }),
+        }
+    }
+
+    pub fn positions(&self, source: &str) -> Positions {
+        match self {
+            // JSON is valid YAML, flow style included
+            Format::Yaml | Format::Json => Positions::parse(source),
+            Format::Toml => Positions::parse_toml(source),
+        }
+    }
+
+    pub fn render(&self, value: &Value) -> Result<String, String> {
+        match self {
+            Format::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+            Format::Json => serde_json::to_string_pretty(value)
+                .map(|x| x + "\n")
+                .map_err(|e| e.to_string()),
+            Format::Toml => toml::to_string(value).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// serde_yaml and serde_json append " at line X column Y" which is reported separately
+fn strip_location(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(idx) => message[..idx].to_string(),
+        None => message.to_string(),
+    }
+}
+
+// 1-based line and column of a byte offset
+pub fn position_of(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|x| *x != '\n').count() + 1;
+
+    (line, column)
+}
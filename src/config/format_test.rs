@@ -0,0 +1,66 @@
+use super::format::*;
+use serde_yaml::Value;
+
+#[test]
+fn test_format() {
+    assert_eq!(Format::from_path("config.yml"), Some(Format::Yaml));
+    assert_eq!(Format::from_path("config.yaml"), Some(Format::Yaml));
+    assert_eq!(
+        Format::from_path("/etc/promptx/config.JSON"),
+        Some(Format::Json)
+    );
+    assert_eq!(Format::from_path("config.toml"), Some(Format::Toml));
+    assert_eq!(Format::from_path("config.txt"), None);
+    assert_eq!(Format::from_name("toml"), Some(Format::Toml));
+    assert_eq!(Format::Json.to_string(), "json");
+}
+
+#[test]
+fn test_convert() {
+    let yaml = "llm:\n- name: openai\n  timeout: 30\nlimits:\n  top_n: 3\n";
+    let value = Format::Yaml.parse(yaml).unwrap();
+
+    for format in [Format::Yaml, Format::Json, Format::Toml] {
+        let rendered = format.render(&value).unwrap();
+        assert_eq!(format.parse(&rendered).unwrap(), value);
+    }
+
+    assert_eq!(Format::Yaml.render(&value).unwrap(), yaml);
+    assert!(Format::Toml
+        .render(&value)
+        .unwrap()
+        .contains("[[llm]]\nname = \"openai\"\ntimeout = 30\n"));
+}
+
+#[test]
+fn test_parse_errors() {
+    let d = Format::Json.parse("{\n  \"llm\": [\n}").err().unwrap();
+    assert_eq!(d.line, Some(3));
+    assert!(!d.message.contains(" at line "));
+
+    let d = Format::Toml.parse("[llm]\nname = \n").err().unwrap();
+    assert_eq!(d.line, Some(2));
+
+    assert_eq!(
+        Format::Toml.parse("").unwrap(),
+        Value::Mapping(Default::default())
+    );
+}
+
+#[test]
+fn test_positions() {
+    let toml =
+        "[[llm]]\nname = \"a\"\n\n[[llm]]\nname = \"b\"\n  api = \"x\"\n\n[optimizer]\ntop_n = 0\n";
+    let positions = Format::Toml.positions(toml);
+
+    assert_eq!(positions.get("llm[0].name"), Some((2, 8)));
+    assert_eq!(positions.get("llm[1].api"), Some((6, 9)));
+    assert_eq!(positions.get("optimizer.top_n"), Some((9, 9)));
+    assert_eq!(positions.get("optimizer.missing"), Some((8, 1)));
+
+    let json = "{\n  \"llm\": [\n    {\"name\": \"a\"}\n  ]\n}\n";
+    let positions = Format::Json.positions(json);
+    assert_eq!(positions.get("llm[0].name"), Some((3, 14)));
+
+    assert_eq!(position_of("ab\ncd", 4), (2, 2));
+}
@@ -0,0 +1,401 @@
+use super::config::{mask_key, mask_proxy};
+use super::format::Format;
+use super::validate::{Diagnostic, Positions};
+use crate::llm::utils::Table;
+use crate::secrets::secrets::SECRET_PREFIX;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+
+// Layers in the order they are merged, later layers win
+pub const DEFAULTS: &str = include_str!("defaults.yml");
+pub const DEFAULTS_LAYER: &str = "defaults";
+pub const SYSTEM_FILE: &str = "/etc/promptx/config.yml";
+pub const PROJECT_FILE: &str = ".promptx/config.yml";
+pub const OVERRIDES_LAYER: &str = "--set";
+pub const PROFILES_KEY: &str = "profiles";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+// Merged config document, with the layer which last set each node, keyed by path
+#[derive(Clone, Debug, Default)]
+pub struct Layers {
+    pub value: Value,
+    pub origins: BTreeMap<String, String>,
+    pub names: Vec<String>,
+    positions: HashMap<String, Positions>,
+    // File a layer was read from, when it is not the layer name itself
+    sources: HashMap<String, String>,
+}
+
+impl Layers {
+    pub fn load(&mut self, name: &str, source: &str) -> Result<(), Diagnostic> {
+        self.load_as(name, source, Format::Yaml)
+    }
+
+    pub fn load_as(&mut self, name: &str, source: &str, format: Format) -> Result<(), Diagnostic> {
+        let value = format.parse(source).map_err(|d| Diagnostic {
+            file: name.to_string(),
+            ..d
+        })?;
+
+        self.positions
+            .insert(name.to_string(), format.positions(source));
+        self.merge(name, value);
+
+        Ok(())
+    }
+
+    pub fn merge(&mut self, name: &str, value: Value) {
+        if !self.names.iter().any(|x| x == name) {
+            self.names.push(name.to_string());
+        }
+
+        // An empty file is a null document
+        if value.is_null() {
+            return;
+        }
+
+        if self.value.is_null() {
+            self.value = Value::Mapping(Mapping::new());
+        }
+
+        merge_node(&mut self.value, value, "", name, &mut self.origins);
+    }
+
+    // Overlay `profiles.<name>` on the merged document, the `profiles` map itself is
+    // dropped whether a profile is selected or not
+    pub fn profile(&mut self, name: &str) -> Result<(), String> {
+        let prefix = format!("{}.{}", PROFILES_KEY, name);
+        let file = self.origin(&prefix).unwrap_or_default().to_string();
+
+        let profiles = self
+            .value
+            .as_mapping_mut()
+            .and_then(|x| x.remove(PROFILES_KEY))
+            .unwrap_or_default();
+        self.origins
+            .retain(|k, _| k != PROFILES_KEY && !is_descendant(k, PROFILES_KEY));
+
+        if name.is_empty() {
+            return Ok(());
+        }
+
+        let overlay = profiles.get(name).cloned().ok_or_else(|| {
+            let available: Vec<String> = profiles
+                .as_mapping()
+                .map(|x| x.keys().map(key_str).collect())
+                .unwrap_or_default();
+            match available.is_empty() {
+                true => format!("unknown profile '{}', no profiles defined", name),
+                false => format!(
+                    "unknown profile '{}', expected one of {}",
+                    name,
+                    available.join(", ")
+                ),
+            }
+        })?;
+
+        // Positions of the profile values, relative to the profile
+        let nodes = self
+            .positions
+            .get(&file)
+            .map(|x| {
+                x.nodes
+                    .iter()
+                    .filter(|(k, _)| is_descendant(k, &prefix))
+                    .map(|(k, v)| (k[prefix.len()..].trim_start_matches('.').to_string(), *v))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let layer = format!("profile {}", name);
+        self.positions.insert(layer.clone(), Positions { nodes });
+        self.sources.insert(layer.clone(), file);
+        self.merge(&layer, overlay);
+
+        Ok(())
+    }
+
+    // Apply `path=value`, the value is parsed as YAML so numbers and booleans keep their type
+    pub fn set(&mut self, expr: &str) -> Result<(), String> {
+        let (path, value) = expr
+            .split_once('=')
+            .ok_or_else(|| format!("invalid override '{}', expected KEY=VALUE", expr))?;
+        let segments = parse_path(path)?;
+        let value = serde_yaml::from_str(value).unwrap_or(Value::String(value.to_string()));
+
+        if !self.names.iter().any(|x| x == OVERRIDES_LAYER) {
+            self.names.push(OVERRIDES_LAYER.to_string());
+        }
+        if self.value.is_null() {
+            self.value = Value::Mapping(Mapping::new());
+        }
+
+        let mut node = &mut self.value;
+        let mut current = String::new();
+
+        for (i, segment) in segments.iter().enumerate() {
+            current = join_path(&current, segment);
+            let last = i == segments.len() - 1;
+
+            node = match segment {
+                Segment::Key(key) => {
+                    if node.is_null() {
+                        *node = Value::Mapping(Mapping::new());
+                    }
+                    let map = node
+                        .as_mapping_mut()
+                        .ok_or_else(|| format!("{}: not a mapping", current))?;
+                    let key = Value::String(key.clone());
+                    if !map.contains_key(&key) {
+                        let empty = if last {
+                            Value::Null
+                        } else {
+                            Value::Mapping(Mapping::new())
+                        };
+                        map.insert(key.clone(), empty);
+                        self.origins
+                            .insert(current.clone(), OVERRIDES_LAYER.to_string());
+                    }
+                    map.get_mut(&key).unwrap()
+                }
+                Segment::Index(index) => node
+                    .as_sequence_mut()
+                    .ok_or_else(|| format!("{}: not a list", current))?
+                    .get_mut(*index)
+                    .ok_or_else(|| format!("{}: index out of range", current))?,
+            };
+        }
+
+        merge_node(node, value, &current, OVERRIDES_LAYER, &mut self.origins);
+
+        Ok(())
+    }
+
+    // Layer which set `path`, or its closest parent
+    pub fn origin(&self, path: &str) -> Option<&str> {
+        let mut path = path;
+        loop {
+            if let Some(origin) = self.origins.get(path) {
+                return Some(origin);
+            }
+            match path.rfind(['.', '[']) {
+                Some(idx) => path = &path[..idx],
+                None if !path.is_empty() => path = "",
+                None => return None,
+            }
+        }
+    }
+
+    pub fn diagnostic(&self, path: &str, message: &str) -> Diagnostic {
+        let layer = self.origin(path).unwrap_or_default();
+        let position = self.positions.get(layer).and_then(|x| x.get(path));
+
+        Diagnostic {
+            file: self.sources.get(layer).map_or(layer, |x| x).to_string(),
+            path: path.to_string(),
+            message: message.to_string(),
+            line: position.map(|x| x.0),
+            column: position.map(|x| x.1),
+        }
+    }
+
+    // Position of a layer in the merge order, used to sort diagnostics
+    pub fn rank(&self, name: &str) -> usize {
+        self.names
+            .iter()
+            .position(|x| x == name)
+            .unwrap_or(self.names.len())
+    }
+
+    // Merged document as YAML, or one row per value with the layer that set it
+    pub fn show(&self, origin: bool) -> Result<String, serde_yaml::Error> {
+        let mut value = self.value.clone();
+        mask_keys(&mut value);
+
+        if !origin {
+            return serde_yaml::to_string(&value);
+        }
+
+        let mut leaves = Vec::new();
+        leaves_of(&value, "", &mut leaves);
+
+        let mut table = Table::new(&["PATH", "VALUE", "ORIGIN"]);
+        for (path, value) in leaves {
+            table.add_row(vec![
+                path.clone(),
+                serde_json::to_string(value).unwrap_or_default(),
+                self.origin(&path).unwrap_or("-").to_string(),
+            ]);
+        }
+
+        Ok(table.render())
+    }
+}
+
+// Merge `overlay` into `base`: mappings are merged key by key, anything else
+// (lists included) replaces the value below it
+pub fn merge(base: &mut Value, overlay: Value) {
+    merge_node(base, overlay, "", "", &mut BTreeMap::new());
+}
+
+fn merge_node(
+    base: &mut Value,
+    overlay: Value,
+    path: &str,
+    layer: &str,
+    origins: &mut BTreeMap<String, String>,
+) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            origins.insert(path.to_string(), layer.to_string());
+            for (key, value) in overlay {
+                let child = join_path(path, &Segment::Key(key_str(&key)));
+                match base.get_mut(&key) {
+                    Some(existing) => merge_node(existing, value, &child, layer, origins),
+                    None => {
+                        record(&value, &child, layer, origins);
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => {
+            origins.retain(|k, _| !is_descendant(k, path));
+            record(&overlay, path, layer, origins);
+            *base = overlay;
+        }
+    }
+}
+
+fn record(value: &Value, path: &str, layer: &str, origins: &mut BTreeMap<String, String>) {
+    origins.insert(path.to_string(), layer.to_string());
+
+    match value {
+        Value::Mapping(map) => {
+            for (key, item) in map {
+                let child = join_path(path, &Segment::Key(key_str(key)));
+                record(item, &child, layer, origins);
+            }
+        }
+        Value::Sequence(seq) => {
+            for (i, item) in seq.iter().enumerate() {
+                record(item, &join_path(path, &Segment::Index(i)), layer, origins);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn leaves_of<'a>(value: &'a Value, path: &str, leaves: &mut Vec<(String, &'a Value)>) {
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            for (key, item) in map {
+                leaves_of(item, &join_path(path, &Segment::Key(key_str(key))), leaves);
+            }
+        }
+        Value::Sequence(seq) if !seq.is_empty() => {
+            for (i, item) in seq.iter().enumerate() {
+                leaves_of(item, &join_path(path, &Segment::Index(i)), leaves);
+            }
+        }
+        _ => leaves.push((path.to_string(), value)),
+    }
+}
+
+// Literal keys, proxy credentials and header values are masked, references to the
+// environment or the secrets store are kept
+fn mask_keys(value: &mut Value) {
+    match value {
+        Value::Mapping(map) => {
+            for (key, item) in map.iter_mut() {
+                match (key.as_str(), item) {
+                    (_, Value::String(s)) if is_reference(s) => {}
+                    (Some("key"), Value::String(s)) => *s = mask_key(s),
+                    (Some("proxy"), Value::String(s)) => *s = mask_proxy(s),
+                    (Some("headers"), Value::Mapping(headers)) => {
+                        for (_, header) in headers.iter_mut() {
+                            if let Value::String(s) = header {
+                                if !is_reference(s) {
+                                    *s = mask_key(s);
+                                }
+                            }
+                        }
+                    }
+                    (_, item) => mask_keys(item),
+                }
+            }
+        }
+        Value::Sequence(seq) => seq.iter_mut().for_each(mask_keys),
+        _ => {}
+    }
+}
+
+fn is_reference(s: &str) -> bool {
+    s.starts_with("${") || s.starts_with(SECRET_PREFIX)
+}
+
+// a.b[0].c
+pub fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(idx) => (&part[..idx], &part[idx..]),
+            None => (part, ""),
+        };
+
+        if key.is_empty() {
+            return Err(format!("invalid path '{}'", path));
+        }
+        segments.push(Segment::Key(key.to_string()));
+
+        while !rest.is_empty() {
+            let end = rest
+                .find(']')
+                .ok_or_else(|| format!("invalid path '{}'", path))?;
+            let index = rest[1..end]
+                .parse()
+                .map_err(|_| format!("invalid index in path '{}'", path))?;
+            segments.push(Segment::Index(index));
+            rest = &rest[end + 1..];
+            if !rest.is_empty() && !rest.starts_with('[') {
+                return Err(format!("invalid path '{}'", path));
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+fn join_path(path: &str, segment: &Segment) -> String {
+    match segment {
+        Segment::Key(key) if path.is_empty() => key.clone(),
+        Segment::Key(key) => format!("{}.{}", path, key),
+        Segment::Index(index) => format!("{}[{}]", path, index),
+    }
+}
+
+fn is_descendant(candidate: &str, path: &str) -> bool {
+    if path.is_empty() {
+        return !candidate.is_empty();
+    }
+
+    candidate.len() > path.len()
+        && candidate.starts_with(path)
+        && matches!(candidate.as_bytes()[path.len()], b'.' | b'[')
+}
+
+fn key_str(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.clone(),
+        other => serde_yaml::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+    }
+}
@@ -1,3 +1,15 @@
 pub mod config;
 #[cfg(test)]
 pub mod config_test;
+pub mod format;
+#[cfg(test)]
+pub mod format_test;
+pub mod layers;
+#[cfg(test)]
+pub mod layers_test;
+pub mod validate;
+#[cfg(test)]
+pub mod validate_test;
+pub mod watch;
+#[cfg(test)]
+pub mod watch_test;
@@ -0,0 +1,597 @@
+use super::config::{interpolate_each, ConfigData};
+use super::layers::Layers;
+use crate::llm::constants::Providers;
+use crate::llm::utils::parse_level;
+use crate::redact::redact::RULES;
+use crate::secrets::secrets::SECRET_PREFIX;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use url::Url;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub path: String,
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+// file:line:column: path: message, as compilers print it
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut location = Vec::new();
+        if !self.file.is_empty() {
+            location.push(self.file.clone());
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            location.push(line.to_string());
+            location.push(column.to_string());
+        }
+        if !location.is_empty() {
+            write!(f, "{}: ", location.join(":"))?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+// Check a single config document and report every problem found, not only the first one
+pub fn validate(source: &str) -> Vec<Diagnostic> {
+    let mut layers = Layers::default();
+
+    match layers.load("", source) {
+        Ok(()) => validate_layers(&layers),
+        Err(d) => vec![d],
+    }
+}
+
+// Check the merged document. Positions come from the layer which set each value,
+// so they point at the field as written.
+pub fn validate_layers(layers: &Layers) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    let mut value = layers.value.clone();
+    interpolate_each(&mut value, "", &mut |path, e| {
+        diagnostics.push(layers.diagnostic(path, &e))
+    });
+
+    if let Some(parsed) = deserialize(value, layers, &mut diagnostics) {
+        check(&parsed, layers, &mut diagnostics);
+    }
+
+    diagnostics.sort_by_key(|x| (layers.rank(&x.file), x.line, x.column));
+
+    diagnostics
+}
+
+// Sections of the config which deserialized. The others are None, so a structural
+// error in one section does not hide the problems of the rest.
+#[derive(Default)]
+struct Parsed {
+    config: ConfigData,
+    // Position in the document of each entry of config.llm
+    llm_index: Vec<usize>,
+    // The llms which failed to deserialize with their name, references to them are not
+    // reported again
+    broken_llm: Vec<Option<String>>,
+}
+
+fn deserialize(value: Value, layers: &Layers, diagnostics: &mut Vec<Diagnostic>) -> Option<Parsed> {
+    let Value::Mapping(map) = &value else {
+        section::<ConfigData>(value, "", layers, diagnostics);
+        return None;
+    };
+
+    let mut parsed = Parsed::default();
+    match map.get("llm") {
+        Some(Value::Sequence(items)) => {
+            for (i, item) in items.iter().enumerate() {
+                match section(item.clone(), &format!("llm[{}]", i), layers, diagnostics) {
+                    Some(llm) => {
+                        parsed.config.llm.push(llm);
+                        parsed.llm_index.push(i);
+                    }
+                    None => parsed.broken_llm.push(
+                        item.get("name")
+                            .and_then(|x| x.as_str())
+                            .map(|x| x.to_string()),
+                    ),
+                }
+            }
+        }
+        Some(other) => match section(other.clone(), "llm", layers, diagnostics) {
+            Some(llm) => {
+                parsed.config.llm = llm;
+                parsed.llm_index = (0..parsed.config.llm.len()).collect();
+            }
+            None => parsed.broken_llm.push(None),
+        },
+        // Reported by check
+        None => {}
+    }
+
+    let config = &mut parsed.config;
+    let get = |key: &str| map.get(key).cloned().filter(|x| !x.is_null());
+    config.setup = get("setup").and_then(|x| section(x, "setup", layers, diagnostics));
+    config.limits = get("limits").and_then(|x| section(x, "limits", layers, diagnostics));
+    config.optimizer = get("optimizer").and_then(|x| section(x, "optimizer", layers, diagnostics));
+    config.prompt_library =
+        get("prompt_library").and_then(|x| section(x, "prompt_library", layers, diagnostics));
+    config.logging = get("logging").and_then(|x| section(x, "logging", layers, diagnostics));
+    config.tracing = get("tracing").and_then(|x| section(x, "tracing", layers, diagnostics));
+    config.redact = get("redact").and_then(|x| section(x, "redact", layers, diagnostics));
+    config.pricing = get("pricing").and_then(|x| section(x, "pricing", layers, diagnostics));
+
+    Some(parsed)
+}
+
+// Deserialize the value at `path`, None after reporting the error
+fn section<T: DeserializeOwned>(
+    value: Value,
+    path: &str,
+    layers: &Layers,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<T> {
+    match serde_path_to_error::deserialize(value) {
+        Ok(x) => Some(x),
+        Err(e) => {
+            let path = match e.path().to_string() {
+                p if p == "." => path.to_string(),
+                p if path.is_empty() => p,
+                p if p.starts_with('[') => format!("{}{}", path, p),
+                p => format!("{}.{}", path, p),
+            };
+            diagnostics.push(layers.diagnostic(&path, &e.inner().to_string()));
+            None
+        }
+    }
+}
+
+fn check(parsed: &Parsed, layers: &Layers, diagnostics: &mut Vec<Diagnostic>) {
+    let config = &parsed.config;
+    let mut push = |path: String, message: String| {
+        diagnostics.push(layers.diagnostic(&path, &message));
+    };
+
+    if config.llm.is_empty() && parsed.broken_llm.is_empty() {
+        push(
+            "llm".to_string(),
+            "at least one llm is required".to_string(),
+        );
+    }
+
+    let mut names = HashSet::new();
+
+    for (i, llm) in parsed.llm_index.iter().zip(&config.llm) {
+        let path = format!("llm[{}]", i);
+
+        if llm.name.is_empty() {
+            push(format!("{}.name", path), "must not be empty".to_string());
+        } else if !names.insert(llm.name.as_str()) {
+            push(
+                format!("{}.name", path),
+                format!("duplicate name '{}'", llm.name),
+            );
+        }
+
+        if let Err(e) = check_url(&llm.api) {
+            push(format!("{}.api", path), e);
+        }
+
+        if llm.endpoint.is_empty() {
+            push(
+                format!("{}.endpoint", path),
+                "must not be empty".to_string(),
+            );
+        }
+
+        if !Providers::ALL.contains(&llm.provider()) {
+            let field = if llm.provider.is_some() {
+                "provider"
+            } else {
+                "name"
+            };
+            push(
+                format!("{}.{}", path, field),
+                format!(
+                    "unknown provider '{}', expected one of {}",
+                    llm.provider(),
+                    Providers::ALL.join(", ")
+                ),
+            );
+        }
+
+        if !llm.key.is_empty() && llm.key_file.is_some() {
+            push(
+                format!("{}.key_file", path),
+                "key and key_file are mutually exclusive".to_string(),
+            );
+        }
+
+        for (field, seconds) in [
+            ("connect_timeout", llm.connect_timeout),
+            ("timeout", llm.timeout),
+        ] {
+            if seconds == Some(0) {
+                push(
+                    format!("{}.{}", path, field),
+                    "must be greater than 0".to_string(),
+                );
+            }
+        }
+
+        if let Some(proxy) = &llm.proxy {
+            if let Err(e) = check_url(proxy) {
+                push(format!("{}.proxy", path), e);
+            }
+        }
+
+        if llm.client_cert.is_some() != llm.client_key.is_some() {
+            push(
+                path.clone(),
+                "client_cert and client_key must be set together".to_string(),
+            );
+        }
+    }
+
+    names.extend(parsed.broken_llm.iter().flatten().map(|x| x.as_str()));
+
+    let mut reference = |path: String, name: &str| {
+        if !names.contains(name) {
+            push(path, format!("unknown llm '{}'", name));
+        }
+    };
+
+    if let Some(setup) = &config.setup {
+        reference(
+            "setup.assistant_llm.prompt_opt".to_string(),
+            &setup.assistant_llm.prompt_opt,
+        );
+    }
+
+    if let Some(optimizer) = &config.optimizer {
+        reference(
+            "optimizer.unique_model_id".to_string(),
+            &optimizer.unique_model_id,
+        );
+    }
+
+    if let Some(library) = &config.prompt_library {
+        for (mode, tasks) in [
+            ("chat", &library.mode.chat),
+            ("generation", &library.mode.generation),
+        ] {
+            for (i, task) in tasks.iter().enumerate() {
+                if let Some(name) = &task.llm_model_id {
+                    reference(
+                        format!("prompt_library.mode.{}[{}].llm_model_id", mode, i),
+                        name,
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(logging) = &config.logging {
+        let levels = std::iter::once(("logging.level".to_string(), &logging.level)).chain(
+            logging
+                .modules
+                .iter()
+                .map(|(module, level)| (format!("logging.modules.{}", module), level)),
+        );
+        for (path, level) in levels {
+            if let Err(e) = parse_level(level) {
+                push(path, e.to_string());
+            }
+        }
+
+        if logging.file && logging.file_name.is_empty() {
+            push(
+                "logging.file_name".to_string(),
+                "must not be empty".to_string(),
+            );
+        }
+
+        if logging.max_size_mb == 0 {
+            push(
+                "logging.max_size_mb".to_string(),
+                "must be greater than 0".to_string(),
+            );
+        }
+    }
+
+    if let Some(endpoint) = config.tracing.as_ref().and_then(|x| x.endpoint.as_ref()) {
+        if let Err(e) = check_url(endpoint) {
+            push("tracing.endpoint".to_string(), e);
+        }
+    }
+
+    for (model, price) in config.pricing.iter().flatten() {
+        for (name, value) in [("input", price.input), ("output", price.output)] {
+            if value < 0.0 {
+                push(
+                    format!("pricing.{}.{}", model, name),
+                    "must not be negative".to_string(),
+                );
+            }
+        }
+    }
+
+    if let Some(redact) = &config.redact {
+        for (i, rule) in redact.rules.iter().enumerate() {
+            if !RULES.iter().any(|(name, _)| name == rule) {
+                let names: Vec<&str> = RULES.iter().map(|(name, _)| *name).collect();
+                push(
+                    format!("redact.rules[{}]", i),
+                    format!(
+                        "unknown rule '{}', expected one of {}",
+                        rule,
+                        names.join(", ")
+                    ),
+                );
+            }
+        }
+
+        for (i, pattern) in redact.patterns.iter().enumerate() {
+            if pattern.name.is_empty() {
+                push(
+                    format!("redact.patterns[{}].name", i),
+                    "must not be empty".to_string(),
+                );
+            }
+            if let Err(e) = Regex::new(&pattern.regex) {
+                push(
+                    format!("redact.patterns[{}].regex", i),
+                    format!(
+                        "invalid regex: {}",
+                        e.to_string().lines().last().unwrap_or_default()
+                    ),
+                );
+            }
+        }
+    }
+
+    let mut counts = Vec::new();
+    if let Some(limits) = &config.limits {
+        counts.extend([
+            (
+                "limits.max_num_requests_in_time_window",
+                limits.max_num_requests_in_time_window,
+            ),
+            (
+                "limits.time_window_length_in_seconds",
+                limits.time_window_length_in_seconds,
+            ),
+        ]);
+    }
+    if let Some(optimizer) = &config.optimizer {
+        counts.extend([
+            ("optimizer.style_variation", optimizer.style_variation),
+            (
+                "optimizer.questions_batch_size",
+                optimizer.questions_batch_size,
+            ),
+            ("optimizer.min_correct_count", optimizer.min_correct_count),
+            ("optimizer.max_eval_batches", optimizer.max_eval_batches),
+            ("optimizer.top_n", optimizer.top_n),
+            ("optimizer.seen_set_size", optimizer.seen_set_size),
+            ("optimizer.num_completions", optimizer.num_completions),
+        ]);
+        // Zero disables these steps
+        for (path, value) in [
+            ("optimizer.mutation_rounds", optimizer.mutation_rounds),
+            (
+                "optimizer.mutate_refine_iterations",
+                optimizer.mutate_refine_iterations,
+            ),
+            (
+                "optimizer.refine_task_eg_iterations",
+                optimizer.refine_task_eg_iterations,
+            ),
+            ("optimizer.few_shot_count", optimizer.few_shot_count),
+            ("optimizer.num_train_examples", optimizer.num_train_examples),
+        ] {
+            if value < 0 {
+                push(path.to_string(), "must not be negative".to_string());
+            }
+        }
+    }
+    if config
+        .optimizer
+        .as_ref()
+        .is_some_and(|x| x.temperature < 0.0)
+    {
+        push(
+            "optimizer.temperature".to_string(),
+            "must not be negative".to_string(),
+        );
+    }
+    for (path, value) in counts {
+        if value <= 0 {
+            push(path.to_string(), "must be greater than 0".to_string());
+        }
+    }
+}
+
+fn check_url(value: &str) -> Result<(), String> {
+    if value.starts_with(SECRET_PREFIX) {
+        return Ok(());
+    }
+
+    match Url::parse(value) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+        Ok(url) => Err(format!("unsupported scheme '{}'", url.scheme())),
+        Err(e) => Err(format!("invalid url '{}': {}", value, e)),
+    }
+}
+
+// Line and column of every node in the document, keyed by path, e.g. llm[0].api
+#[derive(Clone, Debug, Default)]
+pub struct Positions {
+    pub nodes: HashMap<String, (usize, usize)>,
+}
+
+impl Positions {
+    pub fn parse(source: &str) -> Self {
+        let mut receiver = PositionReceiver::default();
+        let mut parser = Parser::new_from_str(source);
+
+        // Syntax errors are reported by serde_yaml
+        let _ = parser.load(&mut receiver, false);
+
+        Positions {
+            nodes: receiver.nodes,
+        }
+    }
+
+    // Approximate positions for TOML from table headers and `key = value` lines,
+    // tables nested in arrays of tables are not tracked
+    pub fn parse_toml(source: &str) -> Self {
+        let mut nodes = HashMap::new();
+        let mut arrays: HashMap<String, usize> = HashMap::new();
+        let mut prefix = String::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let trimmed = line.trim_start();
+            let column = line.chars().count() - trimmed.chars().count() + 1;
+            let line = i + 1;
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = trimmed.strip_prefix("[[") {
+                let name = toml_key(header.split("]]").next().unwrap_or_default());
+                let count = arrays.entry(name.clone()).or_insert(0);
+                prefix = format!("{}[{}]", name, count);
+                *count += 1;
+                nodes.entry(name).or_insert((line, column));
+                nodes.insert(prefix.clone(), (line, column));
+            } else if let Some(header) = trimmed.strip_prefix('[') {
+                prefix = toml_key(header.split(']').next().unwrap_or_default());
+                nodes.insert(prefix.clone(), (line, column));
+            } else if let Some((key, value)) = trimmed.split_once('=') {
+                let key = toml_key(key);
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                let offset = trimmed.chars().count() - value.trim_start().chars().count();
+                nodes.insert(path, (line, column + offset));
+            }
+        }
+
+        Positions { nodes }
+    }
+
+    // Position of `path`, or of its closest parent when the field itself is missing
+    pub fn get(&self, path: &str) -> Option<(usize, usize)> {
+        let mut path = path;
+        loop {
+            if let Some(position) = self.nodes.get(path) {
+                return Some(*position);
+            }
+            match path.rfind(['.', '[']) {
+                Some(idx) => path = &path[..idx],
+                None => return None,
+            }
+        }
+    }
+}
+
+// `a . "b"` -> a.b
+fn toml_key(key: &str) -> String {
+    key.split('.')
+        .map(|x| x.trim().trim_matches(['"', '\'']))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+#[derive(Default)]
+struct PositionReceiver {
+    nodes: HashMap<String, (usize, usize)>,
+    stack: Vec<Frame>,
+}
+
+impl PositionReceiver {
+    // Path of the node starting now, None when the event is a mapping key
+    fn next_path(&mut self, scalar: Option<&str>) -> Option<String> {
+        match self.stack.last_mut() {
+            None => Some(String::new()),
+            Some(Frame::Sequence { path, index }) => {
+                let next = format!("{}[{}]", path, index);
+                *index += 1;
+                Some(next)
+            }
+            Some(Frame::Mapping { path, key }) => match key.take() {
+                Some(key) if path.is_empty() => Some(key),
+                Some(key) => Some(format!("{}.{}", path, key)),
+                None => {
+                    *key = scalar.map(|x| x.to_string());
+                    None
+                }
+            },
+        }
+    }
+}
+
+impl MarkedEventReceiver for PositionReceiver {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let position = (mark.line(), mark.col() + 1);
+
+        match ev {
+            Event::Scalar(value, ..) => {
+                let is_key = matches!(self.stack.last(), Some(Frame::Mapping { key: None, .. }));
+                if is_key {
+                    let parent = match self.stack.last() {
+                        Some(Frame::Mapping { path, .. }) => path.clone(),
+                        _ => String::new(),
+                    };
+                    // The mapping start mark points past the first key, use the key instead
+                    self.nodes.entry(parent.clone()).or_insert(position);
+                    let path = if parent.is_empty() {
+                        value.clone()
+                    } else {
+                        format!("{}.{}", parent, value)
+                    };
+                    self.nodes.insert(path, position);
+                    self.next_path(Some(&value));
+                } else if let Some(path) = self.next_path(None) {
+                    self.nodes.insert(path, position);
+                }
+            }
+            Event::Alias(_) => {
+                if let Some(path) = self.next_path(None) {
+                    self.nodes.insert(path, position);
+                }
+            }
+            Event::MappingStart(..) => {
+                let path = self.next_path(None).unwrap_or_default();
+                self.stack.push(Frame::Mapping { path, key: None });
+            }
+            Event::SequenceStart(..) => {
+                let path = self.next_path(None).unwrap_or_default();
+                self.nodes.entry(path.clone()).or_insert(position);
+                self.stack.push(Frame::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use super::validate::*;
+
+#[test]
+fn test_validate() {
+    let source = "llm:
+  - name: openai
+    api: not a url
+    key: sk-proj
+    endpoint: gpt-4o
+    timeout: 0
+  - name: openai
+    api: ftp://example.com
+    endpoint: gpt-4o
+  - name: mistral
+    api: https://api.mistral.ai/v1/chat/completions
+    key: ${PROMPTX_VALIDATE_TEST_UNSET}
+    endpoint: mistral-large
+";
+
+    let diagnostics = validate(source);
+    let rendered: Vec<String> = diagnostics.iter().map(|x| x.to_string()).collect();
+
+    assert_eq!(
+        rendered,
+        vec![
+            "3:10: llm[0].api: invalid url 'not a url': relative URL without a base",
+            "6:14: llm[0].timeout: must be greater than 0",
+            "7:11: llm[1].name: duplicate name 'openai'",
+            "8:10: llm[1].api: unsupported scheme 'ftp'",
+            "10:11: llm[2].name: unknown provider 'mistral', expected one of doubao, openai",
+            "12:10: llm[2].key: environment variable PROMPTX_VALIDATE_TEST_UNSET is not set",
+        ]
+    );
+}
+
+#[test]
+fn test_validate_syntax() {
+    let diagnostics = validate("llm:\n  - name: openai\n    api: https://api.openai.com\n");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].path, "llm[0]");
+    assert_eq!(diagnostics[0].message, "missing field `endpoint`");
+    assert_eq!(diagnostics[0].line, Some(2));
+
+    let diagnostics = validate("llm: [\n");
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].line.is_some());
+
+    let mut d = diagnostics[0].clone();
+    d.file = "config.yml".to_string();
+    assert_eq!(d.to_string(), format!("config.yml:{}", diagnostics[0]));
+}
+
+#[test]
+fn test_validate_ok() {
+    let source = "llm:
+  - name: openai
+    api: https://api.openai.com/v1/chat/completions
+    key: secret:openai_prod
+    endpoint: gpt-4o
+    proxy: secret:proxy
+  - name: openai-mini
+    provider: openai
+    api: https://api.openai.com/v1/chat/completions
+    key: sk-proj
+    endpoint: gpt-4o-mini
+    timeout: 30
+";
+
+    assert_eq!(validate(source), vec![]);
+}
+
+#[test]
+fn test_positions() {
+    let positions = Positions::parse("llm:\n  - name: a\n    headers:\n      X-Id: b\n");
+
+    assert_eq!(positions.get("llm[0].name"), Some((2, 11)));
+    assert_eq!(positions.get("llm[0].headers.X-Id"), Some((4, 13)));
+    assert_eq!(positions.get("llm[0].api"), Some((2, 5)));
+    assert_eq!(positions.get("missing"), None);
+}
+
+#[test]
+fn test_validate_references() {
+    let source = "llm:
+  - name: openai
+    api: https://api.openai.com/v1/chat/completions
+    key: sk-proj
+    endpoint: gpt-4o
+setup:
+  assistant_llm:
+    prompt_opt: gpt-5
+  dir_info:
+    base_dir: logs
+    log_dir_name: glue_logs
+  experiment_name: test
+  mode: offline
+  description: test
+limits:
+  max_num_requests_in_time_window: 0
+  time_window_length_in_seconds: 60
+prompt_library:
+  mode:
+    chat:
+      - name: math
+        prompt_template: '{question}'
+        llm_request_type: chat
+        llm_model_id: openai
+      - name: summary
+        prompt_template: '{text}'
+        llm_request_type: chat
+        llm_model_id: doubao
+    generation: []
+";
+
+    let rendered: Vec<String> = validate(source).iter().map(|x| x.to_string()).collect();
+
+    assert_eq!(
+        rendered,
+        vec![
+            "8:17: setup.assistant_llm.prompt_opt: unknown llm 'gpt-5'",
+            "16:36: limits.max_num_requests_in_time_window: must be greater than 0",
+            "28:23: prompt_library.mode.chat[1].llm_model_id: unknown llm 'doubao'",
+        ]
+    );
+}
+
+#[test]
+fn test_validate_sections() {
+    let source = "llm:
+  - name: openai
+    api: https://api.openai.com/v1/chat/completions
+  - name: doubao
+    api: not a url
+    endpoint: doubao-pro
+optimizer:
+  unique_model_id: openai
+logging:
+  max_size_mb: 0
+prompt_library:
+  mode:
+    chat:
+      - name: math
+        prompt_template: '{question}'
+        llm_request_type: chat
+        llm_model_id: openai
+    generation: []
+";
+
+    let rendered: Vec<String> = validate(source).iter().map(|x| x.to_string()).collect();
+
+    assert_eq!(rendered.len(), 4);
+    assert_eq!(rendered[0], "2:5: llm[0]: missing field `endpoint`");
+    assert_eq!(
+        rendered[1],
+        "5:10: llm[1].api: invalid url 'not a url': relative URL without a base"
+    );
+    assert_eq!(
+        rendered[2],
+        "7:1: optimizer: missing field `style_variation`"
+    );
+    assert_eq!(
+        rendered[3],
+        "10:16: logging.max_size_mb: must be greater than 0"
+    );
+}
+
+#[test]
+fn test_validate_logging() {
+    let source = "llm:
+  - name: openai
+    api: https://api.openai.com/v1/chat/completions
+    key: sk-proj
+    endpoint: gpt-4o
+logging:
+  level: loud
+  modules:
+    promptx::llm: debug
+    reqwest: chatty
+  max_size_mb: 0
+";
+
+    let rendered: Vec<String> = validate(source).iter().map(|x| x.to_string()).collect();
+
+    assert_eq!(
+        rendered,
+        vec![
+            "7:10: logging.level: invalid log level 'loud', expected one of off, error, warn, info, debug, trace",
+            "10:14: logging.modules.reqwest: invalid log level 'chatty', expected one of off, error, warn, info, debug, trace",
+            "11:16: logging.max_size_mb: must be greater than 0",
+        ]
+    );
+
+    let diagnostics = validate(&source.replace("level: loud", "format: xml"));
+    assert!(diagnostics[0].message.starts_with("unknown variant `xml`"));
+}
+
+#[test]
+fn test_validate_tracing() {
+    let source = "llm:
+  - name: openai
+    api: https://api.openai.com/v1/chat/completions
+    key: sk-proj
+    endpoint: gpt-4o
+tracing:
+  endpoint: localhost:4318
+";
+
+    let rendered: Vec<String> = validate(source).iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        rendered,
+        vec!["7:13: tracing.endpoint: unsupported scheme 'localhost'"]
+    );
+
+    assert_eq!(
+        validate(&source.replace("localhost:4318", "http://localhost:4318")),
+        vec![]
+    );
+}
+
+#[test]
+fn test_validate_redact() {
+    let source = "llm:
+  - name: openai
+    api: https://api.openai.com/v1/chat/completions
+    key: sk-proj
+    endpoint: gpt-4o
+redact:
+  rules: [email, address]
+  patterns:
+    - name: employee_id
+      regex: EMP-[0-9
+";
+
+    let rendered: Vec<String> = validate(source).iter().map(|x| x.to_string()).collect();
+    assert_eq!(
+        rendered,
+        vec![
+            "7:18: redact.rules[1]: unknown rule 'address', expected one of email, phone, ssn, credit_card, token",
+            "10:14: redact.patterns[0].regex: invalid regex: error: unclosed character class",
+        ]
+    );
+
+    assert_eq!(
+        validate(&source.replace(", address", "").replace("[0-9", "[0-9]+")),
+        vec![]
+    );
+}
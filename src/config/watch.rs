@@ -0,0 +1,147 @@
+use super::config::Config;
+use super::layers::{PROJECT_FILE, SYSTEM_FILE};
+use crate::llm::llm::LLM;
+use log::{info, warn};
+use std::error::Error;
+use std::fs;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::task::JoinHandle;
+
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+// LLM state shared by running jobs. A reload replaces it as a whole, requests keep
+// the state they started with until they finish.
+#[derive(Clone, Default)]
+pub struct Shared {
+    inner: Arc<RwLock<Arc<LLM>>>,
+}
+
+impl Shared {
+    pub fn new(llm: LLM) -> Self {
+        Shared {
+            inner: Arc::new(RwLock::new(Arc::new(llm))),
+        }
+    }
+
+    pub fn current(&self) -> Arc<LLM> {
+        self.inner.read().unwrap().clone()
+    }
+
+    pub fn swap(&self, llm: LLM) {
+        *self.inner.write().unwrap() = Arc::new(llm);
+    }
+}
+
+// Re-reads the config when one of its files changes or on SIGHUP, an invalid config
+// is rejected and the current one kept
+pub struct Watcher {
+    pub config: Config,
+    pub shared: Shared,
+    pub interval: Duration,
+    stamps: Vec<Option<SystemTime>>,
+}
+
+impl Watcher {
+    pub fn new(config: Config, shared: Shared) -> Self {
+        let mut watcher = Watcher {
+            config,
+            shared,
+            interval: WATCH_INTERVAL,
+            stamps: Vec::new(),
+        };
+        watcher.stamps = watcher.stamps();
+
+        watcher
+    }
+
+    fn stamps(&self) -> Vec<Option<SystemTime>> {
+        [self.config.config_file.as_str(), PROJECT_FILE, SYSTEM_FILE]
+            .iter()
+            .map(|x| fs::metadata(x).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    // Whether a file was modified, created or removed since the last call
+    pub fn changed(&mut self) -> bool {
+        let stamps = self.stamps();
+        let changed = stamps != self.stamps;
+        self.stamps = stamps;
+
+        changed
+    }
+
+    // Files are read and secrets decrypted on the blocking pool. Secrets come from the
+    // store opened by the first build, a reload never asks for the passphrase.
+    pub async fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+        let config = self.config.clone();
+        let (config, llm) =
+            tokio::task::spawn_blocking(move || Self::load(config).map_err(|e| e.to_string()))
+                .await??;
+
+        self.shared.swap(llm);
+        self.config = config;
+
+        Ok(())
+    }
+
+    fn load(mut config: Config) -> Result<(Config, LLM), Box<dyn Error>> {
+        config.build()?;
+        let llm = LLM::new(config.config_data.clone())?;
+
+        Ok((config, llm))
+    }
+
+    pub async fn run(mut self) {
+        let mut ticker = tokio::time::interval(self.interval);
+        let mut hangup = Hangup::new();
+
+        loop {
+            let forced = tokio::select! {
+                _ = ticker.tick() => false,
+                _ = hangup.recv() => true,
+            };
+
+            if !self.changed() && !forced {
+                continue;
+            }
+
+            match self.reload().await {
+                Ok(()) => info!("reloaded config {}", self.config.config_file),
+                Err(err) => warn!(
+                    "rejected config {}, keeping the current one: {}",
+                    self.config.config_file, err
+                ),
+            }
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+}
+
+// SIGHUP stream, pending forever where signals are not available
+struct Hangup {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangup {
+    fn new() -> Self {
+        Hangup {
+            #[cfg(unix)]
+            signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok(),
+        }
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.signal.as_mut() {
+            signal.recv().await;
+            return;
+        }
+
+        std::future::pending::<()>().await
+    }
+}
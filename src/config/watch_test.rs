@@ -0,0 +1,109 @@
+use super::config::Config;
+use super::watch::*;
+use crate::llm::llm::LLM;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+static STAMP: AtomicU64 = AtomicU64::new(1_700_000_000);
+
+fn write(path: &Path, requests: &str) {
+    let source = format!(
+        "llm:
+  - name: openai
+    api: http://127.0.0.1:1/v1/chat/completions
+    key: sk-proj
+    endpoint: gpt-4o
+limits:
+  max_num_requests_in_time_window: {}
+  time_window_length_in_seconds: 60
+",
+        requests
+    );
+    fs::write(path, source).unwrap();
+
+    // Every write gets a distinct mtime, coarse timestamps must not hide an edit
+    let stamp = SystemTime::UNIX_EPOCH + Duration::from_secs(STAMP.fetch_add(1, Ordering::SeqCst));
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(stamp)
+        .unwrap();
+}
+
+fn requests(llm: &LLM) -> i32 {
+    llm.config
+        .limits
+        .as_ref()
+        .unwrap()
+        .max_num_requests_in_time_window
+}
+
+fn watcher(path: &Path) -> Watcher {
+    let mut config = Config {
+        config_file: path.display().to_string(),
+        version_info: "1.0.0".to_string(),
+        ..Default::default()
+    };
+    config.build().unwrap();
+
+    let shared = Shared::new(LLM::new(config.config_data.clone()).unwrap());
+    Watcher::new(config, shared)
+}
+
+#[tokio::test]
+async fn test_reload() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yml");
+    write(&path, "10");
+
+    let mut w = watcher(&path);
+    assert!(!w.changed());
+
+    let before = w.shared.current();
+    write(&path, "20");
+    assert!(w.changed());
+    assert!(w.reload().await.is_ok());
+
+    // In-flight users keep the state they started with
+    assert_eq!(requests(&before), 10);
+    assert_eq!(requests(&w.shared.current()), 20);
+
+    write(&path, "0");
+    assert!(w.changed());
+    assert!(w.reload().await.is_err());
+    assert_eq!(requests(&w.shared.current()), 20);
+    assert_eq!(
+        w.config
+            .config_data
+            .limits
+            .unwrap()
+            .max_num_requests_in_time_window,
+        20
+    );
+}
+
+#[tokio::test]
+async fn test_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yml");
+    write(&path, "10");
+
+    let mut w = watcher(&path);
+    w.interval = Duration::from_millis(10);
+    let shared = w.shared.clone();
+    let handle = w.spawn();
+
+    write(&path, "30");
+    for _ in 0..100 {
+        if requests(&shared.current()) == 30 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    handle.abort();
+
+    assert_eq!(requests(&shared.current()), 30);
+}
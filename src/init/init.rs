@@ -0,0 +1,130 @@
+use crate::llm::base::DEFAULT_DATASET;
+use crate::llm::constants::Providers;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+pub const CONFIG_TEMPLATE: &str = include_str!("templates/config.yml");
+pub const PROMPT_POOL_TEMPLATE: &str = include_str!("templates/prompt_pool.yml");
+pub const OPTIMIZER_TEMPLATE: &str = include_str!("templates/critique_n_refine.yml");
+pub const DATASET_TEMPLATE: &str = include_str!("templates/dataset.jsonl");
+
+pub const CONFIG_FILE: &str = "config.yml";
+pub const PROMPT_POOL_FILE: &str = "prompt_pool.yml";
+pub const OPTIMIZER_FILE: &str = "critique_n_refine.yml";
+pub const DATASET_FILE: &str = DEFAULT_DATASET;
+
+// Starter project written by `promptx init`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Init {
+    pub provider: String,
+    pub key_env: String,
+}
+
+impl Default for Init {
+    fn default() -> Self {
+        Init::new(Providers::OPENAI)
+    }
+}
+
+impl Init {
+    pub fn new(provider: &str) -> Self {
+        Init {
+            provider: provider.to_string(),
+            key_env: format!("{}_API_KEY", provider.to_uppercase().replace('-', "_")),
+        }
+    }
+
+    // Ask for the provider and the key variable, an empty answer keeps the default
+    pub fn ask<R: BufRead, W: Write>(
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut answer = |question: &str, default: &str| -> Result<String, Box<dyn Error>> {
+            write!(output, "{} [{}]: ", question, default)?;
+            output.flush()?;
+            let mut line = String::new();
+            input.read_line(&mut line)?;
+            let line = line.trim();
+            Ok(if line.is_empty() { default } else { line }.to_string())
+        };
+
+        let provider = answer(
+            &format!("Provider ({})", Providers::ALL.join(", ")),
+            Providers::OPENAI,
+        )?;
+        if !Providers::ALL.contains(&provider.as_str()) {
+            return Err(format!(
+                "unknown provider '{}', expected one of {}",
+                provider,
+                Providers::ALL.join(", ")
+            )
+            .into());
+        }
+
+        let mut init = Init::new(&provider);
+        init.key_env = answer("Environment variable of the API key", &init.key_env)?;
+
+        Ok(init)
+    }
+
+    // API and endpoint of the provider
+    fn api(&self) -> (&str, &str) {
+        match self.provider.as_str() {
+            Providers::DOUBAO => (
+                "https://ark.cn-beijing.volces.com/api/v3/chat/completions",
+                "ep-*",
+            ),
+            _ => ("https://api.openai.com/v1/chat/completions", "gpt-4o-mini"),
+        }
+    }
+
+    // Relative path and content of each file
+    pub fn files(&self) -> Vec<(&'static str, String)> {
+        let (api, endpoint) = self.api();
+        let render = |template: &str| {
+            template
+                .replace("{provider}", &self.provider)
+                .replace("{api}", api)
+                .replace("{endpoint}", endpoint)
+                .replace("{key_env}", &self.key_env)
+        };
+
+        vec![
+            (CONFIG_FILE, render(CONFIG_TEMPLATE)),
+            (PROMPT_POOL_FILE, PROMPT_POOL_TEMPLATE.to_string()),
+            (OPTIMIZER_FILE, render(OPTIMIZER_TEMPLATE)),
+            (DATASET_FILE, DATASET_TEMPLATE.to_string()),
+        ]
+    }
+
+    // Write the files into `dir`, existing files are never overwritten
+    pub fn write<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let files: Vec<(PathBuf, String)> = self
+            .files()
+            .into_iter()
+            .map(|(name, content)| (dir.as_ref().join(name), content))
+            .collect();
+
+        let existing: Vec<String> = files
+            .iter()
+            .filter(|(path, _)| path.exists())
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        if !existing.is_empty() {
+            return Err(format!("{} already exists", existing.join(", ")).into());
+        }
+
+        let mut written = Vec::new();
+        for (path, content) in files {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+}
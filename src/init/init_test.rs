@@ -0,0 +1,74 @@
+use super::init::*;
+use crate::config::validate::validate;
+use crate::llm::base::CritiqueNRefineParams;
+use serde_json::Value;
+use std::io::Cursor;
+
+#[test]
+fn test_files() {
+    std::env::set_var("PROMPTX_INIT_TEST_KEY", "sk-proj");
+
+    let init = Init {
+        key_env: "PROMPTX_INIT_TEST_KEY".to_string(),
+        ..Init::new("doubao")
+    };
+    let files = init.files();
+
+    let config = &files[0].1;
+    assert!(config.contains("key: ${PROMPTX_INIT_TEST_KEY}"));
+    assert!(config.contains("prompt_template: \"Solve the following problem: {question}\""));
+    assert_eq!(validate(config), vec![]);
+
+    let params: CritiqueNRefineParams = serde_yaml::from_str(&files[2].1).unwrap();
+    assert_eq!(params.unique_model_id, "doubao");
+
+    let pool: serde_yaml::Value = serde_yaml::from_str(&files[1].1).unwrap();
+    for key in [
+        "system_prompt",
+        "final_prompt",
+        "eval_prompt",
+        "solve_template",
+    ] {
+        assert!(pool[key].is_string(), "{}", key);
+    }
+    assert!(pool["thinking_styles"].is_sequence());
+
+    for line in files[3].1.lines() {
+        let example: Value = serde_json::from_str(line).unwrap();
+        for key in ["question", "answer", "final_answer"] {
+            assert!(example[key].is_string());
+        }
+    }
+}
+
+#[test]
+fn test_ask() {
+    let mut output = Vec::new();
+    let init = Init::ask(&mut Cursor::new("\n\n"), &mut output).unwrap();
+    assert_eq!(init, Init::new("openai"));
+    assert_eq!(init.key_env, "OPENAI_API_KEY");
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "Provider (doubao, openai) [openai]: Environment variable of the API key [OPENAI_API_KEY]: "
+    );
+
+    let init = Init::ask(&mut Cursor::new("doubao\nARK_KEY\n"), &mut Vec::new()).unwrap();
+    assert_eq!(init.provider, "doubao");
+    assert_eq!(init.key_env, "ARK_KEY");
+
+    assert!(Init::ask(&mut Cursor::new("azure\n"), &mut Vec::new()).is_err());
+}
+
+#[test]
+fn test_write() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = dir.path().join("project");
+
+    let written = Init::default().write(&project).unwrap();
+    assert_eq!(written.len(), 4);
+    assert!(project.join(DATASET_FILE).exists());
+
+    let err = Init::default().write(&project).unwrap_err().to_string();
+    assert!(err.ends_with("already exists"));
+    assert!(err.contains(CONFIG_FILE));
+}
@@ -0,0 +1,3 @@
+pub mod init;
+#[cfg(test)]
+pub mod init_test;
@@ -0,0 +1,261 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+// What to do with a line which is not valid JSON or does not match the record type.
+// Blank lines are always skipped, IO errors always fail.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OnError {
+    // Fail on the first bad line
+    #[default]
+    Strict,
+    // Log a warning and go on
+    Skip,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineError {
+    pub path: PathBuf,
+    // Starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+impl Error for LineError {}
+
+impl From<LineError> for io::Error {
+    fn from(e: LineError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+// Picked from the extension, e.g. train.jsonl.gz or calls.jsonl.zst
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|x| x.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") | Some("zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+// Whether a path names a JSONL file, compressed or not
+pub fn is_jsonl<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    let path = match Compression::from_path(path) {
+        Compression::None => path.to_path_buf(),
+        _ => path.with_extension(""),
+    };
+    path.extension().is_some_and(|x| x == "jsonl")
+}
+
+// Streams the records of a JSONL file one line at a time
+pub struct Reader<T> {
+    path: PathBuf,
+    lines: Lines<Box<dyn BufRead + Send>>,
+    line: usize,
+    on_error: OnError,
+    record: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Reader<T> {
+    pub fn open<P: AsRef<Path>>(path: P, on_error: OnError) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+
+        let reader: Box<dyn BufRead + Send> = match Compression::from_path(&path) {
+            Compression::None => Box::new(BufReader::new(file)),
+            Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(file))),
+            Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::new(file)?)),
+        };
+
+        Ok(Reader {
+            path,
+            lines: reader.lines(),
+            line: 0,
+            on_error,
+            record: PhantomData,
+        })
+    }
+
+    fn error(&self, message: String) -> LineError {
+        LineError {
+            path: self.path.clone(),
+            line: self.line,
+            message,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for Reader<T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = self.lines.next()?;
+            self.line += 1;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    let err = self.error(e.to_string());
+                    return Some(Err(io::Error::new(e.kind(), err)));
+                }
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(&line) {
+                Ok(record) => return Some(Ok(record)),
+                Err(e) => {
+                    let err = self.error(e.to_string());
+                    match self.on_error {
+                        OnError::Strict => return Some(Err(err.into())),
+                        OnError::Skip => warn!("skipped {}", err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Every record of a file, failing on the first bad line
+pub fn read<T, P>(path: P) -> io::Result<Vec<T>>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    Reader::open(path, OnError::Strict)?.collect()
+}
+
+// Every record of a file with its line number, starting at 1
+pub fn read_lines<T, P>(path: P) -> io::Result<Vec<(usize, T)>>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let mut reader = Reader::open(path, OnError::Strict)?;
+    let mut records = Vec::new();
+    while let Some(record) = reader.next() {
+        records.push((reader.line, record?));
+    }
+
+    Ok(records)
+}
+
+// Every record of a file, bad lines handled according to `on_error`
+pub fn read_with<T, P>(path: P, on_error: OnError) -> io::Result<Vec<T>>
+where
+    T: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    Reader::open(path, on_error)?.collect()
+}
+
+// Append to a file, created when missing. Compressed files get a new gzip member or
+// zstd frame, which readers handle transparently.
+pub fn append<T, P>(path: P, records: &[T]) -> io::Result<()>
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    encode(&file, Compression::from_path(&path), records)
+}
+
+// Replace a file with `records`. The records are written to a temporary file next to it
+// which is then renamed, readers never see a partial file. The file keeps its permissions,
+// a new one gets those of any other new file.
+pub fn write<T, P>(path: P, records: &[T]) -> io::Result<()>
+where
+    T: Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let temp = match fs::metadata(path) {
+        Ok(metadata) => {
+            let temp = NamedTempFile::new_in(dir)?;
+            temp.as_file().set_permissions(metadata.permissions())?;
+            temp
+        }
+        Err(_) => new_file_in(dir)?,
+    };
+    encode(temp.as_file(), Compression::from_path(path), records)?;
+    temp.as_file().sync_all()?;
+    temp.persist(path)?;
+
+    Ok(())
+}
+
+// A temporary file created with mode 0666 minus the umask instead of 0600
+#[cfg(unix)]
+fn new_file_in(dir: &Path) -> io::Result<NamedTempFile> {
+    use std::os::unix::fs::PermissionsExt;
+
+    tempfile::Builder::new()
+        .permissions(fs::Permissions::from_mode(0o666))
+        .tempfile_in(dir)
+}
+
+#[cfg(not(unix))]
+fn new_file_in(dir: &Path) -> io::Result<NamedTempFile> {
+    NamedTempFile::new_in(dir)
+}
+
+fn encode<T: Serialize>(file: &File, compression: Compression, records: &[T]) -> io::Result<()> {
+    match compression {
+        Compression::None => {
+            let mut writer = BufWriter::new(file);
+            write_lines(&mut writer, records)?;
+            writer.flush()
+        }
+        Compression::Gzip => {
+            let mut writer = GzEncoder::new(file, flate2::Compression::default());
+            write_lines(&mut writer, records)?;
+            writer.finish()?.flush()
+        }
+        Compression::Zstd => {
+            let mut writer = zstd::Encoder::new(file, 0)?;
+            write_lines(&mut writer, records)?;
+            writer.finish()?.flush()
+        }
+    }
+}
+
+fn write_lines<T: Serialize, W: Write>(writer: &mut W, records: &[T]) -> io::Result<()> {
+    for record in records {
+        serde_json::to_writer(&mut *writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use super::jsonl::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use tempfile::tempdir;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Example {
+    question: String,
+    answer: i64,
+}
+
+const CONTENT: &str = r#"{"question": "1+1", "answer": 2}
+
+{"question": "2+2", "answer": "four"}
+not json
+{"question": "3+3", "answer": 6}
+"#;
+
+#[test]
+fn test_read() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("test.jsonl");
+
+    fs::write(&path, "{\"id\": 1}\n\n{\"id\": 2}\n").unwrap();
+    let records: Vec<Value> = read(&path).unwrap();
+    assert_eq!(records, vec![json!({"id": 1}), json!({"id": 2})]);
+
+    let records: Vec<(usize, Value)> = read_lines(&path).unwrap();
+    assert_eq!(records, vec![(1, json!({"id": 1})), (3, json!({"id": 2}))]);
+
+    assert_eq!(
+        read::<Value, _>(temp_dir.path().join("missing.jsonl"))
+            .unwrap_err()
+            .kind(),
+        io::ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn test_on_error() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("test.jsonl");
+    fs::write(&path, CONTENT).unwrap();
+
+    // Line numbers count blank lines
+    let err = read::<Example, _>(&path).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().starts_with(&format!(
+        "{}:3: invalid type: string \"four\"",
+        path.display()
+    )));
+
+    let records = read_with::<Example, _>(&path, OnError::Skip).unwrap();
+    assert_eq!(
+        records,
+        vec![
+            Example {
+                question: "1+1".to_string(),
+                answer: 2
+            },
+            Example {
+                question: "3+3".to_string(),
+                answer: 6
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_reader() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("test.jsonl");
+    fs::write(&path, CONTENT).unwrap();
+
+    let mut reader = Reader::<Example>::open(&path, OnError::Skip).unwrap();
+    assert_eq!(reader.next().unwrap().unwrap().answer, 2);
+    assert_eq!(reader.next().unwrap().unwrap().answer, 6);
+    assert!(reader.next().is_none());
+
+    let mut reader = Reader::<Example>::open(&path, OnError::Strict).unwrap();
+    assert_eq!(reader.next().unwrap().unwrap().answer, 2);
+    assert!(reader.next().unwrap().is_err());
+}
+
+#[test]
+fn test_append() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("test.jsonl");
+
+    append(&path, &[json!({"id": 1})]).unwrap();
+    append(&path, &[json!({"id": 2}), json!({"id": 3})]).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n"
+    );
+}
+
+#[test]
+fn test_write() {
+    let temp_dir = tempdir().unwrap();
+    let path = temp_dir.path().join("test.jsonl");
+
+    append(&path, &[json!({"id": 1}), json!({"id": 2})]).unwrap();
+    write(&path, &[json!({"id": 3})]).unwrap();
+
+    assert_eq!(read::<Value, _>(&path).unwrap(), vec![json!({"id": 3})]);
+    // The temporary file is renamed, nothing is left behind
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_write_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = tempdir().unwrap();
+    let mode = |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+    // A new file gets the same mode as one created the usual way
+    let created = temp_dir.path().join("created.jsonl");
+    fs::File::create(&created).unwrap();
+    let path = temp_dir.path().join("test.jsonl");
+    write(&path, &[json!({"id": 1})]).unwrap();
+    assert_eq!(mode(&path), mode(&created));
+
+    // A replaced file keeps its mode
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+    write(&path, &[json!({"id": 2})]).unwrap();
+    assert_eq!(mode(&path), 0o640);
+}
+
+#[test]
+fn test_compression() {
+    let temp_dir = tempdir().unwrap();
+
+    for name in ["test.jsonl.gz", "test.jsonl.zst"] {
+        let path = temp_dir.path().join(name);
+
+        write(&path, &[json!({"id": 1})]).unwrap();
+        append(&path, &[json!({"id": 2})]).unwrap();
+
+        assert!(!fs::read(&path).unwrap().starts_with(b"{"));
+        assert_eq!(
+            read::<Value, _>(&path).unwrap(),
+            vec![json!({"id": 1}), json!({"id": 2})],
+            "{}",
+            name
+        );
+    }
+
+    assert_eq!(Compression::from_path("a.jsonl"), Compression::None);
+    assert_eq!(Compression::from_path("a.jsonl.zstd"), Compression::Zstd);
+
+    assert!(is_jsonl("a.jsonl") && is_jsonl("a.jsonl.gz"));
+    assert!(!is_jsonl("a.json.gz") && !is_jsonl("jsonl"));
+}
@@ -0,0 +1,3 @@
+pub mod jsonl;
+#[cfg(test)]
+pub mod jsonl_test;
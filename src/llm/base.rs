@@ -1,4 +1,8 @@
+use super::constants::FileConstants;
+use super::llm::Tool;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Display, Formatter, Result};
 
 pub trait UniversalBase: Display {}
@@ -19,7 +23,7 @@ impl Display for LLMModel {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserLimits {
     pub max_num_requests_in_time_window: i32,
     pub time_window_length_in_seconds: i32,
@@ -31,7 +35,127 @@ impl Display for UserLimits {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // One JSON object per line
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    // off, error, warn, info, debug or trace
+    pub level: String,
+    // Level per module, e.g. promptx::llm: debug
+    pub modules: BTreeMap<String, String>,
+    // Log to stderr
+    pub console: bool,
+    // Log to <log_dir_name>/<file_name> in the run directory
+    pub file: bool,
+    pub file_name: String,
+    pub format: LogFormat,
+    // The file is rotated at max_size_mb, keeping max_files old files
+    pub max_size_mb: u64,
+    pub max_files: u32,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            level: "info".to_string(),
+            modules: BTreeMap::new(),
+            console: true,
+            file: true,
+            file_name: FileConstants::LOGFILE_NAME.to_string(),
+            format: LogFormat::Text,
+            max_size_mb: 50,
+            max_files: 30,
+        }
+    }
+}
+
+impl Display for LoggingConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TracingConfig {
+    // Write the spans of a run to traces.json in the run directory
+    pub file: bool,
+    // OTLP/HTTP collector the spans are sent to, e.g. http://localhost:4318
+    pub endpoint: Option<String>,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        TracingConfig {
+            file: true,
+            endpoint: None,
+        }
+    }
+}
+
+impl Display for TracingConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactPattern {
+    pub name: String,
+    pub regex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactConfig {
+    // Replace the keys and header values of the configured LLMs
+    pub keys: bool,
+    // Built-in rules: email, phone, ssn, credit_card, token
+    pub rules: Vec<String>,
+    pub patterns: Vec<RedactPattern>,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        RedactConfig {
+            keys: true,
+            rules: ["email", "phone", "ssn", "credit_card", "token"]
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl Display for RedactConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl Display for ModelPrice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMQueueSchedulerLimits {
     pub ttl_in_seconds: i32,
     pub max_queue_size: i32,
@@ -100,7 +224,7 @@ impl Display for LLMConfig {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AssistantLLM {
     pub prompt_opt: String,
 }
@@ -111,7 +235,7 @@ impl Display for AssistantLLM {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dir {
     pub base_dir: String,
     pub log_dir_name: String,
@@ -123,20 +247,29 @@ impl Display for Dir {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum OperationMode {
     Online,
     Offline,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SetupConfig {
     pub assistant_llm: AssistantLLM,
     pub dir_info: Dir,
     pub experiment_name: String,
     pub mode: OperationMode,
     pub description: String,
+    // Dataset of the optimizer, relative to the directory of the config file
+    #[serde(default = "default_dataset")]
+    pub dataset: String,
+}
+
+pub const DEFAULT_DATASET: &str = "data/train.jsonl";
+
+fn default_dataset() -> String {
+    DEFAULT_DATASET.to_string()
 }
 
 impl Display for SetupConfig {
@@ -145,7 +278,7 @@ impl Display for SetupConfig {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskConfig {
     pub name: String,
     pub prompt_template: String,
@@ -162,13 +295,13 @@ fn default_true() -> bool {
     true
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mode {
     pub chat: Vec<TaskConfig>,
     pub generation: Vec<TaskConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PromptLibraryConfig {
     pub mode: Mode,
     pub system_prompts: Option<String>,
@@ -180,3 +313,86 @@ impl Display for PromptLibraryConfig {
         write!(f, "{:?}", self)
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptOptimizationParams {
+    pub prompt_technique_name: String,
+}
+
+impl Display for PromptOptimizationParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(
+            f,
+            "[(\"prompt_technique_name\", \"{}\")]",
+            self.prompt_technique_name
+        )
+    }
+}
+
+impl UniversalBase for PromptOptimizationParams {}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CritiqueNRefineParams {
+    #[serde(flatten)]
+    pub base: PromptOptimizationParams,
+    pub unique_model_id: String,
+    // Number of candidate prompts to generate in given iteration
+    pub style_variation: i32,
+    // Number of questions to be asked to LLM in a single go
+    pub questions_batch_size: i32,
+    // Number of batches of questions to correctly answered, for a prompt to be considered as performing good
+    pub min_correct_count: i32,
+    // Max number of mini-batches on which we should evaluate our prompt
+    pub max_eval_batches: i32,
+    // Number of top best performing prompts to be considered for next iterations
+    pub top_n: i32,
+    // Number of rounds of mutation to be performed when generating different styles
+    pub mutation_rounds: i32,
+    // Refine instruction post mutation
+    pub refine_instruction: bool,
+    // Number of iterations for conducting <mutation_rounds> rounds of mutation of task description
+    // followed by refinement of instructions
+    pub mutate_refine_iterations: i32,
+    // Number of iterations for refining task description and in context examples for few-shot
+    pub refine_task_eg_iterations: i32,
+    // Description of task. This will be fed to prompt
+    pub task_description: String,
+    // Base instruction, in line with your dataset. This will be fed to prompt
+    pub base_instruction: String,
+    // Instruction for specifying answer format
+    pub answer_format: String,
+    // Number of samples from dataset, set aside as training data. In every iteration we would be drawing
+    // `questions_batch_size` examples from training data with replacement.
+    pub seen_set_size: i32,
+    // Number of examples to be given for few shots
+    pub few_shot_count: i32,
+    // Generate synthetic reasoning
+    pub generate_reasoning: bool,
+    // Generate description of an expert which can solve the task at hand
+    pub generate_expert_identity: bool,
+    // Generate keywords that describe the intent of the task
+    pub generate_intent_keywords: bool,
+    // number of synthetic training examples to be generated
+    pub num_train_examples: i32,
+    // Tools offered to the LLM, examples with `expected_tool_call` are scored on the tool it calls
+    #[serde(default)]
+    pub tools: Vec<Tool>,
+    // JSON schema of the answer, when set answers are requested in json mode and scored field by field
+    #[serde(default)]
+    pub json_schema: Option<Value>,
+    // Number of completions requested per call for mutation, synthetic examples and
+    // self-consistency scoring, providers without `n` support get repeated calls
+    #[serde(default = "default_num_completions")]
+    pub num_completions: i32,
+    // Sampling temperature when several completions are requested
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+}
+
+fn default_num_completions() -> i32 {
+    1
+}
+
+fn default_temperature() -> f32 {
+    0.8
+}
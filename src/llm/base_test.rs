@@ -96,6 +96,21 @@ fn test_task_config_defaults() {
     assert_eq!(task.name, "test-task");
 }
 
+#[test]
+fn test_setup_config_defaults() {
+    let json = r#"{
+        "assistant_llm": {"prompt_opt": "openai"},
+        "dir_info": {"base_dir": "logs", "log_dir_name": "glue_logs"},
+        "experiment_name": "gsm8k",
+        "mode": "offline",
+        "description": "Math word problems"
+    }"#;
+
+    let setup: SetupConfig = serde_json::from_str(json).unwrap();
+
+    assert_eq!(setup.dataset, DEFAULT_DATASET);
+}
+
 #[test]
 fn test_prompt_library_config() {
     let config = PromptLibraryConfig {
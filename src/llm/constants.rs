@@ -23,3 +23,12 @@ impl LLMOutputTypes {
     pub const EMBEDDINGS: &'static str = "embeddings";
     pub const MULTI_MODAL: &'static str = "multimodal";
 }
+
+#[derive(Clone, Default)]
+pub struct Providers {}
+
+impl Providers {
+    pub const DOUBAO: &'static str = "doubao";
+    pub const OPENAI: &'static str = "openai";
+    pub const ALL: [&'static str; 2] = [Self::DOUBAO, Self::OPENAI];
+}
@@ -19,3 +19,10 @@ fn test_llmoutputtypes() {
     assert_eq!(LLMOutputTypes::EMBEDDINGS, "embeddings");
     assert_eq!(LLMOutputTypes::MULTI_MODAL, "multimodal");
 }
+
+#[test]
+fn test_providers() {
+    assert_eq!(Providers::DOUBAO, "doubao");
+    assert_eq!(Providers::OPENAI, "openai");
+    assert!(Providers::ALL.contains(&"openai"));
+}
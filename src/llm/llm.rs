@@ -1,26 +1,356 @@
+use super::constants::Providers;
+use super::schema::{extract_json, JsonSchema};
 use crate::config::config::{ConfigData, ConfigLLM};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, Identity, Proxy, StatusCode};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::error::Error;
+use std::fs;
+use std::time::Duration;
 
 #[derive(Clone, Default)]
 pub struct LLM {
     pub config: ConfigData,
-    pub client: Client,
+    pub clients: HashMap<String, Client>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Message {
+    pub fn system(content: &str) -> Self {
+        Message {
+            role: "system".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn user(content: &str) -> Self {
+        Message {
+            role: "user".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn assistant(content: &str) -> Self {
+        Message {
+            role: "assistant".to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn tool(tool_call_id: &str, content: &str) -> Self {
+        Message {
+            role: "tool".to_string(),
+            content: content.to_string(),
+            tool_call_id: Some(tool_call_id.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionDefinition,
+}
+
+impl Tool {
+    pub fn function(name: &str, description: &str, parameters: Value) -> Self {
+        Tool {
+            kind: "function".to_string(),
+            function: FunctionDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    #[serde(default)]
+    pub id: String,
+    #[serde(rename = "type", default = "default_tool_kind")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+impl ToolCall {
+    // Arguments are sent by providers as a JSON encoded string
+    pub fn arguments(&self) -> Result<Value, serde_json::Error> {
+        if self.function.arguments.trim().is_empty() {
+            return Ok(Value::Object(Default::default()));
+        }
+
+        serde_json::from_str(&self.function.arguments)
+    }
+
+    // Every expected argument must be given with exactly the same value, other
+    // arguments are ignored and no expected arguments match any call of the tool
+    pub fn matches(&self, expected: &ExpectedToolCall) -> bool {
+        if self.function.name != expected.name {
+            return false;
+        }
+
+        match (&expected.arguments, self.arguments()) {
+            (Value::Null, Ok(_)) => true,
+            (Value::Object(expected), Ok(Value::Object(arguments))) => expected
+                .iter()
+                .all(|(k, v)| arguments.get(k).is_some_and(|x| x == v)),
+            (expected, Ok(arguments)) => *expected == arguments,
+            (_, Err(_)) => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExpectedToolCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<Tool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    // Number of completions to generate, see `LLM::chat_choices`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+impl ResponseFormat {
+    pub fn json_schema(name: &str, schema: Value) -> Self {
+        ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat {
+                name: name.to_string(),
+                schema,
+                strict: false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    pub schema: Value,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatResponse {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+impl ChatResponse {
+    pub fn message(&self) -> Option<&Message> {
+        self.choices.first().map(|x| &x.message)
+    }
+
+    pub fn content(&self) -> Option<&str> {
+        self.message().map(|x| x.content.as_str())
+    }
+
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        self.message()
+            .map(|x| x.tool_calls.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // Content of every choice
+    pub fn contents(&self) -> Vec<String> {
+        self.choices
+            .iter()
+            .map(|x| x.message.content.clone())
+            .collect()
+    }
+
+    // Add the usage of a further call made for the same answer
+    fn add_usage(&mut self, usage: Option<Usage>) {
+        if let Some(usage) = usage {
+            let total = self.usage.get_or_insert_with(Usage::default);
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total.total_tokens += usage.total_tokens;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Choice {
+    #[serde(default)]
+    pub index: u32,
+    pub message: Message,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    #[serde(default)]
+    pub total_tokens: u64,
+}
+
+fn default_tool_kind() -> String {
+    "function".to_string()
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+// Providers without structured output support fall back to json mode or to plain text,
+// in which case the answer is still validated locally
+fn response_format_for(provider: &str, format: ResponseFormat) -> Option<ResponseFormat> {
+    match (provider, format) {
+        (Providers::OPENAI, format) => Some(format),
+        (Providers::DOUBAO, ResponseFormat::JsonSchema { .. }) => Some(ResponseFormat::JsonObject),
+        (Providers::DOUBAO, format) => Some(format),
+        _ => None,
+    }
+}
+
+fn supports_n(provider: &str) -> bool {
+    matches!(provider, Providers::OPENAI)
 }
 
 impl LLM {
-    pub fn new(config: ConfigData) -> Self {
-        LLM {
-            config,
-            client: Client::new(),
+    pub fn new(config: ConfigData) -> Result<Self, Box<dyn Error>> {
+        let mut clients = HashMap::new();
+
+        for llm in &config.llm {
+            let client = Self::build_client(llm)
+                .map_err(|e| ModelError::ConfigError(format!("{}: {}", llm.name, e)))?;
+            clients.insert(llm.name.clone(), client);
+        }
+
+        Ok(LLM { config, clients })
+    }
+
+    pub fn build_client(config: &ConfigLLM) -> Result<Client, Box<dyn Error>> {
+        let mut builder = Client::builder();
+
+        if let Some(secs) = config.connect_timeout {
+            builder = builder.connect_timeout(Duration::from_secs(secs));
+        }
+
+        if let Some(secs) = config.timeout {
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+
+        if let Some(proxy) = &config.proxy {
+            builder = builder.proxy(Proxy::all(proxy)?);
         }
+
+        if !config.headers.is_empty() {
+            let mut headers = HeaderMap::new();
+            for (key, value) in &config.headers {
+                headers.insert(
+                    HeaderName::from_bytes(key.as_bytes())?,
+                    HeaderValue::from_str(value)?,
+                );
+            }
+            builder = builder.default_headers(headers);
+        }
+
+        if let Some(path) = &config.ca_cert {
+            let pem = fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+            for cert in Certificate::from_pem_bundle(&pem)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        match (&config.client_cert, &config.client_key) {
+            (Some(cert), Some(key)) => {
+                let cert = fs::read(cert).map_err(|e| format!("failed to read {}: {}", cert, e))?;
+                let key = fs::read(key).map_err(|e| format!("failed to read {}: {}", key, e))?;
+                builder = builder.identity(Identity::from_pkcs8_pem(&cert, &key)?);
+            }
+            (None, None) => {}
+            _ => return Err("client_cert and client_key must be set together".into()),
+        }
+
+        Ok(builder.build()?)
+    }
+
+    // Endpoints configured after construction fall back to a client without settings
+    fn client(&self, name: &str) -> Client {
+        self.clients.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn find(&self, name: &str) -> Result<&ConfigLLM, ModelError> {
+        self.config
+            .llm
+            .iter()
+            .find(|x| x.name == name)
+            .ok_or_else(|| ModelError::ConfigError(format!("{} is not configured", name)))
     }
 
     pub async fn chat_completion(
@@ -32,22 +362,120 @@ impl LLM {
             return Err("No language model is configured".into());
         }
 
-        let llm = self.config.llm.iter().find(|x| x.name == name).unwrap();
+        let llm = self.find(&name)?;
+
+        match llm.provider() {
+            Providers::DOUBAO => self.call_openai_api(llm.clone(), messages).await,
+            Providers::OPENAI => self.call_openai_api(llm.clone(), messages).await,
+            _ => Err("Unsupported llm name".into()),
+        }
+    }
+
+    pub async fn chat(
+        &self,
+        name: String,
+        messages: Vec<Message>,
+        options: ChatOptions,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let llm = self.find(&name)?;
 
-        match name.as_str() {
-            "doubao" => self.call_openai_api(llm.clone(), messages).await,
-            "openai" => self.call_openai_api(llm.clone(), messages).await,
+        match llm.provider() {
+            Providers::DOUBAO => self.call_openai_chat(llm.clone(), messages, options).await,
+            Providers::OPENAI => self.call_openai_chat(llm.clone(), messages, options).await,
             _ => Err("Unsupported llm name".into()),
         }
     }
 
+    // Generate `options.n` independent completions. Providers that support `n` are asked
+    // once, the others (and providers which silently return fewer choices) get repeated calls.
+    // The choices of every call are returned in one response with their usage summed.
+    pub async fn chat_choices(
+        &self,
+        name: String,
+        messages: Vec<Message>,
+        mut options: ChatOptions,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let n = options.n.unwrap_or(1).max(1) as usize;
+        let mut choices = ChatResponse::default();
+
+        if n > 1 && supports_n(self.find(&name)?.provider()) {
+            let mut response = self
+                .chat(name.clone(), messages.clone(), options.clone())
+                .await?;
+            response.choices.truncate(n);
+            choices = response;
+        }
+
+        options.n = None;
+        while choices.choices.len() < n {
+            let response = self
+                .chat(name.clone(), messages.clone(), options.clone())
+                .await?;
+            if choices.model.is_empty() {
+                choices.model = response.model;
+            }
+            choices.add_usage(response.usage);
+            let choice = response.choices.into_iter().next().unwrap_or_default();
+            choices.choices.push(choice);
+        }
+
+        Ok(choices)
+    }
+
+    // Ask for a JSON answer, validate it against `schema` and re-ask once with the
+    // validation errors when the answer cannot be parsed or does not conform. The
+    // response the answer was taken from carries the usage of both attempts.
+    pub async fn chat_json(
+        &self,
+        name: String,
+        mut messages: Vec<Message>,
+        schema: Option<Value>,
+    ) -> Result<(Value, ChatResponse), Box<dyn Error>> {
+        let format = match &schema {
+            Some(schema) => ResponseFormat::json_schema("response", schema.clone()),
+            None => ResponseFormat::JsonObject,
+        };
+        let options = ChatOptions {
+            response_format: response_format_for(self.find(&name)?.provider(), format),
+            ..Default::default()
+        };
+        let schema = schema.map(JsonSchema::new);
+
+        let mut problems = Vec::new();
+        let mut usage = None;
+        for _ in 0..2 {
+            let mut response = self
+                .chat(name.clone(), messages.clone(), options.clone())
+                .await?;
+            response.add_usage(usage.take());
+            usage = response.usage.clone();
+            let content = response.content().unwrap_or_default().to_string();
+
+            problems = match extract_json(&content) {
+                Some(value) => match schema.as_ref().map(|x| x.validate(&value)) {
+                    Some(Err(errors)) => errors,
+                    _ => return Ok((value, response)),
+                },
+                None => vec!["response is not valid JSON".to_string()],
+            };
+
+            messages.push(Message::assistant(&content));
+            messages.push(Message::user(&format!(
+                "Your previous response was rejected:\n{}\nReply again with only the corrected JSON.",
+                problems.join("\n")
+            )));
+        }
+
+        Err(Box::new(ModelError::ValidationError(problems.join("; "))))
+    }
+
     pub async fn call_openai_api(
         &self,
         config: ConfigLLM,
         messages: Vec<Message>,
     ) -> Result<String, Box<dyn Error>> {
         let response = self
-            .client
+            .client(&config.name)
             .post(config.api.clone())
             .header("Authorization", format!("Bearer {}", config.key))
             .json(&serde_json::json!({
@@ -65,6 +493,74 @@ impl LLM {
             .to_string())
     }
 
+    pub async fn call_openai_chat(
+        &self,
+        config: ConfigLLM,
+        messages: Vec<Message>,
+        options: ChatOptions,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let body = Self::openai_request_body(&config, &messages, &options)?;
+
+        let (status, text) = self
+            .send_openai_request(&config, &body)
+            .await
+            .map_err(|e| ModelError::ApiError(e.to_string()))?;
+
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(Box::new(ModelError::AuthenticationError(format!(
+                "{} returned {}",
+                config.name, status
+            ))));
+        }
+
+        if !status.is_success() {
+            return Err(Box::new(ModelError::ApiError(format!(
+                "{} returned {}: {}",
+                config.name, status, text
+            ))));
+        }
+
+        Ok(serde_json::from_str::<ChatResponse>(&text)?)
+    }
+
+    pub fn openai_request_body(
+        config: &ConfigLLM,
+        messages: &[Message],
+        options: &ChatOptions,
+    ) -> Result<Value, serde_json::Error> {
+        let mut body = serde_json::json!({
+            "model": config.endpoint,
+            "messages": messages,
+            "temperature": 0.0
+        });
+
+        if let (Value::Object(body), Value::Object(options)) =
+            (&mut body, serde_json::to_value(options)?)
+        {
+            body.extend(options);
+        }
+
+        Ok(body)
+    }
+
+    // Transport errors are returned as is, HTTP errors are left to the caller
+    pub async fn send_openai_request(
+        &self,
+        config: &ConfigLLM,
+        body: &Value,
+    ) -> Result<(StatusCode, String), reqwest::Error> {
+        let response = self
+            .client(&config.name)
+            .post(config.api.clone())
+            .header("Authorization", format!("Bearer {}", config.key))
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        Ok((status, response.text().await?))
+    }
+
     pub fn list_model_type(&self) -> Vec<String> {
         if self.config.llm.is_empty() {
             return vec![];
@@ -79,6 +575,7 @@ pub enum ModelError {
     ApiError(String),
     ConfigError(String),
     AuthenticationError(String),
+    ValidationError(String),
 }
 
 impl std::fmt::Display for ModelError {
@@ -87,6 +584,7 @@ impl std::fmt::Display for ModelError {
             ModelError::ApiError(msg) => write!(f, "API Error: {}", msg),
             ModelError::ConfigError(msg) => write!(f, "Configuration Error: {}", msg),
             ModelError::AuthenticationError(msg) => write!(f, "Authentication Error: {}", msg),
+            ModelError::ValidationError(msg) => write!(f, "Validation Error: {}", msg),
         }
     }
 }
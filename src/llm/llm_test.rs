@@ -1,5 +1,9 @@
 use super::llm::*;
 use crate::config::config::{ConfigData, ConfigLLM};
+use serde_json::json;
+use std::collections::BTreeMap;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
 #[tokio::test]
 async fn test_chat_completion_success() {
@@ -9,12 +13,15 @@ async fn test_chat_completion_success() {
             api: "https://api.openai.com/v1/engines/davinci-codex/completions".to_string(),
             key: "test_key".to_string(),
             endpoint: "davinci-codex".to_string(),
+            ..Default::default()
         }],
+        ..Default::default()
     };
-    let llm = LLM::new(config);
+    let llm = LLM::new(config).unwrap();
     let messages = vec![Message {
         role: "user".to_string(),
         content: "Hello, world!".to_string(),
+        ..Default::default()
     }];
 
     let result = llm.chat_completion("openai".to_string(), messages).await;
@@ -23,11 +30,15 @@ async fn test_chat_completion_success() {
 
 #[tokio::test]
 async fn test_chat_completion_unsupported_llm() {
-    let config = ConfigData { llm: vec![] };
-    let llm = LLM::new(config);
+    let config = ConfigData {
+        llm: vec![],
+        ..Default::default()
+    };
+    let llm = LLM::new(config).unwrap();
     let messages = vec![Message {
         role: "user".to_string(),
         content: "Hello, world!".to_string(),
+        ..Default::default()
     }];
 
     let result = llm
@@ -43,13 +54,17 @@ async fn test_call_openai_api_success() {
         api: "https://api.openai.com/v1/engines/davinci-codex/completions".to_string(),
         key: "test_key".to_string(),
         endpoint: "davinci-codex".to_string(),
+        ..Default::default()
     };
     let llm = LLM::new(ConfigData {
         llm: vec![config.clone()],
-    });
+        ..Default::default()
+    })
+    .unwrap();
     let messages = vec![Message {
         role: "user".to_string(),
         content: "Hello, world!".to_string(),
+        ..Default::default()
     }];
 
     let result = llm.call_openai_api(config, messages).await;
@@ -65,16 +80,19 @@ async fn test_list_model_type() {
                 api: "https://ark.cn-beijing.volces.com/api/v3/chat/completions".to_string(),
                 key: "test_key".to_string(),
                 endpoint: "doubao-1.5-pro-32k".to_string(),
+                ..Default::default()
             },
             ConfigLLM {
                 name: "openai".to_string(),
                 api: "https://api.openai.com/v1/engines/davinci-codex/completions".to_string(),
                 key: "test_key".to_string(),
                 endpoint: "davinci-codex".to_string(),
+                ..Default::default()
             },
         ],
+        ..Default::default()
     };
-    let llm = LLM::new(config);
+    let llm = LLM::new(config).unwrap();
 
     let model_types = llm.list_model_type();
     assert_eq!(
@@ -82,3 +100,375 @@ async fn test_list_model_type() {
         vec!["doubao".to_string(), "openai".to_string()]
     );
 }
+
+fn mock_config(name: &str, api: String) -> ConfigData {
+    ConfigData {
+        llm: vec![ConfigLLM {
+            name: name.to_string(),
+            api,
+            key: "test_key".to_string(),
+            endpoint: "gpt-4o".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_chat_with_tools() {
+    let mock_server = MockServer::start().await;
+
+    let weather = Tool::function(
+        "get_weather",
+        "Get the current weather",
+        json!({
+            "type": "object",
+            "properties": {"city": {"type": "string"}},
+            "required": ["city"]
+        }),
+    );
+
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({
+            "model": "gpt-4o",
+            "tools": [{"type": "function", "function": {"name": "get_weather"}}],
+            "tool_choice": "auto"
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "chatcmpl-1",
+            "model": "gpt-4o-2024-08-06",
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\",\"unit\":\"c\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }],
+            "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let llm = LLM::new(mock_config(
+        "openai",
+        format!("{}/v1/chat/completions", mock_server.uri()),
+    ))
+    .unwrap();
+    let options = ChatOptions {
+        tools: vec![weather],
+        tool_choice: Some(json!("auto")),
+        ..Default::default()
+    };
+
+    let response = llm
+        .chat(
+            "openai".to_string(),
+            vec![Message::user("What is the weather in Paris?")],
+            options,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.model, "gpt-4o-2024-08-06");
+    assert_eq!(response.content(), Some(""));
+    assert_eq!(response.usage.unwrap().total_tokens, 15);
+
+    let calls = response.choices[0].message.tool_calls.clone();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].id, "call_1");
+    assert_eq!(calls[0].arguments().unwrap()["city"], "Paris");
+
+    let expected = ExpectedToolCall {
+        name: "get_weather".to_string(),
+        arguments: json!({"city": "Paris"}),
+    };
+    assert!(calls[0].matches(&expected));
+
+    let expected = ExpectedToolCall {
+        name: "get_weather".to_string(),
+        arguments: json!({"city": "London"}),
+    };
+    assert!(!calls[0].matches(&expected));
+
+    // Arguments are compared exactly, unlike answers
+    let expected = ExpectedToolCall {
+        name: "get_weather".to_string(),
+        arguments: json!({"city": "paris"}),
+    };
+    assert!(!calls[0].matches(&expected));
+}
+
+#[tokio::test]
+async fn test_chat_authentication_error() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&mock_server)
+        .await;
+
+    let llm = LLM::new(mock_config("openai", mock_server.uri())).unwrap();
+    let result = llm
+        .chat(
+            "openai".to_string(),
+            vec![Message::user("Hello, world!")],
+            ChatOptions::default(),
+        )
+        .await;
+
+    let err = result.unwrap_err();
+    assert!(err.to_string().starts_with("Authentication Error"));
+}
+
+#[test]
+fn test_tool_message_serialization() {
+    let message = Message::tool("call_1", "{\"temperature\": 21}");
+    let value = serde_json::to_value(&message).unwrap();
+
+    assert_eq!(value["role"], "tool");
+    assert_eq!(value["tool_call_id"], "call_1");
+    assert!(value.get("tool_calls").is_none());
+
+    let message = Message::user("Hello, world!");
+    let value = serde_json::to_value(&message).unwrap();
+    assert_eq!(value, json!({"role": "user", "content": "Hello, world!"}));
+}
+
+#[tokio::test]
+async fn test_chat_json_repair() {
+    let mock_server = MockServer::start().await;
+
+    let reply = |content: &str| {
+        ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"role": "assistant", "content": content}}]
+        }))
+    };
+
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({
+            "response_format": {"type": "json_schema", "json_schema": {"name": "response"}}
+        })))
+        .respond_with(reply("```json\n{\"answer\": \"four\"}\n```"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .respond_with(reply("{\"answer\": 4}"))
+        .with_priority(2)
+        .mount(&mock_server)
+        .await;
+
+    let llm = LLM::new(mock_config("openai", mock_server.uri())).unwrap();
+    let schema = json!({
+        "type": "object",
+        "properties": {"answer": {"type": "integer"}},
+        "required": ["answer"]
+    });
+
+    let (value, _) = llm
+        .chat_json(
+            "openai".to_string(),
+            vec![Message::user("What is 2 + 2? Reply in JSON.")],
+            Some(schema),
+        )
+        .await
+        .unwrap();
+    assert_eq!(value, json!({"answer": 4}));
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+
+    let body: serde_json::Value = requests[1].body_json().unwrap();
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 3);
+    assert!(messages[2]["content"]
+        .as_str()
+        .unwrap()
+        .contains("$.answer: expected integer"));
+}
+
+#[tokio::test]
+async fn test_chat_json_invalid() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"role": "assistant", "content": "not json"}}]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let llm = LLM::new(mock_config("doubao", mock_server.uri())).unwrap();
+    let result = llm
+        .chat_json(
+            "doubao".to_string(),
+            vec![Message::user("Reply in JSON.")],
+            None,
+        )
+        .await;
+
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .starts_with("Validation Error"));
+    assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_http_settings() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("POST"))
+        .and(header("OpenAI-Organization", "org-test"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({
+                    "choices": [{"message": {"role": "assistant", "content": "pong"}}]
+                }))
+                .set_delay(std::time::Duration::from_millis(1500)),
+        )
+        .mount(&mock_server)
+        .await;
+
+    let mut config = mock_config("openai", mock_server.uri());
+    config.llm[0].headers =
+        BTreeMap::from([("OpenAI-Organization".to_string(), "org-test".to_string())]);
+    config.llm[0].timeout = Some(5);
+
+    let llm = LLM::new(config.clone()).unwrap();
+    let response = llm
+        .chat(
+            "openai".to_string(),
+            vec![Message::user("ping")],
+            ChatOptions::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.content(), Some("pong"));
+
+    config.llm[0].timeout = Some(1);
+    let llm = LLM::new(config).unwrap();
+    let result = llm
+        .chat(
+            "openai".to_string(),
+            vec![Message::user("ping")],
+            ChatOptions::default(),
+        )
+        .await;
+    assert!(result.unwrap_err().to_string().starts_with("API Error"));
+}
+
+#[test]
+fn test_build_client_errors() {
+    let config = ConfigLLM {
+        name: "openai".to_string(),
+        proxy: Some("not a proxy url".to_string()),
+        ..Default::default()
+    };
+    assert!(LLM::build_client(&config).is_err());
+
+    let config = ConfigLLM {
+        name: "openai".to_string(),
+        client_cert: Some("client.pem".to_string()),
+        ..Default::default()
+    };
+    assert!(LLM::build_client(&config).is_err());
+
+    let config = ConfigLLM {
+        name: "openai".to_string(),
+        ca_cert: Some("/nonexistent/ca.pem".to_string()),
+        ..Default::default()
+    };
+    let err = LLM::new(ConfigData {
+        llm: vec![config],
+        ..Default::default()
+    })
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("/nonexistent/ca.pem"));
+
+    let config = ConfigLLM {
+        name: "openai".to_string(),
+        proxy: Some("http://127.0.0.1:3128".to_string()),
+        connect_timeout: Some(3),
+        ..Default::default()
+    };
+    assert!(LLM::build_client(&config).is_ok());
+}
+
+#[tokio::test]
+async fn test_chat_choices() {
+    let mock_server = MockServer::start().await;
+
+    let reply = |contents: &[&str]| {
+        let choices: Vec<_> = contents
+            .iter()
+            .enumerate()
+            .map(|(i, x)| json!({"index": i, "message": {"role": "assistant", "content": x}}))
+            .collect();
+        ResponseTemplate::new(200).set_body_json(json!({ "choices": choices }))
+    };
+
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({"n": 3, "temperature": 0.5})))
+        .respond_with(reply(&["a", "b"]))
+        .with_priority(1)
+        .mount(&mock_server)
+        .await;
+
+    Mock::given(method("POST"))
+        .respond_with(reply(&["c"]))
+        .with_priority(2)
+        .mount(&mock_server)
+        .await;
+
+    let options = ChatOptions {
+        temperature: Some(0.5),
+        n: Some(3),
+        ..Default::default()
+    };
+
+    let llm = LLM::new(mock_config("openai", mock_server.uri())).unwrap();
+    let choices = llm
+        .chat_choices(
+            "openai".to_string(),
+            vec![Message::user("Rephrase")],
+            options.clone(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(choices.contents(), vec!["a", "b", "c"]);
+    assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+
+    mock_server.reset().await;
+    Mock::given(method("POST"))
+        .respond_with(reply(&["c"]))
+        .mount(&mock_server)
+        .await;
+
+    let llm = LLM::new(mock_config("doubao", mock_server.uri())).unwrap();
+    let choices = llm
+        .chat_choices(
+            "doubao".to_string(),
+            vec![Message::user("Rephrase")],
+            options,
+        )
+        .await
+        .unwrap();
+    assert_eq!(choices.contents(), vec!["c", "c", "c"]);
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 3);
+    let body: serde_json::Value = requests[0].body_json().unwrap();
+    assert!(body.get("n").is_none());
+    assert_eq!(body["temperature"], 0.5);
+}
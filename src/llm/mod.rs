@@ -14,6 +14,14 @@ pub mod llm;
 #[cfg(test)]
 pub mod llm_test;
 
+pub mod models;
+#[cfg(test)]
+pub mod models_test;
+
+pub mod schema;
+#[cfg(test)]
+pub mod schema_test;
+
 pub mod utils;
 #[cfg(test)]
 pub mod utils_test;
@@ -0,0 +1,227 @@
+use super::llm::{ChatOptions, ChatResponse, Message, ModelError, LLM};
+use super::utils::Table;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::time::Instant;
+
+const PROBE_PROMPT: &str = "Reply with the single word: pong";
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ModelInfo {
+    pub name: String,
+    pub provider: String,
+    pub api: String,
+    pub endpoint: String,
+    pub key: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ProbeResult {
+    pub name: String,
+    pub reachable: bool,
+    pub auth: String,
+    pub latency_ms: f64,
+    pub model: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BenchResult {
+    pub name: String,
+    pub calls: usize,
+    pub failures: usize,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    // Rates over the whole run, of every call and of the successful ones
+    pub attempted_per_sec: f64,
+    pub succeeded_per_sec: f64,
+    pub tokens_per_sec: f64,
+}
+
+#[derive(Clone, Default)]
+pub struct Models {
+    pub llm: LLM,
+}
+
+impl Models {
+    pub fn new(llm: LLM) -> Self {
+        Models { llm }
+    }
+
+    pub fn list(&self) -> Vec<ModelInfo> {
+        self.llm
+            .config
+            .llm
+            .iter()
+            .map(|x| ModelInfo {
+                name: x.name.clone(),
+                provider: x.provider().to_string(),
+                api: x.api.clone(),
+                endpoint: x.endpoint.clone(),
+                key: x.masked_key(),
+            })
+            .collect()
+    }
+
+    // Send a one token request, an HTTP error still proves the endpoint is reachable
+    pub async fn probe(&self, name: &str) -> ProbeResult {
+        let mut result = ProbeResult {
+            name: name.to_string(),
+            ..Default::default()
+        };
+
+        let config = match self.llm.find(name) {
+            Ok(config) => config,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                return result;
+            }
+        };
+
+        let options = ChatOptions {
+            max_tokens: Some(1),
+            ..Default::default()
+        };
+        let body = match LLM::openai_request_body(config, &[Message::user(PROBE_PROMPT)], &options)
+        {
+            Ok(body) => body,
+            Err(e) => {
+                result.error = Some(e.to_string());
+                return result;
+            }
+        };
+
+        let start = Instant::now();
+        let response = self.llm.send_openai_request(config, &body).await;
+        result.latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+        match response {
+            Ok((status, text)) => {
+                result.reachable = true;
+                result.auth = match status {
+                    StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => "denied".to_string(),
+                    s if s.is_success() => "ok".to_string(),
+                    _ => "unknown".to_string(),
+                };
+                if status.is_success() {
+                    result.model = serde_json::from_str::<serde_json::Value>(&text)
+                        .ok()
+                        .and_then(|x| x["model"].as_str().map(|x| x.to_string()));
+                } else {
+                    result.error = Some(status.to_string());
+                }
+            }
+            Err(e) => {
+                result.auth = "unknown".to_string();
+                result.error = Some(e.to_string());
+            }
+        }
+
+        result
+    }
+
+    // Calls are sent as raw requests like `probe`, one after another
+    pub async fn bench(&self, name: &str, calls: usize) -> Result<BenchResult, ModelError> {
+        let config = self.llm.find(name)?;
+        let body = LLM::openai_request_body(
+            config,
+            &[Message::user(PROBE_PROMPT)],
+            &ChatOptions::default(),
+        )
+        .map_err(|e| ModelError::ConfigError(e.to_string()))?;
+
+        let mut latencies = Vec::new();
+        let mut failures = 0;
+        let mut tokens = 0;
+
+        let start = Instant::now();
+        for _ in 0..calls {
+            let call_start = Instant::now();
+            match self.llm.send_openai_request(config, &body).await {
+                Ok((status, text)) if status.is_success() => {
+                    latencies.push(call_start.elapsed().as_secs_f64() * 1000.0);
+                    tokens += serde_json::from_str::<ChatResponse>(&text)
+                        .ok()
+                        .and_then(|x| x.usage)
+                        .map(|x| x.completion_tokens)
+                        .unwrap_or(0);
+                }
+                _ => failures += 1,
+            }
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+
+        latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let rate = |count: f64| if elapsed > 0.0 { count / elapsed } else { 0.0 };
+
+        Ok(BenchResult {
+            name: name.to_string(),
+            calls,
+            failures,
+            p50_ms: percentile(&latencies, 50.0),
+            p95_ms: percentile(&latencies, 95.0),
+            attempted_per_sec: rate(calls as f64),
+            succeeded_per_sec: rate(latencies.len() as f64),
+            tokens_per_sec: rate(tokens as f64),
+        })
+    }
+
+    pub fn render_list(models: &[ModelInfo]) -> String {
+        let mut table = Table::new(&["NAME", "PROVIDER", "ENDPOINT", "API", "KEY"]);
+        for m in models {
+            table.add_row(vec![
+                m.name.clone(),
+                m.provider.clone(),
+                m.endpoint.clone(),
+                m.api.clone(),
+                m.key.clone(),
+            ]);
+        }
+        table.render()
+    }
+
+    pub fn render_probe(results: &[ProbeResult]) -> String {
+        let mut table = Table::new(&["NAME", "REACHABLE", "AUTH", "LATENCY", "MODEL", "ERROR"]);
+        for r in results {
+            table.add_row(vec![
+                r.name.clone(),
+                if r.reachable { "yes" } else { "no" }.to_string(),
+                r.auth.clone(),
+                format!("{:.0}ms", r.latency_ms),
+                r.model.clone().unwrap_or("-".to_string()),
+                r.error.clone().unwrap_or("-".to_string()),
+            ]);
+        }
+        table.render()
+    }
+
+    pub fn render_bench(results: &[BenchResult]) -> String {
+        let mut table = Table::new(&[
+            "NAME", "CALLS", "FAILED", "P50", "P95", "REQ/S", "OK/S", "TOK/S",
+        ]);
+        for r in results {
+            table.add_row(vec![
+                r.name.clone(),
+                r.calls.to_string(),
+                r.failures.to_string(),
+                format!("{:.0}ms", r.p50_ms),
+                format!("{:.0}ms", r.p95_ms),
+                format!("{:.2}", r.attempted_per_sec),
+                format!("{:.2}", r.succeeded_per_sec),
+                format!("{:.1}", r.tokens_per_sec),
+            ]);
+        }
+        table.render()
+    }
+}
+
+// Nearest-rank percentile of sorted samples
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
@@ -0,0 +1,141 @@
+use super::llm::*;
+use super::models::*;
+use crate::config::config::{ConfigData, ConfigLLM};
+use serde_json::json;
+use wiremock::matchers::{body_partial_json, header, method};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn models(server: &MockServer) -> Models {
+    let config = ConfigData {
+        llm: vec![
+            ConfigLLM {
+                name: "openai-prod".to_string(),
+                api: server.uri(),
+                key: "sk-proj-1234567890abcd".to_string(),
+                endpoint: "gpt-4o".to_string(),
+                provider: Some("openai".to_string()),
+                ..Default::default()
+            },
+            ConfigLLM {
+                name: "openai-revoked".to_string(),
+                api: server.uri(),
+                key: "revoked".to_string(),
+                endpoint: "gpt-4o".to_string(),
+                provider: Some("openai".to_string()),
+                ..Default::default()
+            },
+            ConfigLLM {
+                name: "doubao".to_string(),
+                api: "http://127.0.0.1:1/api/v3/chat/completions".to_string(),
+                key: "test_key".to_string(),
+                endpoint: "ep-1".to_string(),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    Models::new(LLM::new(config).unwrap())
+}
+
+async fn mount(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(header("Authorization", "Bearer revoked"))
+        .respond_with(ResponseTemplate::new(401))
+        .with_priority(1)
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "model": "gpt-4o-2024-08-06",
+            "choices": [{"message": {"role": "assistant", "content": "pong"}}],
+            "usage": {"prompt_tokens": 12, "completion_tokens": 1, "total_tokens": 13}
+        })))
+        .with_priority(2)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn test_list() {
+    let server = MockServer::start().await;
+    let m = models(&server);
+
+    let list = m.list();
+    assert_eq!(list.len(), 3);
+    assert_eq!(list[0].provider, "openai");
+    assert_eq!(list[0].key, "****");
+    assert_eq!(list[2].provider, "doubao");
+
+    let rendered = Models::render_list(&list);
+    assert!(rendered.starts_with("NAME"));
+    assert!(!rendered.contains("sk-proj-1234567890abcd"));
+}
+
+#[tokio::test]
+async fn test_probe() {
+    let server = MockServer::start().await;
+    mount(&server).await;
+
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({"max_tokens": 1})))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .named("probe is sent with max_tokens")
+        .mount(&server)
+        .await;
+
+    let m = models(&server);
+
+    let result = m.probe("openai-prod").await;
+    assert!(result.reachable);
+    assert_eq!(result.auth, "ok");
+    assert_eq!(result.model.as_deref(), Some("gpt-4o-2024-08-06"));
+
+    let result = m.probe("openai-revoked").await;
+    assert!(result.reachable);
+    assert_eq!(result.auth, "denied");
+    assert!(result.model.is_none());
+
+    let result = m.probe("doubao").await;
+    assert!(!result.reachable);
+    assert!(result.error.is_some());
+
+    let result = m.probe("missing").await;
+    assert!(!result.reachable);
+    assert!(Models::render_probe(&[result]).contains("missing is not configured"));
+}
+
+#[tokio::test]
+async fn test_bench() {
+    let server = MockServer::start().await;
+    mount(&server).await;
+
+    let m = models(&server);
+
+    let result = m.bench("openai-prod", 4).await.unwrap();
+    assert_eq!(result.calls, 4);
+    assert_eq!(result.failures, 0);
+    assert!(result.p50_ms > 0.0);
+    assert!(result.p95_ms >= result.p50_ms);
+    assert!(result.tokens_per_sec > 0.0);
+    assert_eq!(result.succeeded_per_sec, result.attempted_per_sec);
+
+    let result = m.bench("openai-revoked", 2).await.unwrap();
+    assert_eq!(result.failures, 2);
+    assert!(result.attempted_per_sec > 0.0);
+    assert_eq!(result.succeeded_per_sec, 0.0);
+    assert!(Models::render_bench(&[result]).contains("OK/S"));
+
+    assert!(m.bench("missing", 1).await.is_err());
+}
+
+#[test]
+fn test_percentile() {
+    let samples = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+
+    assert_eq!(percentile(&samples, 50.0), 5.0);
+    assert_eq!(percentile(&samples, 95.0), 10.0);
+    assert_eq!(percentile(&[], 50.0), 0.0);
+}
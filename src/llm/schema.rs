@@ -0,0 +1,274 @@
+use regex::Regex;
+use serde_json::Value;
+use std::sync::LazyLock;
+
+// Validator for the subset of JSON Schema used to describe task answers:
+// type, enum, const, properties, required, additionalProperties, items,
+// min/max(Length|Items), minimum, maximum, pattern, allOf, anyOf and oneOf
+#[derive(Clone, Debug, Default)]
+pub struct JsonSchema {
+    pub schema: Value,
+}
+
+impl JsonSchema {
+    pub fn new(schema: Value) -> Self {
+        JsonSchema { schema }
+    }
+
+    pub fn validate(&self, value: &Value) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        Self::check(&self.schema, value, "$", &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                errors.push(format!("{}: no value is allowed", path));
+                return;
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(t) => t.iter().filter_map(|x| x.as_str()).collect(),
+                _ => vec![],
+            };
+            if !types.is_empty() && !types.iter().any(|t| Self::is_type(value, t)) {
+                errors.push(format!(
+                    "{}: expected {}, found {}",
+                    path,
+                    types.join(" or "),
+                    Self::type_name(value)
+                ));
+                return;
+            }
+        }
+
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(value) {
+                errors.push(format!("{}: {} is not one of {:?}", path, value, allowed));
+            }
+        }
+
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                errors.push(format!("{}: expected {}", path, expected));
+            }
+        }
+
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::Array(required)) = schema.get("required") {
+                    for key in required.iter().filter_map(|x| x.as_str()) {
+                        if !object.contains_key(key) {
+                            errors.push(format!("{}: missing required property '{}'", path, key));
+                        }
+                    }
+                }
+
+                let properties = schema.get("properties").and_then(|x| x.as_object());
+                for (key, item) in object {
+                    let item_path = format!("{}.{}", path, key);
+                    match properties.and_then(|x| x.get(key)) {
+                        Some(item_schema) => Self::check(item_schema, item, &item_path, errors),
+                        None => match schema.get("additionalProperties") {
+                            Some(Value::Bool(false)) => {
+                                errors.push(format!("{}: unexpected property '{}'", path, key))
+                            }
+                            Some(additional) => Self::check(additional, item, &item_path, errors),
+                            None => {}
+                        },
+                    }
+                }
+            }
+            Value::Array(items) => {
+                Self::check_len(schema, "minItems", "maxItems", items.len(), path, errors);
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        Self::check(item_schema, item, &format!("{}[{}]", path, i), errors);
+                    }
+                }
+            }
+            Value::String(s) => {
+                let len = s.chars().count();
+                Self::check_len(schema, "minLength", "maxLength", len, path, errors);
+                if let Some(pattern) = schema.get("pattern").and_then(|x| x.as_str()) {
+                    match Regex::new(pattern) {
+                        Ok(re) if !re.is_match(s) => {
+                            errors.push(format!("{}: does not match pattern {}", path, pattern))
+                        }
+                        Err(e) => {
+                            errors.push(format!("{}: invalid pattern {}: {}", path, pattern, e))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Value::Number(n) => {
+                let n = n.as_f64().unwrap_or_default();
+                if let Some(min) = schema.get("minimum").and_then(|x| x.as_f64()) {
+                    if n < min {
+                        errors.push(format!("{}: {} is less than {}", path, n, min));
+                    }
+                }
+                if let Some(max) = schema.get("maximum").and_then(|x| x.as_f64()) {
+                    if n > max {
+                        errors.push(format!("{}: {} is greater than {}", path, n, max));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for item in all {
+                Self::check(item, value, path, errors);
+            }
+        }
+
+        if let Some(Value::Array(any)) = schema.get("anyOf") {
+            if !any.iter().any(|x| Self::is_valid(x, value)) {
+                errors.push(format!(
+                    "{}: does not match any of the allowed schemas",
+                    path
+                ));
+            }
+        }
+
+        if let Some(Value::Array(one)) = schema.get("oneOf") {
+            let count = one.iter().filter(|x| Self::is_valid(x, value)).count();
+            if count != 1 {
+                errors.push(format!(
+                    "{}: matches {} schemas, expected exactly one",
+                    path, count
+                ));
+            }
+        }
+    }
+
+    fn check_len(
+        schema: &serde_json::Map<String, Value>,
+        min_key: &str,
+        max_key: &str,
+        len: usize,
+        path: &str,
+        errors: &mut Vec<String>,
+    ) {
+        if let Some(min) = schema.get(min_key).and_then(|x| x.as_u64()) {
+            if (len as u64) < min {
+                errors.push(format!("{}: length {} is less than {}", path, len, min));
+            }
+        }
+        if let Some(max) = schema.get(max_key).and_then(|x| x.as_u64()) {
+            if (len as u64) > max {
+                errors.push(format!("{}: length {} is greater than {}", path, len, max));
+            }
+        }
+    }
+
+    fn is_valid(schema: &Value, value: &Value) -> bool {
+        let mut errors = Vec::new();
+        Self::check(schema, value, "$", &mut errors);
+        errors.is_empty()
+    }
+
+    fn is_type(value: &Value, name: &str) -> bool {
+        match name {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            _ => false,
+        }
+    }
+
+    fn type_name(value: &Value) -> &'static str {
+        match value {
+            Value::Object(_) => "object",
+            Value::Array(_) => "array",
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "boolean",
+            Value::Null => "null",
+        }
+    }
+}
+
+static FENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)```(?:json)?\s*(.*?)```").unwrap());
+static ANSWER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<ANS_START>(.*?)<ANS_END>").unwrap());
+
+// Pull a JSON document out of an LLM answer. Models wrap JSON in markdown fences,
+// answer delimiters or surrounding prose even when asked not to.
+pub fn extract_json(text: &str) -> Option<Value> {
+    let text = text.trim();
+
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+
+    if let Some(caps) = FENCE.captures(text) {
+        if let Ok(value) = serde_json::from_str(caps[1].trim()) {
+            return Some(value);
+        }
+    }
+
+    if let Some(caps) = ANSWER.captures(text) {
+        if let Ok(value) = serde_json::from_str(caps[1].trim()) {
+            return Some(value);
+        }
+    }
+
+    for (open, close) in [('{', '}'), ('[', ']')] {
+        if let (Some(start), Some(end)) = (text.find(open), text.rfind(close)) {
+            if start < end {
+                if let Ok(value) = serde_json::from_str(&text[start..=end]) {
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Compare the fields of a ground truth answer with a parsed answer. Strings are
+// compared case-insensitively after trimming, fields absent from `expected` are ignored
+// and a null in `expected` matches any value.
+pub fn fields_match(expected: &Value, actual: &Value) -> bool {
+    match (expected, actual) {
+        (Value::Null, _) => true,
+        (Value::Object(expected), Value::Object(actual)) => expected
+            .iter()
+            .all(|(k, v)| actual.get(k).is_some_and(|x| fields_match(v, x))),
+        (Value::Array(expected), Value::Array(actual)) => {
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual.iter())
+                    .all(|(e, a)| fields_match(e, a))
+        }
+        (Value::String(expected), Value::String(actual)) => {
+            expected.trim().to_lowercase() == actual.trim().to_lowercase()
+        }
+        (Value::Number(expected), Value::Number(actual)) => expected.as_f64() == actual.as_f64(),
+        (Value::String(expected), Value::Number(actual)) => {
+            expected.trim().parse::<f64>().ok() == actual.as_f64()
+        }
+        _ => expected == actual,
+    }
+}
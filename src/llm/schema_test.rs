@@ -0,0 +1,89 @@
+use super::schema::*;
+use serde_json::json;
+
+#[test]
+fn test_validate() {
+    let schema = JsonSchema::new(json!({
+        "type": "object",
+        "properties": {
+            "label": {"type": "string", "enum": ["positive", "negative"]},
+            "score": {"type": "number", "minimum": 0, "maximum": 1},
+            "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2}
+        },
+        "required": ["label", "score"],
+        "additionalProperties": false
+    }));
+
+    assert!(schema
+        .validate(&json!({"label": "positive", "score": 0.9}))
+        .is_ok());
+
+    let errors = schema
+        .validate(&json!({"label": "neutral", "score": 2, "tags": ["a", 1, "c"], "extra": true}))
+        .unwrap_err();
+    assert_eq!(errors.len(), 5);
+    assert!(errors.iter().any(|x| x.starts_with("$.label: ")));
+    assert!(errors.iter().any(|x| x == "$.score: 2 is greater than 1"));
+    assert!(errors
+        .iter()
+        .any(|x| x == "$.tags: length 3 is greater than 2"));
+    assert!(errors
+        .iter()
+        .any(|x| x == "$.tags[1]: expected string, found number"));
+    assert!(errors.iter().any(|x| x == "$: unexpected property 'extra'"));
+
+    let errors = schema.validate(&json!({"label": "positive"})).unwrap_err();
+    assert_eq!(errors, vec!["$: missing required property 'score'"]);
+}
+
+#[test]
+fn test_validate_combinators() {
+    let schema = JsonSchema::new(json!({
+        "anyOf": [{"type": "integer"}, {"type": "string", "pattern": "^[0-9]+$"}]
+    }));
+
+    assert!(schema.validate(&json!(42)).is_ok());
+    assert!(schema.validate(&json!("42")).is_ok());
+    assert!(schema.validate(&json!("forty two")).is_err());
+
+    let schema = JsonSchema::new(json!({
+        "oneOf": [{"type": "number"}, {"type": "integer"}]
+    }));
+    assert!(schema.validate(&json!(1.5)).is_ok());
+    assert!(schema.validate(&json!(1)).is_err());
+}
+
+#[test]
+fn test_extract_json() {
+    assert_eq!(extract_json("{\"a\": 1}"), Some(json!({"a": 1})));
+    assert_eq!(
+        extract_json("Sure:\n```json\n{\"a\": 1}\n```\nDone."),
+        Some(json!({"a": 1}))
+    );
+    assert_eq!(
+        extract_json("Reasoning... <ANS_START>[1, 2]<ANS_END>"),
+        Some(json!([1, 2]))
+    );
+    assert_eq!(
+        extract_json("The answer is {\"a\": {\"b\": 2}} as requested"),
+        Some(json!({"a": {"b": 2}}))
+    );
+    assert_eq!(extract_json("no json here"), None);
+}
+
+#[test]
+fn test_fields_match() {
+    let actual = json!({"city": " Paris ", "population": 2102650, "extra": "ignored"});
+
+    assert!(fields_match(&json!({"city": "paris"}), &actual));
+    assert!(fields_match(&json!({"population": "2102650"}), &actual));
+    assert!(!fields_match(&json!({"city": "London"}), &actual));
+    assert!(!fields_match(&json!({"country": "France"}), &actual));
+
+    let actual = json!({"city": "Paris", "days": 3, "tags": ["a", "b"]});
+    assert!(fields_match(&json!({}), &actual));
+    assert!(fields_match(&json!({"city": null}), &actual));
+    assert!(fields_match(&json!({"days": 3.0}), &actual));
+    assert!(fields_match(&json!({"tags": ["a", "b"]}), &actual));
+    assert!(!fields_match(&json!({"tags": ["a"]}), &actual));
+}
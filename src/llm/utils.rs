@@ -1,17 +1,20 @@
 use super::constants::*;
+use crate::config::layers::merge;
+use crate::llm::base::{LogFormat, LoggingConfig};
+use crate::redact::redact::{RedactEncoder, Redactor};
 use anyhow::{Context, Result};
-use log::{LevelFilter, SetLoggerError};
+use log::LevelFilter;
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use serde_json;
+use serde::Deserialize;
 use serde_yaml::Value;
-use std::fs::{self, File, OpenOptions};
-use std::io;
-use std::io::{BufRead, BufReader, Write};
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use url::Url;
 
 use log4rs::{
+    append::console::{ConsoleAppender, Target},
     append::rolling_file::{
         policy::compound::{
             roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy,
@@ -19,7 +22,7 @@ use log4rs::{
         RollingFileAppender,
     },
     config::{Appender, Config, Root},
-    encode::pattern::PatternEncoder,
+    encode::{json::JsonEncoder, pattern::PatternEncoder, Encode},
     Handle,
 };
 
@@ -105,18 +108,9 @@ impl FileUtils {
         let mut custom_args = FileUtils::yaml_to_dict(&yaml_file_path)?;
 
         if let Some(default_path) = default_yaml_file_path {
-            let default_args = FileUtils::yaml_to_dict(default_path)?;
-
-            if let (Value::Mapping(mut custom), Value::Mapping(default)) =
-                (custom_args.clone(), default_args)
-            {
-                for (key, value) in default {
-                    if !custom.contains_key(&key) {
-                        custom.insert(key, value);
-                    }
-                }
-                custom_args = Value::Mapping(custom);
-            }
+            let mut default_args = FileUtils::yaml_to_dict(default_path)?;
+            merge(&mut default_args, custom_args);
+            custom_args = default_args;
         }
 
         serde_yaml::from_value(custom_args).with_context(|| {
@@ -127,132 +121,173 @@ impl FileUtils {
         })
     }
 
-    pub fn read_jsonl<P: AsRef<Path>>(file_path: P) -> Result<Vec<Value>> {
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
-        let mut jsonl_list = Vec::new();
+    pub fn str_list_to_dir_path<S: AsRef<str>>(str_list: &[S]) -> PathBuf {
+        str_list.iter().fold(PathBuf::new(), |path, dir_name| {
+            path.join(dir_name.as_ref())
+        })
+    }
+}
 
-        for line in reader.lines() {
-            let line = line?;
-            if !line.trim().is_empty() {
-                let json_object: Value = serde_json::from_str(&line)?;
-                jsonl_list.push(json_object);
-            }
-        }
+pub struct Logger {}
 
-        Ok(jsonl_list)
+impl Logger {
+    // Console output goes to stderr, stdout is left to command output. `verbosity` shifts
+    // every level, e.g. 1 for -v and -1 for -q. Records are redacted before they are written.
+    pub fn set_logger(
+        &mut self,
+        module_name: &str,
+        log_dirpath: Option<&str>,
+        logging: &LoggingConfig,
+        verbosity: i8,
+        redactor: &Redactor,
+    ) -> Result<Handle> {
+        let config = Self::config(module_name, log_dirpath, logging, verbosity, redactor)?;
+        log4rs::init_config(config).context("failed to set logger")
     }
 
-    pub fn read_jsonl_row<P: AsRef<Path>>(
-        file_path: P,
-    ) -> io::Result<impl Iterator<Item = Result<Value>>> {
-        let file = File::open(&file_path)?;
-        let reader = BufReader::new(file);
-
-        Ok(reader
-            .lines()
-            .filter_map(move |line_result| match line_result {
-                Ok(line) => {
-                    if line.trim().is_empty() {
-                        return None;
-                    }
-                    match serde_json::from_str(&line) {
-                        Ok(json_object) => Some(Ok(json_object)),
-                        Err(e) => {
-                            eprintln!(
-                                "Error while reading JSONL file at {:?}: {}",
-                                file_path.as_ref(),
-                                e
-                            );
-                            None
-                        }
-                    }
-                }
-                Err(e) => Some(Err(e.into())),
-            }))
+    // Replace the config of a logger set with `set_logger`
+    pub fn reset_logger(
+        handle: &Handle,
+        module_name: &str,
+        log_dirpath: Option<&str>,
+        logging: &LoggingConfig,
+        verbosity: i8,
+        redactor: &Redactor,
+    ) -> Result<()> {
+        handle.set_config(Self::config(
+            module_name,
+            log_dirpath,
+            logging,
+            verbosity,
+            redactor,
+        )?);
+        Ok(())
     }
 
-    pub fn append_as_jsonl<T, P>(file_path: P, args_to_log: &T) -> Result<()>
-    where
-        T: Serialize,
-        P: AsRef<Path>,
-    {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)?;
+    pub fn config(
+        module_name: &str,
+        log_dirpath: Option<&str>,
+        logging: &LoggingConfig,
+        verbosity: i8,
+        redactor: &Redactor,
+    ) -> Result<Config> {
+        let encoder = || -> Box<dyn Encode> {
+            let inner: Box<dyn Encode> = match logging.format {
+                LogFormat::Text => Box::new(PatternEncoder::new("{d} - {l} - {m}{n}")),
+                LogFormat::Json => Box::new(JsonEncoder::new()),
+            };
+            Box::new(RedactEncoder {
+                inner,
+                redactor: redactor.clone(),
+            })
+        };
 
-        let json_str = serde_json::to_string(args_to_log)?;
-        writeln!(file, "{}", json_str)?;
-        Ok(())
-    }
+        let mut builder = Config::builder();
+        let mut appenders = Vec::new();
+
+        if logging.console {
+            let console = ConsoleAppender::builder()
+                .target(Target::Stderr)
+                .encoder(encoder())
+                .build();
+            let name = format!("{}_console", module_name);
+            builder = builder.appender(Appender::builder().build(&name, Box::new(console)));
+            appenders.push(name);
+        }
 
-    pub fn save_jsonlist<T, P>(file_path: P, json_list: &[T], append: bool) -> Result<()>
-    where
-        T: Serialize,
-        P: AsRef<Path>,
-    {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .append(append)
-            .truncate(!append)
-            .open(file_path)?;
-
-        for json_obj in json_list {
-            let json_str = serde_json::to_string(json_obj)?;
-            writeln!(file, "{}", json_str)?;
+        if let (true, Some(log_dirpath)) = (logging.file, log_dirpath) {
+            fs::create_dir_all(log_dirpath)
+                .with_context(|| format!("failed to create log dir {}", log_dirpath))?;
+
+            let log_path = Path::new(log_dirpath).join(&logging.file_name);
+            let roller = FixedWindowRoller::builder()
+                .build(
+                    &format!("{}/{}.{{}}", log_dirpath, logging.file_name),
+                    logging.max_files,
+                )
+                .map_err(|e| anyhow::anyhow!("invalid log rotation: {}", e))?;
+
+            let trigger = SizeTrigger::new(logging.max_size_mb * 1024 * 1024);
+            let policy = CompoundPolicy::new(Box::new(trigger), Box::new(roller));
+
+            let file_appender = RollingFileAppender::builder()
+                .encoder(encoder())
+                .build(&log_path, Box::new(policy))
+                .with_context(|| format!("failed to create log file {}", log_path.display()))?;
+
+            let name = format!("{}_file", module_name);
+            builder = builder.appender(Appender::builder().build(&name, Box::new(file_appender)));
+            appenders.push(name);
         }
-        Ok(())
-    }
 
-    pub fn str_list_to_dir_path<S: AsRef<str>>(str_list: &[S]) -> PathBuf {
-        str_list.iter().fold(PathBuf::new(), |path, dir_name| {
-            path.join(dir_name.as_ref())
-        })
+        for (module, level) in &logging.modules {
+            let level = shift_level(parse_level(level)?, verbosity);
+            builder = builder.logger(log4rs::config::Logger::builder().build(module, level));
+        }
+
+        let level = shift_level(parse_level(&logging.level)?, verbosity);
+        builder
+            .build(Root::builder().appenders(appenders).build(level))
+            .context("invalid logger config")
     }
 }
 
-pub struct Logger {}
+pub fn parse_level(level: &str) -> Result<LevelFilter> {
+    LevelFilter::from_str(level).map_err(|_| {
+        anyhow::anyhow!(
+            "invalid log level '{}', expected one of off, error, warn, info, debug, trace",
+            level
+        )
+    })
+}
 
-impl Logger {
-    pub fn set_logger(
-        &mut self,
-        module_name: &str,
-        log_dirpath: &str,
-    ) -> std::result::Result<Handle, SetLoggerError> {
-        fs::create_dir_all(log_dirpath).expect("Failed to create log dir");
+// Move a level towards trace for positive `verbosity` and towards off for negative
+pub fn shift_level(level: LevelFilter, verbosity: i8) -> LevelFilter {
+    let levels: Vec<LevelFilter> = LevelFilter::iter().collect();
+    let index = (level as i64 + verbosity as i64).clamp(0, levels.len() as i64 - 1);
 
-        let pattern = "{d} - {l} - {m}{n}";
-        let log_path = Path::new(log_dirpath).join(FileConstants::LOGFILE_NAME);
+    levels[index as usize]
+}
 
-        let roller = FixedWindowRoller::builder()
-            .build(
-                &format!("{}/{}.{{}}.log", log_dirpath, FileConstants::LOGFILE_PREFIX),
-                30,
-            )
-            .unwrap();
+#[derive(Clone, Default)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
 
-        let trigger = SizeTrigger::new(50 * 1024 * 1024);
-        let policy = CompoundPolicy::new(Box::new(trigger), Box::new(roller));
+impl Table {
+    pub fn new(headers: &[&str]) -> Self {
+        Table {
+            headers: headers.iter().map(|x| x.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
 
-        let file_appender = RollingFileAppender::builder()
-            .encoder(Box::new(PatternEncoder::new(pattern)))
-            .build(log_path, Box::new(policy))
-            .expect("Failed to create log file");
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
 
-        let file =
-            Appender::builder().build(&format!("{}_file", module_name), Box::new(file_appender));
+    pub fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|x| x.chars().count()).collect();
+        for row in &self.rows {
+            for (i, cell) in row.iter().enumerate().take(widths.len()) {
+                widths[i] = widths[i].max(cell.chars().count());
+            }
+        }
 
-        let config = Config::builder()
-            .appender(file)
-            .build(
-                Root::builder()
-                    .appender(&format!("{}_file", module_name))
-                    .build(LevelFilter::Debug),
-            )
-            .unwrap();
+        let line = |cells: &[String]| {
+            cells
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
 
-        log4rs::init_config(config)
+        let mut out = vec![line(&self.headers)];
+        out.extend(self.rows.iter().map(|x| line(x)));
+        out.join("\n")
     }
 }
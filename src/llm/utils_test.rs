@@ -1,8 +1,10 @@
 use super::constants::*;
 use super::utils::*;
-use log::debug;
+use crate::llm::base::{LogFormat, LoggingConfig, RedactConfig};
+use crate::redact::redact::Redactor;
+use log::{debug, info, LevelFilter};
 use serde::Deserialize;
-use serde_json::json;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -165,84 +167,79 @@ fn test_yaml_to_class() {
 }
 
 #[test]
-fn test_read_jsonl() {
-    let temp_dir = tempdir().unwrap();
-    let jsonl_path = temp_dir.path().join("test.jsonl");
-
-    let content = r#"{"id": 1, "name": "test1"}
-{"id": 2, "name": "test2"}
-"#;
-    fs::write(&jsonl_path, content).unwrap();
-
-    let result = FileUtils::read_jsonl(&jsonl_path).unwrap();
-    assert_eq!(result.len(), 2);
-    assert_eq!(result[0]["id"], 1);
-    assert_eq!(result[1]["name"], "test2");
-
-    temp_dir
-        .close()
-        .expect("failed to delete temporary directory");
-}
-
-#[test]
-fn test_read_jsonl_row() {
-    let temp_dir = tempdir().unwrap();
-    let jsonl_path = temp_dir.path().join("test.jsonl");
-
-    let content = r#"{"id": 1}
-{"id": 2}
-"#;
-    fs::write(&jsonl_path, content).unwrap();
-
-    match FileUtils::read_jsonl_row(&jsonl_path.clone()) {
-        Ok(iterator) => {
-            let mut iter = iterator.enumerate();
-            while let Some((i, json_result)) = iter.next() {
-                match i {
-                    0 => assert_eq!(json_result.unwrap()["id"], 1),
-                    1 => assert_eq!(json_result.unwrap()["id"], 2),
-                    _ => assert!(false),
-                }
-            }
-        }
-        Err(_) => assert!(false),
-    }
-
-    temp_dir
-        .close()
-        .expect("failed to delete temporary directory");
+fn test_str_list_to_dir_path() {
+    let str_list = vec!["path", "to", "dir"];
+    let result = FileUtils::str_list_to_dir_path(&str_list);
+    assert_eq!(result, PathBuf::from("path").join("to").join("dir"));
 }
 
 #[test]
-fn test_append_as_jsonl() {
+fn test_logger() {
     let temp_dir = tempdir().unwrap();
-    let jsonl_path = temp_dir.path().join("test.jsonl");
+    let log_dir = temp_dir.path().to_str().unwrap();
 
-    let data = json!({"test": "value"});
-    FileUtils::append_as_jsonl(&jsonl_path, &data).unwrap();
+    let logging = LoggingConfig {
+        level: "debug".to_string(),
+        console: false,
+        ..Default::default()
+    };
 
-    let content = fs::read_to_string(&jsonl_path).unwrap();
-    assert_eq!(content.trim(), r#"{"test":"value"}"#);
+    let mut logger = Logger {};
+    let handle = logger
+        .set_logger(
+            "test_module",
+            Some(log_dir),
+            &logging,
+            0,
+            &Redactor::default(),
+        )
+        .unwrap();
 
-    temp_dir
-        .close()
-        .expect("failed to delete temporary directory");
-}
+    assert!(Path::new(log_dir).exists());
 
-#[test]
-fn test_save_jsonlist() {
-    let temp_dir = tempdir().unwrap();
-    let jsonl_path = temp_dir.path().join("test.jsonl");
+    let log_file = Path::new(log_dir).join(FileConstants::LOGFILE_NAME);
+    assert!(log_file.exists());
 
-    let data = vec![json!({"id": 1}), json!({"id": 2})];
+    debug!("This is a test debug message.");
 
-    FileUtils::save_jsonlist(&jsonl_path, &data, false).unwrap();
-    let content = fs::read_to_string(&jsonl_path).unwrap();
-    assert_eq!(content.lines().count(), 2);
+    let log_contents = fs::read_to_string(&log_file).unwrap();
+    assert!(log_contents.contains("This is a test debug message."));
 
-    FileUtils::save_jsonlist(&jsonl_path, &data, true).unwrap();
-    let content = fs::read_to_string(&jsonl_path).unwrap();
-    assert_eq!(content.lines().count(), 4);
+    // Switch to JSON lines and drop debug with -q
+    let json_dir = temp_dir.path().join("json");
+    let logging = LoggingConfig {
+        format: LogFormat::Json,
+        ..logging
+    };
+    let redactor = Redactor::new(
+        &RedactConfig::default(),
+        &["sk-test-0123456789abcdef".to_string()],
+    )
+    .unwrap();
+    Logger::reset_logger(
+        &handle,
+        "test_module",
+        json_dir.to_str(),
+        &logging,
+        -1,
+        &redactor,
+    )
+    .unwrap();
+
+    debug!("This is a dropped message.");
+    info!("This is a test info message for a@b.com with sk-test-0123456789abcdef.");
+
+    let log_contents = fs::read_to_string(json_dir.join(FileConstants::LOGFILE_NAME)).unwrap();
+    let lines: Vec<serde_json::Value> = log_contents
+        .lines()
+        .map(|x| serde_json::from_str(x).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(
+        lines[0]["message"],
+        "This is a test info message for [EMAIL_1] with [API_KEY_1]."
+    );
+    assert_eq!(lines[0]["level"], "INFO");
 
     temp_dir
         .close()
@@ -250,32 +247,79 @@ fn test_save_jsonlist() {
 }
 
 #[test]
-fn test_str_list_to_dir_path() {
-    let str_list = vec!["path", "to", "dir"];
-    let result = FileUtils::str_list_to_dir_path(&str_list);
-    assert_eq!(result, PathBuf::from("path").join("to").join("dir"));
+fn test_logger_config() {
+    let logging = LoggingConfig {
+        level: "warn".to_string(),
+        modules: BTreeMap::from([("promptx::llm".to_string(), "debug".to_string())]),
+        file: true,
+        ..Default::default()
+    };
+
+    // No file output without a log dir
+    let config = Logger::config("test_module", None, &logging, 1, &Redactor::default()).unwrap();
+    assert_eq!(config.root().level(), LevelFilter::Info);
+    assert_eq!(config.root().appenders(), ["test_module_console"]);
+    assert_eq!(config.loggers()[0].name(), "promptx::llm");
+    assert_eq!(config.loggers()[0].level(), LevelFilter::Trace);
+
+    let logging = LoggingConfig {
+        level: "loud".to_string(),
+        ..Default::default()
+    };
+    let err = Logger::config("test_module", None, &logging, 0, &Redactor::default()).unwrap_err();
+    assert!(err.to_string().starts_with("invalid log level 'loud'"));
 }
 
 #[test]
-fn test_logger() {
-    let temp_dir = tempdir().unwrap();
-    let log_dir = temp_dir.path().to_str().unwrap();
+fn test_shift_level() {
+    assert_eq!(shift_level(LevelFilter::Info, 1), LevelFilter::Debug);
+    assert_eq!(shift_level(LevelFilter::Info, -2), LevelFilter::Error);
+    assert_eq!(shift_level(LevelFilter::Info, 10), LevelFilter::Trace);
+    assert_eq!(shift_level(LevelFilter::Warn, -10), LevelFilter::Off);
+    assert_eq!(parse_level("DEBUG").unwrap(), LevelFilter::Debug);
+}
 
-    let mut logger = Logger {};
-    let result = logger.set_logger("test_module", log_dir);
-    assert!(result.is_ok());
+#[test]
+fn test_table() {
+    let mut table = Table::new(&["NAME", "PROVIDER"]);
+    table.add_row(vec!["openai-prod".to_string(), "openai".to_string()]);
+    table.add_row(vec!["doubao".to_string(), "doubao".to_string()]);
 
-    assert!(Path::new(log_dir).exists());
+    assert_eq!(
+        table.render(),
+        "NAME         PROVIDER\nopenai-prod  openai\ndoubao       doubao"
+    );
+}
 
-    let log_file = Path::new(log_dir).join(FileConstants::LOGFILE_NAME);
-    assert!(log_file.exists());
+#[test]
+fn test_yaml_to_class_nested() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Inner {
+        a: i32,
+        b: i32,
+    }
 
-    debug!("This is a test debug message.");
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct TestConfig {
+        inner: Inner,
+        list: Vec<i32>,
+    }
 
-    let log_contents = fs::read_to_string(log_file).unwrap();
-    assert!(log_contents.contains("This is a test debug message."));
+    let temp_dir = tempdir().unwrap();
+    let custom_path = temp_dir.path().join("custom.yaml");
+    let default_path = temp_dir.path().join("default.yaml");
 
-    temp_dir
-        .close()
-        .expect("failed to delete temporary directory");
+    fs::write(&custom_path, "inner:\n  b: 2\nlist: [3]").unwrap();
+    fs::write(&default_path, "inner:\n  a: 1\n  b: 0\nlist: [1, 2]").unwrap();
+
+    let result: TestConfig =
+        FileUtils::yaml_to_class(Some(&custom_path), Some(&default_path)).unwrap();
+
+    assert_eq!(
+        result,
+        TestConfig {
+            inner: Inner { a: 1, b: 2 },
+            list: vec![3],
+        }
+    );
 }
@@ -0,0 +1,178 @@
+use super::logger::io_record;
+use super::utils::ToInputs;
+use crate::jsonl::jsonl;
+use crate::redact::redact::Redactor;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+enum Command {
+    Append(PathBuf, Vec<Value>),
+    Flush(oneshot::Sender<io::Result<()>>),
+}
+
+// Counterpart of `Logger` for futures. Clones share the chained log and a single writer
+// thread, so records of concurrent tasks never interleave and callers never block on IO.
+#[derive(Clone)]
+pub struct AsyncLogger {
+    pub base_path: PathBuf,
+    chained_log: Arc<Mutex<Vec<Value>>>,
+    sender: mpsc::UnboundedSender<Command>,
+    pub redactor: Redactor,
+}
+
+impl AsyncLogger {
+    // Must be called within a tokio runtime
+    pub fn new<P: AsRef<Path>>(base_path: P) -> io::Result<Self> {
+        let base_path = base_path.as_ref().to_path_buf();
+        if !base_path.as_os_str().is_empty() {
+            fs::create_dir_all(&base_path)?;
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::task::spawn_blocking(move || write_loop(receiver));
+
+        Ok(AsyncLogger {
+            base_path,
+            chained_log: Arc::new(Mutex::new(Vec::new())),
+            sender,
+            redactor: Redactor::default(),
+        })
+    }
+
+    // Records are redacted before they are queued
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    pub fn chained_log(&self) -> Vec<Value> {
+        self.chained_log.lock().unwrap().clone()
+    }
+
+    pub fn append_dict_to_chained_logs(&self, args_to_log: Value) {
+        self.chained_log.lock().unwrap().push(args_to_log);
+    }
+
+    // Queue the chained log for `<file_name>.jsonl` and start a new one
+    pub fn dump_chained_log_to_file(&self, file_name: &str) -> io::Result<()> {
+        let records = std::mem::take(&mut *self.chained_log.lock().unwrap())
+            .iter()
+            .map(|x| self.redactor.redact_value(x))
+            .collect();
+        self.send(Command::Append(self.path(file_name), records))
+    }
+
+    pub async fn append_to_chained_log<F, T, A>(
+        &self,
+        method_name: &str,
+        inputs: &A,
+        future: F,
+    ) -> T
+    where
+        F: Future<Output = T>,
+        T: Serialize,
+        A: ToInputs + ?Sized,
+    {
+        let start = Instant::now();
+        let result = future.await;
+        let duration = start.elapsed();
+
+        let args_to_log = io_record(None, method_name, inputs.to_inputs(), &result, duration);
+        self.append_dict_to_chained_logs(args_to_log);
+
+        result
+    }
+
+    // Await `future` and queue its inputs, outputs and duration for `<file_name>.jsonl`.
+    // Every call gets its own id, concurrent calls do not share a sample.
+    pub async fn log_io_params<F, T, A>(
+        &self,
+        method_name: &str,
+        inputs: &A,
+        future: F,
+        file_name: &str,
+    ) -> io::Result<T>
+    where
+        F: Future<Output = T>,
+        T: Serialize,
+        A: ToInputs + ?Sized,
+    {
+        let start = Instant::now();
+        let result = future.await;
+        let duration = start.elapsed();
+
+        let id = Uuid::new_v4().to_string();
+        let args_to_log = io_record(
+            Some(&id),
+            method_name,
+            inputs.to_inputs(),
+            &result,
+            duration,
+        );
+        let args_to_log = self.redactor.redact_value(&args_to_log);
+        self.send(Command::Append(self.path(file_name), vec![args_to_log]))?;
+
+        Ok(result)
+    }
+
+    pub async fn log_io_params_for_method<F, T, A>(
+        &self,
+        method_name: &str,
+        inputs: &A,
+        future: F,
+    ) -> io::Result<T>
+    where
+        F: Future<Output = T>,
+        T: Serialize,
+        A: ToInputs + ?Sized,
+    {
+        self.log_io_params(method_name, inputs, future, method_name)
+            .await
+    }
+
+    // Wait until every queued record is written, returns the first write error since
+    // the last flush
+    pub async fn flush(&self) -> io::Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(Command::Flush(sender))?;
+
+        receiver.await.map_err(|_| closed())?
+    }
+
+    fn path(&self, file_name: &str) -> PathBuf {
+        self.base_path.join(format!("{}.jsonl", file_name))
+    }
+
+    fn send(&self, command: Command) -> io::Result<()> {
+        self.sender.send(command).map_err(|_| closed())
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "log writer stopped")
+}
+
+fn write_loop(mut receiver: mpsc::UnboundedReceiver<Command>) {
+    let mut error = None;
+
+    while let Some(command) = receiver.blocking_recv() {
+        match command {
+            Command::Append(path, records) => {
+                if let Err(e) = jsonl::append(&path, &records) {
+                    error.get_or_insert(e);
+                }
+            }
+            Command::Flush(sender) => {
+                let _ = sender.send(error.take().map_or(Ok(()), Err));
+            }
+        }
+    }
+}
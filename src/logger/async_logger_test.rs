@@ -0,0 +1,144 @@
+use super::async_logger::*;
+use super::constants::*;
+use crate::jsonl::jsonl;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_log_io_params() {
+    let temp_dir = tempdir().unwrap();
+    let logger = AsyncLogger::new(temp_dir.path()).unwrap();
+
+    let inputs = json!({"prompt": "What is 6 x 7?"});
+    let result = logger
+        .log_io_params(
+            "chat_completion",
+            &inputs,
+            async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                "42"
+            },
+            "calls",
+        )
+        .await
+        .unwrap();
+    assert_eq!(result, "42");
+
+    logger.flush().await.unwrap();
+
+    let records = jsonl::read::<Value, _>(temp_dir.path().join("calls.jsonl")).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0][Constants::INPUTS], inputs);
+    assert_eq!(records[0][Constants::OUTPUTS], "42");
+    assert!(records[0][Constants::ID].is_string());
+    assert!(
+        records[0][Constants::META][Constants::EXEC_SEC]
+            .as_f64()
+            .unwrap()
+            >= 0.02
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent() {
+    let temp_dir = tempdir().unwrap();
+    let logger = AsyncLogger::new(temp_dir.path()).unwrap();
+
+    let handles: Vec<_> = (0..50)
+        .map(|i| {
+            let logger = logger.clone();
+            tokio::spawn(async move {
+                logger
+                    .log_io_params_for_method("score", &json!({"i": i}), async move { i * 2 })
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.await.unwrap(), i * 2);
+    }
+
+    logger.flush().await.unwrap();
+
+    let records = jsonl::read::<Value, _>(temp_dir.path().join("score.jsonl")).unwrap();
+    assert_eq!(records.len(), 50);
+    for record in &records {
+        let i = record[Constants::INPUTS]["i"].as_u64().unwrap();
+        assert_eq!(record[Constants::OUTPUTS].as_u64().unwrap(), i * 2);
+    }
+
+    let mut ids: Vec<&str> = records
+        .iter()
+        .map(|x| x[Constants::ID].as_str().unwrap())
+        .collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 50);
+}
+
+#[tokio::test]
+async fn test_chained_log() {
+    let temp_dir = tempdir().unwrap();
+    let logger = AsyncLogger::new(temp_dir.path()).unwrap();
+
+    let result = logger
+        .append_to_chained_log("mutate", &json!({"round": 1}), async { vec!["a", "b"] })
+        .await;
+    assert_eq!(result, vec!["a", "b"]);
+    logger.append_dict_to_chained_logs(json!({"best_prompt": "a"}));
+
+    let chained_log = logger.chained_log();
+    assert_eq!(chained_log.len(), 2);
+    assert_eq!(chained_log[0][Constants::INPUTS]["round"], 1);
+    assert_eq!(
+        chained_log[0][Constants::META][Constants::METHOD_NAME],
+        "mutate"
+    );
+
+    logger.dump_chained_log_to_file("best_prompt").unwrap();
+    assert!(logger.chained_log().is_empty());
+    logger.flush().await.unwrap();
+
+    let records = jsonl::read::<Value, _>(temp_dir.path().join("best_prompt.jsonl")).unwrap();
+    assert_eq!(records, chained_log);
+}
+
+#[tokio::test]
+async fn test_flush_error() {
+    let temp_dir = tempdir().unwrap();
+    let logger = AsyncLogger::new(temp_dir.path().join("logs")).unwrap();
+
+    std::fs::remove_dir(temp_dir.path().join("logs")).unwrap();
+    logger
+        .log_io_params("chat", &json!({}), async { 1 }, "calls")
+        .await
+        .unwrap();
+
+    assert!(logger.flush().await.is_err());
+    // The error is reported once
+    assert!(logger.flush().await.is_ok());
+}
+
+#[tokio::test]
+async fn test_redactor() {
+    use crate::llm::base::RedactConfig;
+    use crate::redact::redact::Redactor;
+
+    let temp_dir = tempdir().unwrap();
+    let redactor = Redactor::new(&RedactConfig::default(), &[]).unwrap();
+    let logger = AsyncLogger::new(temp_dir.path())
+        .unwrap()
+        .with_redactor(redactor);
+
+    let inputs = json!({"prompt": "call 555-123-4567"});
+    logger
+        .log_io_params("chat", &inputs, async { "ok" }, "calls")
+        .await
+        .unwrap();
+    logger.flush().await.unwrap();
+
+    let records = jsonl::read::<Value, _>(temp_dir.path().join("calls.jsonl")).unwrap();
+    assert_eq!(records[0][Constants::INPUTS]["prompt"], "call [PHONE_1]");
+}
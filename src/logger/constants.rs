@@ -9,6 +9,7 @@ impl Constants {
     pub const TIMESTAMP: &'static str = "timestamp";
     pub const EXEC_SEC: &'static str = "execution_time_sec";
     pub const EVAL_RESULT: &'static str = "eval_result";
+    pub const EVAL_ERROR: &'static str = "eval_error";
     pub const METHOD_NAME: &'static str = "method_name";
     pub const DIR_NAME: &'static str = "io_logs";
 }
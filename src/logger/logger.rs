@@ -1,12 +1,20 @@
 use super::constants::*;
-use super::file_utils::*;
+use super::utils::ToInputs;
+use crate::jsonl::jsonl;
+use crate::redact::redact::Redactor;
 use chrono::Utc;
-use serde::Serialize;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
+use std::future::Future;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 #[derive(Clone, Default)]
@@ -15,6 +23,8 @@ pub struct Logger {
     pub sample_unq_id: Option<Uuid>,
     pub chained_log: Vec<Value>,
     pub del_self_arg: bool,
+    // Applied to every record before it is written
+    pub redactor: Redactor,
 }
 
 impl Logger {
@@ -29,9 +39,15 @@ impl Logger {
             sample_unq_id: None,
             chained_log: Vec::new(),
             del_self_arg: true,
+            redactor: Redactor::default(),
         })
     }
 
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
     pub fn reset_eval_glue<P: AsRef<Path>>(&mut self, base_path: P) -> io::Result<()> {
         self.base_path = base_path.as_ref().to_path_buf();
         fs::create_dir_all(&self.base_path)?;
@@ -46,7 +62,12 @@ impl Logger {
 
     pub fn dump_chained_log_to_file(&mut self, file_name: &str) -> io::Result<()> {
         let file_path = self.base_path.join(format!("{}.jsonl", file_name));
-        FileUtils::save_jsonlist(&file_path, &self.chained_log, "a")?;
+        let records: Vec<Value> = self
+            .chained_log
+            .iter()
+            .map(|x| self.redactor.redact_value(x))
+            .collect();
+        jsonl::append(&file_path, &records)?;
         self.clear_chained_log();
         Ok(())
     }
@@ -55,38 +76,34 @@ impl Logger {
         self.chained_log.push(args_to_log);
     }
 
-    pub fn append_to_chained_log<F, T>(&mut self, method_name: &str, method: F) -> T
+    // `inputs` is recorded next to the outputs, e.g. the prompt, messages and parameters
+    pub fn append_to_chained_log<F, T, A>(&mut self, method_name: &str, inputs: &A, method: F) -> T
     where
         F: FnOnce() -> T,
         T: Serialize,
+        A: ToInputs + ?Sized,
     {
         let start = Instant::now();
         let result = method();
         let duration = start.elapsed();
 
-        let args_to_log = json!({
-            Constants::INPUTS: {},
-            Constants::OUTPUTS: &result,
-            Constants::META: {
-                Constants::METHOD_NAME: method_name,
-                Constants::EXEC_SEC: duration.as_secs_f64(),
-                Constants::TIMESTAMP: Utc::now().to_rfc3339()
-            }
-        });
+        let args_to_log = io_record(None, method_name, inputs.to_inputs(), &result, duration);
 
         self.chained_log.push(args_to_log);
         result
     }
 
-    pub fn log_io_params<F, T>(
+    pub fn log_io_params<F, T, A>(
         &mut self,
         method_name: &str,
+        inputs: &A,
         method: F,
         file_name: &str,
     ) -> io::Result<T>
     where
         F: FnOnce() -> T,
         T: Serialize,
+        A: ToInputs + ?Sized,
     {
         let start = Instant::now();
         let result = method();
@@ -96,66 +113,296 @@ impl Logger {
             self.sample_unq_id = Some(Uuid::new_v4());
         }
 
-        let args_to_log = json!({
-            Constants::ID: self.sample_unq_id.unwrap().to_string(),
-            Constants::INPUTS: {},
-            Constants::OUTPUTS: &result,
-            Constants::META: {
-                Constants::METHOD_NAME: method_name,
-                Constants::EXEC_SEC: duration.as_secs_f64(),
-                Constants::TIMESTAMP: Utc::now().to_rfc3339()
-            }
-        });
+        let id = self.sample_unq_id.unwrap().to_string();
+        let args_to_log = io_record(
+            Some(&id),
+            method_name,
+            inputs.to_inputs(),
+            &result,
+            duration,
+        );
 
         let file_path = self.base_path.join(format!("{}.jsonl", file_name));
-        FileUtils::append_as_jsonl(&file_path, &args_to_log)?;
+        jsonl::append(&file_path, &[self.redactor.redact_value(&args_to_log)])?;
         self.sample_unq_id = None;
 
         Ok(result)
     }
 
-    pub fn log_io_params_for_method<F, T>(&mut self, method_name: &str, method: F) -> io::Result<T>
+    pub fn log_io_params_for_method<F, T, A>(
+        &mut self,
+        method_name: &str,
+        inputs: &A,
+        method: F,
+    ) -> io::Result<T>
     where
         F: FnOnce() -> T,
         T: Serialize,
+        A: ToInputs + ?Sized,
     {
-        self.log_io_params(method_name, method, method_name)
+        self.log_io_params(method_name, inputs, method, method_name)
     }
 
-    pub fn run_over_logs<F, T>(
+    // Evaluate the records of an IO log with up to `parallelism` evaluators at a time.
+    // Records already evaluated successfully by an earlier run are skipped, so an
+    // interrupted run can be resumed. Records without an id, such as chained log entries,
+    // are keyed by their line. The summary covers the whole eval file and is also written
+    // to `<eval file>_summary.json`.
+    pub async fn run_over_logs_async<F, Fut, T, E>(
         &self,
         method_name: &str,
         eval_method: F,
         file_path: &Path,
-    ) -> io::Result<()>
+        parallelism: usize,
+    ) -> io::Result<EvalSummary>
     where
-        F: Fn(&str, &Value, &Value, &Value) -> T,
+        F: Fn(String, Value, Value, Value) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
         T: Serialize,
+        E: fmt::Display,
     {
-        let eval_file_path = self.base_path.join(format!(
+        let eval_file_path = self.eval_file_path(method_name, file_path)?;
+
+        let done: HashSet<String> = read_eval_rows(&eval_file_path)
+            .await?
+            .iter()
+            .filter(|x| x.get(Constants::EVAL_RESULT).is_some())
+            .map(|x| record_id(x).to_string())
+            .collect();
+
+        let path = file_path.to_path_buf();
+        let records = blocking(move || jsonl::read_lines::<Value, _>(&path)).await?;
+        let total = records.len();
+        let pending: Vec<(String, Value)> = records
+            .into_iter()
+            .map(|(line, record)| (record_key(&record, line), record))
+            .filter(|(key, _)| !done.contains(key))
+            .collect();
+        let skipped = total - pending.len();
+
+        let mut results = stream::iter(pending)
+            .map(|(key, record)| {
+                let eval = eval_method(
+                    record_id(&record).to_string(),
+                    record[Constants::INPUTS].clone(),
+                    record[Constants::OUTPUTS].clone(),
+                    record[Constants::META].clone(),
+                );
+                async move {
+                    let result = eval.await;
+                    (key, record, result)
+                }
+            })
+            .buffer_unordered(parallelism.max(1));
+
+        let mut evaluated = 0;
+        while let Some((key, record, result)) = results.next().await {
+            let result = match result {
+                Ok(value) => serde_json::to_value(value).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            let args_to_log = self
+                .redactor
+                .redact_value(&eval_record(&key, &record, result));
+            let path = eval_file_path.clone();
+            blocking(move || jsonl::append(&path, &[args_to_log])).await?;
+            evaluated += 1;
+        }
+
+        let rows = read_eval_rows(&eval_file_path).await?;
+        let summary = EvalSummary {
+            total,
+            evaluated,
+            skipped,
+            ..EvalSummary::aggregate(&rows)
+        };
+
+        let summary_path = eval_file_path.with_file_name(format!(
+            "{}_summary.json",
+            file_part(eval_file_path.file_stem(), &eval_file_path)?
+        ));
+        let content = serde_json::to_string_pretty(&summary)? + "\n";
+        blocking(move || fs::write(summary_path, content)).await?;
+
+        Ok(summary)
+    }
+
+    fn eval_file_path(&self, method_name: &str, file_path: &Path) -> io::Result<PathBuf> {
+        Ok(self.base_path.join(format!(
             "{}_{}",
             method_name,
-            file_path.file_name().unwrap().to_string_lossy()
-        ));
+            file_part(file_path.file_name(), file_path)?
+        )))
+    }
+}
 
-        for json_obj in FileUtils::read_jsonl_row(file_path)? {
-            let json_obj = json_obj?;
-            let eval_result = eval_method(
-                json_obj[Constants::ID].as_str().unwrap_or_default(),
-                &json_obj[Constants::INPUTS],
-                &json_obj[Constants::OUTPUTS],
-                &json_obj[Constants::META],
-            );
-            let args_to_log = json!({
-                Constants::ID: json_obj[Constants::ID],
-                Constants::EVAL_RESULT: eval_result,
-                Constants::META: {
-                    Constants::TIMESTAMP: Utc::now().to_rfc3339()
-                }
-            });
-            FileUtils::append_as_jsonl(&eval_file_path, &args_to_log)?;
+// One line of an IO log, chained log entries have no id
+pub fn io_record<T: Serialize>(
+    id: Option<&str>,
+    method_name: &str,
+    inputs: Value,
+    outputs: &T,
+    duration: Duration,
+) -> Value {
+    let mut record = json!({
+        Constants::INPUTS: inputs,
+        Constants::OUTPUTS: outputs,
+        Constants::META: {
+            Constants::METHOD_NAME: method_name,
+            Constants::EXEC_SEC: duration.as_secs_f64(),
+            Constants::TIMESTAMP: Utc::now().to_rfc3339()
         }
+    });
+    if let Some(id) = id {
+        record[Constants::ID] = json!(id);
+    }
 
-        Ok(())
+    record
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MethodSummary {
+    pub count: usize,
+    pub failed: usize,
+    // Results which are numbers or booleans, true counts as 1
+    pub scored: usize,
+    pub mean: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalFailure {
+    pub id: String,
+    pub method_name: String,
+    pub error: String,
+}
+
+// Aggregate of an eval file, counts of the last run are in total, evaluated and skipped
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalSummary {
+    pub total: usize,
+    pub evaluated: usize,
+    pub skipped: usize,
+    pub count: usize,
+    pub failed: usize,
+    pub scored: usize,
+    pub mean: Option<f64>,
+    pub methods: BTreeMap<String, MethodSummary>,
+    pub failures: Vec<EvalFailure>,
+}
+
+impl EvalSummary {
+    // The last row of each record wins, a retried failure is not counted twice
+    pub fn aggregate(rows: &[Value]) -> Self {
+        let mut latest: BTreeMap<&str, &Value> = BTreeMap::new();
+        for row in rows {
+            latest.insert(record_id(row), row);
+        }
+
+        let mut summary = EvalSummary::default();
+        let mut sums: BTreeMap<String, f64> = BTreeMap::new();
+        let mut total_sum = 0.0;
+
+        for (id, row) in latest {
+            let method_name = row[Constants::META][Constants::METHOD_NAME]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let method = summary.methods.entry(method_name.clone()).or_default();
+            method.count += 1;
+            summary.count += 1;
+
+            if let Some(error) = row.get(Constants::EVAL_ERROR) {
+                method.failed += 1;
+                summary.failed += 1;
+                summary.failures.push(EvalFailure {
+                    id: id.to_string(),
+                    method_name,
+                    error: error.as_str().unwrap_or_default().to_string(),
+                });
+                continue;
+            }
+
+            let score = match &row[Constants::EVAL_RESULT] {
+                Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+                Value::Number(n) => n.as_f64(),
+                _ => None,
+            };
+            if let Some(score) = score {
+                method.scored += 1;
+                summary.scored += 1;
+                *sums.entry(method_name).or_default() += score;
+                total_sum += score;
+            }
+        }
+
+        for (name, method) in summary.methods.iter_mut() {
+            if method.scored > 0 {
+                method.mean = Some(sums[name] / method.scored as f64);
+            }
+        }
+        if summary.scored > 0 {
+            summary.mean = Some(total_sum / summary.scored as f64);
+        }
+
+        summary
     }
 }
+
+// A file name or stem of `path`, paths such as `..` or `/` have none
+fn file_part<'a>(part: Option<&'a OsStr>, path: &Path) -> io::Result<Cow<'a, str>> {
+    part.map(|x| x.to_string_lossy()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("not a file: {}", path.display()),
+        )
+    })
+}
+
+fn record_id(record: &Value) -> &str {
+    record[Constants::ID].as_str().unwrap_or_default()
+}
+
+// The id of a record, or `line:<n>` when it has none
+fn record_key(record: &Value, line: usize) -> String {
+    match record_id(record) {
+        "" => format!("line:{}", line),
+        id => id.to_string(),
+    }
+}
+
+// Run file IO off the async runtime
+async fn blocking<F, T>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+}
+
+async fn read_eval_rows(path: &Path) -> io::Result<Vec<Value>> {
+    let path = path.to_path_buf();
+    blocking(move || match path.exists() {
+        true => jsonl::read(&path),
+        false => Ok(Vec::new()),
+    })
+    .await
+}
+
+// Eval row of an IO log record keyed by `key`, with the result or the error of the evaluator
+fn eval_record(key: &str, record: &Value, result: Result<Value, String>) -> Value {
+    let (name, value) = match result {
+        Ok(value) => (Constants::EVAL_RESULT, value),
+        Err(error) => (Constants::EVAL_ERROR, json!(error)),
+    };
+
+    json!({
+        Constants::ID: key,
+        name: value,
+        Constants::META: {
+            Constants::METHOD_NAME: record[Constants::META][Constants::METHOD_NAME],
+            Constants::TIMESTAMP: Utc::now().to_rfc3339()
+        }
+    })
+}
@@ -1,7 +1,9 @@
 use super::constants::*;
-use super::file_utils::*;
 use super::logger::*;
+use super::utils::MethodArgs;
+use crate::jsonl::jsonl;
 use serde_json::json;
+use std::collections::HashMap;
 use std::fs;
 use std::io;
 use tempfile::tempdir;
@@ -78,12 +80,14 @@ fn test_append_dict_to_chained_logs() {
 #[test]
 fn test_append_to_chained_log() {
     let (mut logger, _temp_dir) = setup();
-    let result = logger.append_to_chained_log("test_method", || 42);
+    let inputs = json!({"prompt": "What is 6 x 7?"});
+    let result = logger.append_to_chained_log("test_method", &inputs, || 42);
 
     assert_eq!(result, 42);
     assert_eq!(logger.chained_log.len(), 1);
 
     let log_entry = &logger.chained_log[0];
+    assert_eq!(log_entry[Constants::INPUTS], inputs);
     assert_eq!(log_entry[Constants::OUTPUTS], json!(42));
     assert_eq!(
         log_entry[Constants::META][Constants::METHOD_NAME],
@@ -96,7 +100,7 @@ fn test_append_to_chained_log() {
 #[test]
 fn test_log_io_params() -> io::Result<()> {
     let (mut logger, temp_dir) = setup();
-    let result = logger.log_io_params("test_method", || "test_result", "test_file")?;
+    let result = logger.log_io_params("test_method", &json!({}), || "test_result", "test_file")?;
 
     assert_eq!(result, "test_result");
     let file_path = temp_dir.path().join("test_file.jsonl");
@@ -113,7 +117,7 @@ fn test_log_io_params() -> io::Result<()> {
 fn test_log_io_params_for_method() -> io::Result<()> {
     let (mut logger, temp_dir) = setup();
     let method_name = "test_method";
-    let result = logger.log_io_params_for_method(method_name, || "test_result")?;
+    let result = logger.log_io_params_for_method(method_name, &json!({}), || "test_result")?;
 
     assert_eq!(result, "test_result");
     let file_path = temp_dir.path().join(format!("{}.jsonl", method_name));
@@ -126,8 +130,8 @@ fn test_log_io_params_for_method() -> io::Result<()> {
     Ok(())
 }
 
-#[test]
-fn test_run_over_logs() -> io::Result<()> {
+#[tokio::test]
+async fn test_run_over_logs() -> io::Result<()> {
     let (logger, temp_dir) = setup();
 
     let input_path = temp_dir.path().join("input.jsonl");
@@ -137,19 +141,22 @@ fn test_run_over_logs() -> io::Result<()> {
         Constants::OUTPUTS: {"output": "test"},
         Constants::META: {"meta": "test"}
     });
-    FileUtils::append_as_jsonl(&input_path, &test_input)?;
-
-    logger.run_over_logs(
-        "test_eval",
-        |id, inputs, outputs, meta| {
-            assert_eq!(id, "test_id");
-            assert_eq!(inputs["input"], "test");
-            assert_eq!(outputs["output"], "test");
-            assert_eq!(meta["meta"], "test");
-            Constants::EVAL_RESULT
-        },
-        &input_path,
-    )?;
+    jsonl::append(&input_path, &[test_input])?;
+
+    logger
+        .run_over_logs_async(
+            "test_eval",
+            |id, inputs, outputs, meta| async move {
+                assert_eq!(id, "test_id");
+                assert_eq!(inputs["input"], "test");
+                assert_eq!(outputs["output"], "test");
+                assert_eq!(meta["meta"], "test");
+                Ok::<_, String>(Constants::EVAL_RESULT)
+            },
+            &input_path,
+            1,
+        )
+        .await?;
 
     let eval_path = temp_dir.path().join("test_eval_input.jsonl");
     assert!(eval_path.exists());
@@ -160,3 +167,206 @@ fn test_run_over_logs() -> io::Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_log_io_params_inputs() -> io::Result<()> {
+    let (mut logger, temp_dir) = setup();
+
+    let args = MethodArgs {
+        args: vec!["What is 6 x 7?"],
+        kwargs: HashMap::from([("temperature".to_string(), json!(0.0))]),
+    };
+    logger.log_io_params("chat", &args, || "42", "calls")?;
+
+    // Logged inputs are handed back to evaluators
+    let input_path = temp_dir.path().join("calls.jsonl");
+    logger
+        .run_over_logs_async(
+            "rescore",
+            |_, inputs, outputs, _| async move {
+                assert_eq!(inputs["arg_0"], "What is 6 x 7?");
+                assert_eq!(inputs["temperature"], 0.0);
+                Ok::<_, String>(outputs == "42")
+            },
+            &input_path,
+            1,
+        )
+        .await?;
+
+    let content = fs::read_to_string(temp_dir.path().join("rescore_calls.jsonl"))?;
+    assert!(content.contains("\"eval_result\":true"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_over_logs_chained() -> io::Result<()> {
+    let (mut logger, temp_dir) = setup();
+
+    // Chained log entries have no id
+    for n in [1, 2, 3] {
+        logger.append_to_chained_log("step", &json!({"n": n}), || n);
+    }
+    logger.dump_chained_log_to_file("chain")?;
+
+    let input_path = temp_dir.path().join("chain.jsonl");
+    let summary = logger
+        .run_over_logs_async(
+            "check",
+            |_, inputs, _, _| async move {
+                match inputs["n"].as_i64() {
+                    Some(2) => Err("flaky"),
+                    n => Ok(n),
+                }
+            },
+            &input_path,
+            2,
+        )
+        .await?;
+
+    assert_eq!(summary.count, 3);
+    assert_eq!(summary.failed, 1);
+    assert_eq!(summary.failures[0].id, "line:2");
+
+    let summary = logger
+        .run_over_logs_async(
+            "check",
+            |_, _, _, _| async move { Ok::<_, String>(2) },
+            &input_path,
+            2,
+        )
+        .await?;
+
+    assert_eq!(summary.evaluated, 1);
+    assert_eq!(summary.skipped, 2);
+    assert_eq!(summary.count, 3);
+    assert_eq!(summary.failed, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_over_logs_async() -> io::Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let (logger, temp_dir) = setup();
+
+    let input_path = temp_dir.path().join("calls.jsonl");
+    for (i, method) in ["chat", "chat", "chat", "score", "score"]
+        .iter()
+        .enumerate()
+    {
+        let record = json!({
+            Constants::ID: format!("id-{}", i),
+            Constants::INPUTS: {"n": i},
+            Constants::OUTPUTS: i * 10,
+            Constants::META: {Constants::METHOD_NAME: method}
+        });
+        jsonl::append(&input_path, &[record])?;
+    }
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+
+    let summary = logger
+        .run_over_logs_async(
+            "grade",
+            |id, inputs, outputs, _| {
+                let running = running.clone();
+                let peak = peak.clone();
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+
+                    match id.as_str() {
+                        "id-4" => Err("model unavailable"),
+                        _ => Ok(outputs == inputs["n"].as_u64().unwrap() * 10 && id != "id-2"),
+                    }
+                }
+            },
+            &input_path,
+            2,
+        )
+        .await?;
+
+    assert!(peak.load(Ordering::SeqCst) <= 2);
+    assert_eq!(summary.total, 5);
+    assert_eq!(summary.evaluated, 5);
+    assert_eq!(summary.skipped, 0);
+    assert_eq!(summary.failed, 1);
+    assert_eq!(summary.scored, 4);
+    assert_eq!(summary.mean, Some(0.75));
+    assert_eq!(summary.methods["chat"].mean, Some(2.0 / 3.0));
+    assert_eq!(summary.methods["score"].count, 2);
+    assert_eq!(summary.methods["score"].failed, 1);
+    assert_eq!(summary.failures[0].id, "id-4");
+    assert_eq!(summary.failures[0].error, "model unavailable");
+
+    // Only the failed record is evaluated again
+    let summary = logger
+        .run_over_logs_async(
+            "grade",
+            |id, _, _, _| async move {
+                assert_eq!(id, "id-4");
+                Ok::<_, String>(1)
+            },
+            &input_path,
+            4,
+        )
+        .await?;
+
+    assert_eq!(summary.evaluated, 1);
+    assert_eq!(summary.skipped, 4);
+    assert_eq!(summary.failed, 0);
+    assert_eq!(summary.count, 5);
+    assert_eq!(summary.mean, Some(0.8));
+
+    let content = fs::read_to_string(temp_dir.path().join("grade_calls_summary.json"))?;
+    let written: EvalSummary = serde_json::from_str(&content)?;
+    assert_eq!(written, summary);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_over_logs_without_file_name() {
+    let (logger, _temp_dir) = setup();
+
+    let err = logger
+        .run_over_logs_async(
+            "grade",
+            |_, _, _, _| async move { Ok::<_, String>(1) },
+            std::path::Path::new(".."),
+            1,
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_redactor() -> io::Result<()> {
+    use crate::llm::base::RedactConfig;
+    use crate::redact::redact::Redactor;
+
+    let (logger, temp_dir) = setup();
+    let redactor = Redactor::new(&RedactConfig::default(), &[]).unwrap();
+    let mut logger = logger.with_redactor(redactor.clone());
+
+    logger.log_io_params("chat", &json!("mail a@example.com"), || "ok", "calls")?;
+    logger.append_to_chained_log("chat", &json!("from a@example.com"), || "ok");
+    logger.dump_chained_log_to_file("chain")?;
+
+    let calls = fs::read_to_string(temp_dir.path().join("calls.jsonl"))?;
+    assert!(calls.contains("mail [EMAIL_1]"));
+    let chain = fs::read_to_string(temp_dir.path().join("chain.jsonl"))?;
+    assert!(chain.contains("from [EMAIL_1]"));
+    assert!(!calls.contains("a@example.com") && !chain.contains("a@example.com"));
+    assert_eq!(redactor.hits()["email"], 2);
+
+    Ok(())
+}
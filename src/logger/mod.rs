@@ -1,15 +1,27 @@
-pub mod constants;
+pub mod async_logger;
 #[cfg(test)]
-pub mod constants_test;
+pub mod async_logger_test;
 
-pub mod file_utils;
+pub mod constants;
 #[cfg(test)]
-pub mod file_utils_test;
+pub mod constants_test;
 
 pub mod logger;
 #[cfg(test)]
 pub mod logger_test;
 
+pub mod query;
+#[cfg(test)]
+pub mod query_test;
+
+pub mod run_dir;
+#[cfg(test)]
+pub mod run_dir_test;
+
+pub mod tracer;
+#[cfg(test)]
+pub mod tracer_test;
+
 pub mod utils;
 #[cfg(test)]
 pub mod utils_test;
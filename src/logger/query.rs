@@ -0,0 +1,253 @@
+use super::constants::Constants;
+use crate::jsonl::jsonl::{self, OnError};
+use crate::llm::models::percentile;
+use crate::llm::utils::Table;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Width of the inputs column of the table
+const PREVIEW_LEN: usize = 60;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub method: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    // Prefix of the record id
+    pub id: Option<String>,
+    // Substring of any string in the inputs or outputs
+    pub text: Option<String>,
+}
+
+impl Filter {
+    pub fn matches(&self, record: &Value) -> bool {
+        if let Some(method) = &self.method {
+            if method_name(record) != method {
+                return false;
+            }
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let Some(timestamp) = timestamp(record) else {
+                return false;
+            };
+            if self.since.is_some_and(|x| timestamp < x)
+                || self.until.is_some_and(|x| timestamp > x)
+            {
+                return false;
+            }
+        }
+
+        if let Some(id) = &self.id {
+            if !record_id(record).starts_with(id.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some(text) = &self.text {
+            if !contains(&record[Constants::INPUTS], text)
+                && !contains(&record[Constants::OUTPUTS], text)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencyStats {
+    pub method_name: String,
+    pub calls: usize,
+    pub mean_sec: f64,
+    pub p50_sec: f64,
+    pub p95_sec: f64,
+    pub max_sec: f64,
+}
+
+// The JSONL files of a log file, a directory or a run directory, whose io logs are used
+pub fn log_files<P: AsRef<Path>>(path: P) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let io_logs = path.join(Constants::DIR_NAME);
+    let dir = match io_logs.is_dir() {
+        true => io_logs,
+        false => path.to_path_buf(),
+    };
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| x.is_file() && jsonl::is_jsonl(x))
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
+
+// Matching records of every log file in file order, bad lines are skipped
+pub fn query<P: AsRef<Path>>(path: P, filter: &Filter) -> io::Result<Vec<Value>> {
+    let mut records = Vec::new();
+    for file in log_files(path)? {
+        for record in jsonl::Reader::<Value>::open(&file, OnError::Skip)? {
+            let record = record?;
+            if filter.matches(&record) {
+                records.push(record);
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+// The `n` longest calls, longest first
+pub fn slowest(records: &[Value], n: usize) -> Vec<Value> {
+    let mut timed: Vec<&Value> = records.iter().filter(|x| exec_sec(x).is_some()).collect();
+    timed.sort_by(|a, b| exec_sec(b).unwrap().total_cmp(&exec_sec(a).unwrap()));
+
+    timed.into_iter().take(n).cloned().collect()
+}
+
+// Latency per method, records without a duration such as eval rows are left out
+pub fn stats(records: &[Value]) -> Vec<LatencyStats> {
+    let mut durations: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for record in records {
+        if let Some(sec) = exec_sec(record) {
+            durations.entry(method_name(record)).or_default().push(sec);
+        }
+    }
+
+    durations
+        .into_iter()
+        .map(|(method_name, mut secs)| {
+            secs.sort_by(|a, b| a.total_cmp(b));
+            LatencyStats {
+                method_name: method_name.to_string(),
+                calls: secs.len(),
+                mean_sec: secs.iter().sum::<f64>() / secs.len() as f64,
+                p50_sec: percentile(&secs, 50.0),
+                p95_sec: percentile(&secs, 95.0),
+                max_sec: secs[secs.len() - 1],
+            }
+        })
+        .collect()
+}
+
+pub fn render_records(records: &[Value]) -> String {
+    let mut table = Table::new(&["TIMESTAMP", "METHOD", "ID", "DURATION", "INPUTS"]);
+    for record in records {
+        let id = record_id(record);
+        table.add_row(vec![
+            record[Constants::META][Constants::TIMESTAMP]
+                .as_str()
+                .unwrap_or("-")
+                .to_string(),
+            method_name(record).to_string(),
+            match id.is_empty() {
+                true => "-".to_string(),
+                false => id.chars().take(8).collect(),
+            },
+            exec_sec(record)
+                .map(|x| format!("{:.3}s", x))
+                .unwrap_or("-".to_string()),
+            preview(&record[Constants::INPUTS]),
+        ]);
+    }
+    table.render()
+}
+
+pub fn render_stats(stats: &[LatencyStats]) -> String {
+    let mut table = Table::new(&["METHOD", "CALLS", "MEAN", "P50", "P95", "MAX"]);
+    for s in stats {
+        table.add_row(vec![
+            s.method_name.clone(),
+            s.calls.to_string(),
+            format!("{:.3}s", s.mean_sec),
+            format!("{:.3}s", s.p50_sec),
+            format!("{:.3}s", s.p95_sec),
+            format!("{:.3}s", s.max_sec),
+        ]);
+    }
+    table.render()
+}
+
+// RFC 3339, a date such as 2025-01-31 or a duration before now such as 30m, 2h ago or 7d
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+
+    let invalid = || {
+        format!(
+            "invalid time '{}', expected RFC 3339, YYYY-MM-DD or e.g. 30m ago",
+            value
+        )
+    };
+    let amount = value.strip_suffix(" ago").unwrap_or(value);
+    let (index, unit) = amount.char_indices().next_back().ok_or_else(invalid)?;
+    let count: i64 = amount[..index].parse().map_err(|_| invalid())?;
+    let ago = match unit {
+        's' => Duration::seconds(count),
+        'm' => Duration::minutes(count),
+        'h' => Duration::hours(count),
+        'd' => Duration::days(count),
+        _ => return Err(invalid()),
+    };
+
+    Ok(Utc::now() - ago)
+}
+
+fn record_id(record: &Value) -> &str {
+    record[Constants::ID].as_str().unwrap_or_default()
+}
+
+fn method_name(record: &Value) -> &str {
+    record[Constants::META][Constants::METHOD_NAME]
+        .as_str()
+        .unwrap_or_default()
+}
+
+fn exec_sec(record: &Value) -> Option<f64> {
+    record[Constants::META][Constants::EXEC_SEC].as_f64()
+}
+
+fn timestamp(record: &Value) -> Option<DateTime<Utc>> {
+    let timestamp = record[Constants::META][Constants::TIMESTAMP].as_str()?;
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|x| x.with_timezone(&Utc))
+}
+
+fn contains(value: &Value, text: &str) -> bool {
+    match value {
+        Value::String(s) => s.contains(text),
+        Value::Array(items) => items.iter().any(|x| contains(x, text)),
+        Value::Object(map) => map.values().any(|x| contains(x, text)),
+        _ => false,
+    }
+}
+
+// Inputs on one line, cut to the column width
+fn preview(inputs: &Value) -> String {
+    let text = match inputs {
+        Value::Null => return "-".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    match text.chars().count() > PREVIEW_LEN {
+        true => text.chars().take(PREVIEW_LEN - 3).collect::<String>() + "...",
+        false => text,
+    }
+}
@@ -0,0 +1,168 @@
+use super::query::*;
+use crate::jsonl::jsonl;
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use std::fs;
+use tempfile::tempdir;
+
+fn record(id: &str, method: &str, timestamp: &str, sec: f64, prompt: &str) -> Value {
+    json!({
+        "id": id,
+        "inputs": {"messages": [{"role": "user", "content": prompt}]},
+        "outputs": "ok",
+        "meta": {"method_name": method, "timestamp": timestamp, "execution_time_sec": sec}
+    })
+}
+
+fn records() -> Vec<Value> {
+    vec![
+        record(
+            "a1",
+            "chat",
+            "2025-01-01T10:00:00+00:00",
+            1.5,
+            "What is 6 x 7?",
+        ),
+        record(
+            "b2",
+            "score",
+            "2025-01-02T10:00:00+00:00",
+            0.5,
+            "Grade the answer",
+        ),
+        record(
+            "c3",
+            "chat",
+            "2025-01-03T10:00:00+00:00",
+            3.0,
+            "Name a prime",
+        ),
+        json!({"id": "a1", "eval_result": true, "meta": {"method_name": "rescore"}}),
+    ]
+}
+
+#[test]
+fn test_filter() {
+    let r = records();
+    let ids = |filter: &Filter| -> Vec<&str> {
+        r.iter()
+            .filter(|x| filter.matches(x))
+            .map(|x| x["id"].as_str().unwrap())
+            .collect()
+    };
+
+    let filter = Filter {
+        method: Some("chat".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["a1", "c3"]);
+
+    // Records without a timestamp never match a time range
+    let filter = Filter {
+        since: Some(parse_time("2025-01-02").unwrap()),
+        until: Some(parse_time("2025-01-02T23:59:59Z").unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["b2"]);
+
+    let filter = Filter {
+        id: Some("a".to_string()),
+        text: Some("6 x 7".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(ids(&filter), vec!["a1"]);
+
+    assert_eq!(ids(&Filter::default()).len(), 4);
+}
+
+#[test]
+fn test_query() {
+    let temp_dir = tempdir().unwrap();
+    let io_logs = temp_dir.path().join("io_logs");
+    fs::create_dir_all(&io_logs).unwrap();
+
+    let r = records();
+    jsonl::write(io_logs.join("chat.jsonl"), &r[..2]).unwrap();
+    jsonl::write(io_logs.join("more.jsonl.gz"), &r[2..]).unwrap();
+    fs::write(io_logs.join("notes.txt"), "not a log").unwrap();
+    fs::write(io_logs.join("broken.jsonl"), "{\"id\": \n").unwrap();
+
+    // A run directory reads its io logs
+    let all = query(temp_dir.path(), &Filter::default()).unwrap();
+    assert_eq!(all.len(), 4);
+    assert_eq!(log_files(&io_logs).unwrap().len(), 3);
+
+    let filter = Filter {
+        method: Some("chat".to_string()),
+        ..Default::default()
+    };
+    let single = query(io_logs.join("chat.jsonl"), &filter).unwrap();
+    assert_eq!(single, vec![r[0].clone()]);
+
+    assert!(query(temp_dir.path().join("missing.jsonl"), &filter).is_err());
+}
+
+#[test]
+fn test_slowest() {
+    let r = records();
+
+    let slowest = slowest(&r, 2);
+    assert_eq!(slowest, vec![r[2].clone(), r[0].clone()]);
+}
+
+#[test]
+fn test_stats() {
+    let stats = stats(&records());
+
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].method_name, "chat");
+    assert_eq!(stats[0].calls, 2);
+    assert_eq!(stats[0].mean_sec, 2.25);
+    assert_eq!(stats[0].p50_sec, 1.5);
+    assert_eq!(stats[0].max_sec, 3.0);
+    assert_eq!(stats[1].method_name, "score");
+
+    let rendered = render_stats(&stats);
+    assert!(rendered.starts_with("METHOD  CALLS  MEAN    P50     P95     MAX"));
+    assert!(rendered.contains("chat    2      2.250s  1.500s  3.000s  3.000s"));
+}
+
+#[test]
+fn test_render_records() {
+    let mut r = records();
+    r[0]["inputs"] = json!("word ".repeat(20));
+
+    let rendered = render_records(&r);
+    let lines: Vec<&str> = rendered.lines().collect();
+
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("TIMESTAMP"));
+    assert!(lines[1].contains("chat     a1  1.500s"));
+    assert!(lines[1].ends_with("word wo..."));
+    assert!(lines[4].contains("rescore  a1  -"));
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(
+        parse_time("2025-01-31").unwrap().to_rfc3339(),
+        "2025-01-31T00:00:00+00:00"
+    );
+    assert_eq!(
+        parse_time("2025-01-31T08:00:00+08:00")
+            .unwrap()
+            .to_rfc3339(),
+        "2025-01-31T00:00:00+00:00"
+    );
+
+    let since = parse_time("2h").unwrap();
+    let ago = Utc::now() - since;
+    assert!(ago >= Duration::hours(2) && ago < Duration::hours(2) + Duration::minutes(1));
+
+    let since = parse_time("30m ago").unwrap();
+    assert!(Utc::now() - since >= Duration::minutes(30));
+
+    assert!(parse_time("yesterday").is_err());
+    assert!(parse_time("3é").is_err());
+    assert!(parse_time("").is_err());
+}
@@ -0,0 +1,168 @@
+use super::constants::Constants;
+use crate::llm::base::{OperationMode, SetupConfig};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const CHECKPOINTS_DIR: &str = "checkpoints";
+pub const OUTPUTS_DIR: &str = "outputs";
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_SUCCEEDED: &str = "succeeded";
+pub const STATUS_FAILED: &str = "failed";
+
+// What produced a run and how it ended, kept as manifest.json in the run directory
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub run_id: String,
+    pub experiment_name: String,
+    pub description: String,
+    pub mode: OperationMode,
+    pub version: String,
+    pub config_file: String,
+    #[serde(default)]
+    pub log_dir_name: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub status: String,
+    // Number of redacted values per rule
+    #[serde(default)]
+    pub redactions: BTreeMap<String, usize>,
+}
+
+// <base_dir>/<experiment_name>/<run_id>/ with
+//   <log_dir_name>/   application logs
+//   io_logs/          inputs and outputs of every call
+//   checkpoints/      intermediate state
+//   outputs/          results such as the best prompt and synthetic examples
+//   manifest.json
+#[derive(Clone, Debug)]
+pub struct RunDir {
+    pub root: PathBuf,
+    pub log_dir_name: String,
+    pub manifest: Manifest,
+}
+
+impl RunDir {
+    // A timestamp is used when no run id is given, a suffix is added if it is taken
+    pub fn create(
+        setup: &SetupConfig,
+        run_id: Option<&str>,
+        version: &str,
+        config_file: &str,
+    ) -> io::Result<Self> {
+        if let Some(id) = run_id {
+            if id.is_empty() || id.contains(['/', '\\']) || id == "." || id == ".." {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid run id '{}'", id),
+                ));
+            }
+        }
+
+        let experiment = Path::new(&setup.dir_info.base_dir).join(&setup.experiment_name);
+        fs::create_dir_all(&experiment)?;
+
+        let (root, run_id) = match run_id {
+            Some(id) => {
+                let root = experiment.join(id);
+                fs::create_dir(&root)?;
+                (root, id.to_string())
+            }
+            None => Self::unique(&experiment, &Utc::now().format("%Y%m%d-%H%M%S").to_string())?,
+        };
+
+        let run = RunDir {
+            root,
+            log_dir_name: setup.dir_info.log_dir_name.clone(),
+            manifest: Manifest {
+                run_id,
+                experiment_name: setup.experiment_name.clone(),
+                description: setup.description.clone(),
+                mode: setup.mode.clone(),
+                version: version.to_string(),
+                config_file: config_file.to_string(),
+                log_dir_name: setup.dir_info.log_dir_name.clone(),
+                started_at: Utc::now().to_rfc3339(),
+                finished_at: None,
+                status: STATUS_RUNNING.to_string(),
+                redactions: BTreeMap::new(),
+            },
+        };
+
+        for dir in [run.logs(), run.io_logs(), run.checkpoints(), run.outputs()] {
+            fs::create_dir_all(dir)?;
+        }
+        run.write_manifest()?;
+
+        Ok(run)
+    }
+
+    // A run created earlier, e.g. to report on it
+    pub fn open<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let manifest = Self::load_manifest(&root)?;
+
+        Ok(RunDir {
+            log_dir_name: manifest.log_dir_name.clone(),
+            root,
+            manifest,
+        })
+    }
+
+    fn unique(experiment: &Path, stamp: &str) -> io::Result<(PathBuf, String)> {
+        let mut n = 0;
+        loop {
+            let id = match n {
+                0 => stamp.to_string(),
+                _ => format!("{}-{}", stamp, n),
+            };
+            let root = experiment.join(&id);
+            match fs::create_dir(&root) {
+                Ok(()) => return Ok((root, id)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    pub fn logs(&self) -> PathBuf {
+        self.root.join(&self.log_dir_name)
+    }
+
+    pub fn io_logs(&self) -> PathBuf {
+        self.root.join(Constants::DIR_NAME)
+    }
+
+    pub fn checkpoints(&self) -> PathBuf {
+        self.root.join(CHECKPOINTS_DIR)
+    }
+
+    pub fn outputs(&self) -> PathBuf {
+        self.root.join(OUTPUTS_DIR)
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE)
+    }
+
+    pub fn write_manifest(&self) -> io::Result<()> {
+        let content = serde_json::to_string_pretty(&self.manifest)?;
+        fs::write(self.manifest_path(), content + "\n")
+    }
+
+    pub fn finish(&mut self, status: &str) -> io::Result<()> {
+        self.manifest.finished_at = Some(Utc::now().to_rfc3339());
+        self.manifest.status = status.to_string();
+        self.write_manifest()
+    }
+
+    pub fn load_manifest<P: AsRef<Path>>(root: P) -> io::Result<Manifest> {
+        let content = fs::read_to_string(root.as_ref().join(MANIFEST_FILE))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
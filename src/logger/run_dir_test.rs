@@ -0,0 +1,77 @@
+use super::run_dir::*;
+use crate::llm::base::{AssistantLLM, Dir, OperationMode, SetupConfig};
+use tempfile::tempdir;
+
+fn setup(base_dir: &str) -> SetupConfig {
+    SetupConfig {
+        assistant_llm: AssistantLLM {
+            prompt_opt: "openai".to_string(),
+        },
+        dir_info: Dir {
+            base_dir: base_dir.to_string(),
+            log_dir_name: "glue_logs".to_string(),
+        },
+        experiment_name: "gsm8k".to_string(),
+        mode: OperationMode::Offline,
+        description: "Math word problems".to_string(),
+        dataset: "data/train.jsonl".to_string(),
+    }
+}
+
+#[test]
+fn test_create() {
+    let temp_dir = tempdir().unwrap();
+    let s = setup(temp_dir.path().to_str().unwrap());
+
+    let run = RunDir::create(&s, Some("run-1"), "1.0.0", "config.yml").unwrap();
+    let root = temp_dir.path().join("gsm8k").join("run-1");
+
+    assert_eq!(run.root, root);
+    assert_eq!(run.logs(), root.join("glue_logs"));
+    assert_eq!(run.io_logs(), root.join("io_logs"));
+    for dir in [run.logs(), run.io_logs(), run.checkpoints(), run.outputs()] {
+        assert!(dir.is_dir());
+    }
+
+    let manifest = RunDir::load_manifest(&root).unwrap();
+    assert_eq!(manifest, run.manifest);
+    assert_eq!(manifest.run_id, "run-1");
+    assert_eq!(manifest.status, STATUS_RUNNING);
+    assert!(manifest.finished_at.is_none());
+
+    // A run id is never reused
+    assert!(RunDir::create(&s, Some("run-1"), "1.0.0", "config.yml").is_err());
+    assert!(RunDir::create(&s, Some("../x"), "1.0.0", "config.yml").is_err());
+}
+
+#[test]
+fn test_create_timestamp() {
+    let temp_dir = tempdir().unwrap();
+    let s = setup(temp_dir.path().to_str().unwrap());
+
+    let first = RunDir::create(&s, None, "1.0.0", "config.yml").unwrap();
+    let second = RunDir::create(&s, None, "1.0.0", "config.yml").unwrap();
+
+    assert_ne!(first.root, second.root);
+    assert!(second
+        .manifest
+        .run_id
+        .starts_with(&first.manifest.run_id[..15]));
+}
+
+#[test]
+fn test_finish() {
+    let temp_dir = tempdir().unwrap();
+    let s = setup(temp_dir.path().to_str().unwrap());
+
+    let mut run = RunDir::create(&s, Some("run-1"), "1.0.0", "config.yml").unwrap();
+    run.finish(STATUS_SUCCEEDED).unwrap();
+
+    let manifest = RunDir::load_manifest(&run.root).unwrap();
+    assert_eq!(manifest.status, STATUS_SUCCEEDED);
+    assert!(manifest.finished_at.is_some());
+
+    let opened = RunDir::open(&run.root).unwrap();
+    assert_eq!(opened.manifest, manifest);
+    assert_eq!(opened.logs(), run.logs());
+}
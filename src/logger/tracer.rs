@@ -0,0 +1,262 @@
+use crate::llm::llm::ChatResponse;
+use crate::redact::redact::Redactor;
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const TRACES_FILE: &str = "traces.json";
+pub const SCOPE_NAME: &str = "promptx";
+
+// Span names, a run holds rounds, a round holds stages and stages hold LLM calls
+pub const SPAN_RUN: &str = "run";
+pub const SPAN_ROUND: &str = "round";
+pub const SPAN_MUTATION: &str = "mutation";
+pub const SPAN_SCORING: &str = "scoring";
+pub const SPAN_CRITIQUE: &str = "critique";
+pub const SPAN_REFINE: &str = "refine";
+pub const SPAN_EXAMPLE_SYNTHESIS: &str = "example_synthesis";
+pub const SPAN_LLM_CALL: &str = "llm_call";
+
+// Time allowed for the collector to accept the spans
+pub const EXPORT_TIMEOUT_SECS: u64 = 10;
+
+pub const OUTCOME_OK: &str = "ok";
+pub const OUTCOME_ERROR: &str = "error";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Map<String, Value>,
+    pub error: Option<String>,
+}
+
+impl Span {
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+}
+
+// Collects the finished spans of one trace
+#[derive(Clone)]
+pub struct Tracer {
+    pub trace_id: String,
+    pub service_name: String,
+    spans: Arc<Mutex<Vec<Span>>>,
+    redactor: Redactor,
+}
+
+impl Tracer {
+    pub fn new(service_name: &str) -> Self {
+        Tracer {
+            trace_id: format!("{:032x}", rand::random::<u128>()),
+            service_name: service_name.to_string(),
+            spans: Arc::new(Mutex::new(Vec::new())),
+            redactor: Redactor::default(),
+        }
+    }
+
+    // Exported spans are redacted, the spans kept in memory are not
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    pub fn start(&self, name: &str) -> ActiveSpan {
+        ActiveSpan::new(self.clone(), name, None)
+    }
+
+    // Finished spans in the order they ended
+    pub fn spans(&self) -> Vec<Span> {
+        self.spans.lock().unwrap().clone()
+    }
+
+    // ExportTraceServiceRequest in the OTLP/JSON encoding
+    pub fn to_otlp(&self) -> Value {
+        let spans: Vec<Value> = self.spans().iter().map(otlp_span).collect();
+
+        let otlp = json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [otlp_attribute("service.name", &json!(self.service_name))]
+                },
+                "scopeSpans": [{
+                    "scope": {"name": SCOPE_NAME},
+                    "spans": spans
+                }]
+            }]
+        });
+
+        self.redactor.redact_value(&otlp)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let content = serde_json::to_string_pretty(&self.to_otlp())?;
+        fs::write(path, content + "\n")?;
+
+        Ok(())
+    }
+
+    // POST to an OTLP/HTTP collector, e.g. http://localhost:4318
+    pub async fn export(&self, endpoint: &str) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        let response = Client::builder()
+            .timeout(Duration::from_secs(EXPORT_TIMEOUT_SECS))
+            .build()?
+            .post(&url)
+            .json(&self.to_otlp())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("{} returned {}", url, response.status()).into());
+        }
+
+        Ok(())
+    }
+}
+
+// Span in progress, recorded when ended or dropped
+pub struct ActiveSpan {
+    tracer: Tracer,
+    span: Option<Span>,
+}
+
+impl ActiveSpan {
+    fn new(tracer: Tracer, name: &str, parent_span_id: Option<String>) -> Self {
+        let span = Span {
+            trace_id: tracer.trace_id.clone(),
+            span_id: format!("{:016x}", rand::random::<u64>()),
+            parent_span_id,
+            name: name.to_string(),
+            start: SystemTime::now(),
+            end: SystemTime::now(),
+            attributes: Map::new(),
+            error: None,
+        };
+
+        ActiveSpan {
+            tracer,
+            span: Some(span),
+        }
+    }
+
+    pub fn span_id(&self) -> &str {
+        &self.span.as_ref().unwrap().span_id
+    }
+
+    pub fn child(&self, name: &str) -> ActiveSpan {
+        ActiveSpan::new(self.tracer.clone(), name, Some(self.span_id().to_string()))
+    }
+
+    pub fn set<V: Into<Value>>(&mut self, key: &str, value: V) {
+        if let Some(span) = self.span.as_mut() {
+            span.attributes.insert(key.to_string(), value.into());
+        }
+    }
+
+    pub fn fail(&mut self, message: &str) {
+        if let Some(span) = self.span.as_mut() {
+            span.error = Some(message.to_string());
+        }
+    }
+
+    // Model, tokens, latency and outcome of an LLM call made within this span
+    pub fn record_chat<E: ToString>(&mut self, model: &str, result: &Result<ChatResponse, E>) {
+        self.set("llm.model", model);
+        let latency = SystemTime::now()
+            .duration_since(self.span.as_ref().unwrap().start)
+            .unwrap_or_default();
+        self.set("llm.latency_ms", latency.as_millis() as u64);
+
+        match result {
+            Ok(response) => {
+                if let Some(usage) = &response.usage {
+                    self.set("llm.prompt_tokens", usage.prompt_tokens);
+                    self.set("llm.completion_tokens", usage.completion_tokens);
+                    self.set("llm.total_tokens", usage.total_tokens);
+                }
+                self.set("outcome", OUTCOME_OK);
+            }
+            Err(err) => {
+                self.set("outcome", OUTCOME_ERROR);
+                self.fail(&err.to_string());
+            }
+        }
+    }
+
+    pub fn end(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        if let Some(mut span) = self.span.take() {
+            span.end = SystemTime::now();
+            self.tracer.spans.lock().unwrap().push(span);
+        }
+    }
+}
+
+impl Drop for ActiveSpan {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+fn nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn otlp_span(span: &Span) -> Value {
+    let attributes: Vec<Value> = span
+        .attributes
+        .iter()
+        .map(|(k, v)| otlp_attribute(k, v))
+        .collect();
+
+    // STATUS_CODE_OK is 1, STATUS_CODE_ERROR is 2
+    let status = match &span.error {
+        Some(message) => json!({"code": 2, "message": message}),
+        None => json!({"code": 1}),
+    };
+
+    let mut value = json!({
+        "traceId": span.trace_id,
+        "spanId": span.span_id,
+        "name": span.name,
+        "kind": 1,
+        "startTimeUnixNano": nanos(span.start),
+        "endTimeUnixNano": nanos(span.end),
+        "attributes": attributes,
+        "status": status
+    });
+    if let Some(parent) = &span.parent_span_id {
+        value["parentSpanId"] = json!(parent);
+    }
+
+    value
+}
+
+// 64-bit integers are strings in OTLP/JSON
+fn otlp_attribute(key: &str, value: &Value) -> Value {
+    let value = match value {
+        Value::Bool(b) => json!({"boolValue": b}),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({"intValue": n.to_string()}),
+        Value::Number(n) => json!({"doubleValue": n.as_f64()}),
+        Value::String(s) => json!({"stringValue": s}),
+        other => json!({"stringValue": other.to_string()}),
+    };
+
+    json!({"key": key, "value": value})
+}
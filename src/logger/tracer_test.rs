@@ -0,0 +1,138 @@
+use super::tracer::*;
+use crate::llm::llm::{ChatResponse, Usage};
+use serde_json::{json, Value};
+use tempfile::tempdir;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn trace() -> Tracer {
+    let tracer = Tracer::new("promptx");
+
+    let mut run = tracer.start(SPAN_RUN);
+    run.set("run.id", "run-1");
+    {
+        let round = run.child(SPAN_ROUND);
+        let stage = round.child(SPAN_SCORING);
+
+        let mut call = stage.child(SPAN_LLM_CALL);
+        let response = ChatResponse {
+            usage: Some(Usage {
+                prompt_tokens: 12,
+                completion_tokens: 3,
+                total_tokens: 15,
+            }),
+            ..Default::default()
+        };
+        call.record_chat::<String>("openai", &Ok(response));
+        call.end();
+
+        let mut call = stage.child(SPAN_LLM_CALL);
+        call.record_chat::<String>("openai", &Err("timeout".to_string()));
+    }
+    run.end();
+
+    tracer
+}
+
+#[test]
+fn test_spans() {
+    let tracer = trace();
+    let spans = tracer.spans();
+
+    let names: Vec<&str> = spans.iter().map(|x| x.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            SPAN_LLM_CALL,
+            SPAN_LLM_CALL,
+            SPAN_SCORING,
+            SPAN_ROUND,
+            SPAN_RUN
+        ]
+    );
+
+    // Each span is a child of the one that ends after it, the run is the root
+    assert_eq!(spans[0].parent_span_id.as_ref(), Some(&spans[2].span_id));
+    assert_eq!(spans[2].parent_span_id.as_ref(), Some(&spans[3].span_id));
+    assert_eq!(spans[3].parent_span_id.as_ref(), Some(&spans[4].span_id));
+    assert!(spans[4].parent_span_id.is_none());
+    assert!(spans.iter().all(|x| x.trace_id == tracer.trace_id));
+
+    assert_eq!(spans[0].attributes["llm.total_tokens"], 15);
+    assert_eq!(spans[0].attributes["outcome"], OUTCOME_OK);
+    assert_eq!(spans[1].attributes["outcome"], OUTCOME_ERROR);
+    assert_eq!(spans[1].error.as_deref(), Some("timeout"));
+    assert!(spans[4].duration() >= spans[0].duration());
+}
+
+#[test]
+fn test_to_otlp() {
+    let otlp = trace().to_otlp();
+    let spans = otlp["resourceSpans"][0]["scopeSpans"][0]["spans"]
+        .as_array()
+        .unwrap();
+
+    assert_eq!(
+        otlp["resourceSpans"][0]["resource"]["attributes"][0],
+        json!({"key": "service.name", "value": {"stringValue": "promptx"}})
+    );
+    assert_eq!(spans.len(), 5);
+    assert_eq!(spans[0]["traceId"].as_str().unwrap().len(), 32);
+    assert_eq!(spans[0]["spanId"].as_str().unwrap().len(), 16);
+    assert_eq!(spans[1]["status"], json!({"code": 2, "message": "timeout"}));
+    assert!(spans[4].get("parentSpanId").is_none());
+
+    let tokens = spans[0]["attributes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|x| x["key"] == "llm.prompt_tokens")
+        .unwrap();
+    assert_eq!(tokens["value"], json!({"intValue": "12"}));
+}
+
+#[test]
+fn test_write() {
+    let temp_dir = tempdir().unwrap();
+    let file = temp_dir.path().join(TRACES_FILE);
+
+    let tracer = trace();
+    tracer.write(&file).unwrap();
+
+    let content: Value = serde_json::from_str(&std::fs::read_to_string(file).unwrap()).unwrap();
+    assert_eq!(content, tracer.to_otlp());
+}
+
+#[tokio::test]
+async fn test_export() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/traces"))
+        .and(body_partial_json(
+            json!({"resourceSpans": [{"scopeSpans": [{"scope": {"name": "promptx"}}]}]}),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let tracer = trace();
+    assert!(tracer.export(&format!("{}/", server.uri())).await.is_ok());
+    assert!(tracer.export("http://127.0.0.1:1").await.is_err());
+}
+
+#[test]
+fn test_redactor() {
+    use crate::llm::base::RedactConfig;
+    use crate::redact::redact::Redactor;
+
+    let redactor = Redactor::new(&RedactConfig::default(), &[]).unwrap();
+    let tracer = Tracer::new("promptx").with_redactor(redactor);
+    tracer.start(SPAN_RUN).set("user", "a@example.com");
+
+    let otlp = tracer.to_otlp().to_string();
+    assert!(otlp.contains("[EMAIL_1]"));
+    assert!(!otlp.contains("a@example.com"));
+    // Spans in memory are untouched
+    assert_eq!(tracer.spans()[0].attributes["user"], "a@example.com");
+}
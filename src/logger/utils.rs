@@ -1,7 +1,8 @@
 use chrono::Utc;
-use serde::Serialize;
-use serde_json::{Map, Value};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Map, Value};
 use std::collections::HashMap;
+use std::fmt;
 use std::io;
 use std::time::Instant;
 
@@ -76,3 +77,22 @@ impl<T: Serialize> ToInputs for MethodArgs<T> {
         Value::Object(inputs)
     }
 }
+
+// Already shaped inputs, e.g. json!({"prompt": prompt, "temperature": 0.0})
+impl ToInputs for Value {
+    fn to_inputs(&self) -> Value {
+        self.clone()
+    }
+}
+
+// Outputs of a call which may fail, a failure is logged as `{"error": <message>}`
+pub struct CallResult<T, E>(pub Result<T, E>);
+
+impl<T: Serialize, E: fmt::Display> Serialize for CallResult<T, E> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            Ok(value) => value.serialize(serializer),
+            Err(e) => json!({"error": e.to_string()}).serialize(serializer),
+        }
+    }
+}
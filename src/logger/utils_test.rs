@@ -96,3 +96,21 @@ fn test_method_args_null_values() {
         panic!("Expected Object value with null");
     }
 }
+
+#[test]
+fn test_value_to_inputs() {
+    let inputs = json!({"messages": [{"role": "user", "content": "hi"}], "n": 2});
+    assert_eq!(inputs.to_inputs(), inputs);
+}
+
+#[test]
+fn test_call_result() {
+    let ok: CallResult<i32, String> = CallResult(Ok(1));
+    assert_eq!(serde_json::to_value(&ok).unwrap(), json!(1));
+
+    let err: CallResult<i32, String> = CallResult(Err("timeout".to_string()));
+    assert_eq!(
+        serde_json::to_value(&err).unwrap(),
+        json!({"error": "timeout"})
+    );
+}
@@ -1,38 +1,475 @@
 mod arg;
 mod config;
+mod init;
+mod jsonl;
 mod llm;
 mod logger;
 mod optimizer;
+mod redact;
+mod report;
+mod secrets;
+mod task;
 
-use arg::arg::Argument;
+use arg::arg::{Argument, Commands, ConfigCommands, LogsQuery, SecretsCommands};
 use config::config::Config;
+use config::format::Format;
+use config::watch::{Shared, Watcher};
+use init::init::{Init, CONFIG_FILE};
+use llm::base::{LoggingConfig, ModelPrice, TracingConfig};
 use llm::llm::LLM;
-use logger::logger::Logger;
+use llm::models::Models;
+use llm::utils::Logger as AppLogger;
+use log::{error, info, warn};
+use log4rs::Handle;
+use logger::query::{self, Filter};
+use logger::run_dir::{RunDir, OUTPUTS_DIR, STATUS_FAILED, STATUS_SUCCEEDED};
+use logger::tracer::{Tracer, SPAN_RUN, TRACES_FILE};
 use optimizer::optimizer::Optimizer;
+use redact::redact::Redactor;
+use report::report::{prices, Report};
+use secrets::secrets::Secrets;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
 use std::process;
+use task::task::{variables, Tasks};
+
+const LOGGER_NAME: &str = "promptx";
+
+#[tokio::main]
+async fn main() {
+    let mut app_logger = AppLogger {};
+    let handle = match app_logger.set_logger(
+        LOGGER_NAME,
+        None,
+        &LoggingConfig::default(),
+        0,
+        &Redactor::default(),
+    ) {
+        Ok(handle) => Some(handle),
+        Err(err) => {
+            eprintln!("failed to set logger: {}", err);
+            None
+        }
+    };
 
-fn main() {
     let mut a = Argument {
         ..Default::default()
     };
 
     if let Err(err) = a.parse() {
-        println!("failed to parse argument: {}", err);
+        error!("failed to parse argument: {}", err);
         process::exit(-1);
     }
 
+    logging(
+        &handle,
+        None,
+        &LoggingConfig::default(),
+        a.verbosity,
+        &Redactor::default(),
+    );
+
+    if let Some(Commands::Secrets(command)) = a.command {
+        if let Err(err) = secrets(command) {
+            error!("failed to run secrets: {}", err);
+            process::exit(-4);
+        }
+        return;
+    }
+
+    if let Some(Commands::Init { dir, interactive }) = a.command {
+        if let Err(err) = init(&dir, interactive) {
+            error!("failed to init {}: {}", dir, err);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(Commands::Logs(q)) = a.command {
+        if let Err(err) = logs(&q) {
+            error!("failed to query logs {}: {}", q.path, err);
+            process::exit(1);
+        }
+        return;
+    }
+
     let mut c = Config {
         config_file: a.config_file,
+        config_format: a.config_format,
+        profile: a.profile,
+        overrides: a.overrides,
         version_info: a.version_info,
         ..Default::default()
     };
 
+    if let Some(Commands::Config(command)) = a.command {
+        process::exit(config(&mut c, command));
+    }
+
+    // A report needs no secrets, costs are left out when the config cannot be loaded
+    if let Some(Commands::Report { run_dir, output }) = a.command {
+        let pricing = match c.config() {
+            Ok(()) => prices(&c.config_data),
+            Err(err) => {
+                warn!("reporting without costs, failed to load config: {}", err);
+                BTreeMap::new()
+            }
+        };
+        if let Err(err) = report(&run_dir, output, &pricing) {
+            error!("failed to report on {}: {}", run_dir, err);
+            process::exit(1);
+        }
+        return;
+    }
+
     if let Err(err) = c.build() {
-        println!("failed to build config: {}", err);
+        error!("failed to build config: {}", err);
         process::exit(-2);
     }
 
-    let _ = LLM::new(c.config_data.clone());
-    let _ = Logger::new("");
-    let _ = Optimizer::new(c.config_data.clone());
+    let redactor = match Redactor::from_config(&c.config_data) {
+        Ok(redactor) => redactor,
+        Err(err) => {
+            error!("failed to create redactor: {}", err);
+            process::exit(-2);
+        }
+    };
+
+    let log_config = c.config_data.logging.clone().unwrap_or_default();
+    logging(&handle, None, &log_config, a.verbosity, &redactor);
+
+    let l = match LLM::new(c.config_data.clone()) {
+        Ok(l) => l,
+        Err(err) => {
+            error!("failed to create llm: {}", err);
+            process::exit(-3);
+        }
+    };
+
+    if let Some(Commands::Models { probe, bench }) = a.command {
+        models(l, probe, bench).await;
+        return;
+    }
+
+    if let Some(Commands::RunTask { name, vars, input }) = a.command {
+        match run_task(l, &name, &vars, input).await {
+            Ok(output) => println!("{}", output),
+            Err(err) => {
+                error!("failed to run task {}: {}", name, err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut run = None;
+    if let Some(setup) = &c.config_data.setup {
+        let run_id = Some(a.run_id.as_str()).filter(|x| !x.is_empty());
+        match RunDir::create(setup, run_id, &c.version_info, &c.config_file) {
+            Ok(r) => {
+                println!("run directory: {}", r.root.display());
+                logging(
+                    &handle,
+                    Some(&r.logs()),
+                    &log_config,
+                    a.verbosity,
+                    &redactor,
+                );
+                run = Some(r);
+            }
+            Err(err) => {
+                error!("failed to create run directory: {}", err);
+                process::exit(-5);
+            }
+        }
+    }
+
+    let tracer = Tracer::new(LOGGER_NAME).with_redactor(redactor.clone());
+    let mut span = tracer.start(SPAN_RUN);
+    if let Some(run) = &run {
+        span.set("run.id", run.manifest.run_id.as_str());
+        span.set("run.experiment", run.manifest.experiment_name.as_str());
+    }
+
+    let mut status = STATUS_SUCCEEDED;
+    if let Some(run) = &run {
+        let dir = Path::new(&c.config_file).parent().unwrap_or(Path::new(""));
+        let optimizer =
+            Optimizer::new(c.config_data.clone(), dir, run.clone()).with_redactor(redactor.clone());
+
+        // Edits of the config reach the calls of a running optimization
+        let shared = Shared::new(l);
+        let watcher = Watcher::new(c.clone(), shared.clone()).spawn();
+        let result = optimizer.run(shared, &span).await;
+        watcher.abort();
+
+        if let Err(err) = result {
+            error!("failed to optimize prompt: {}", err);
+            span.fail(&err.to_string());
+            status = STATUS_FAILED;
+        }
+    }
+
+    span.end();
+
+    if let Some(mut run) = run {
+        if let Some(tracing) = &c.config_data.tracing {
+            traces(&tracer, &run, tracing).await;
+        }
+        for (rule, count) in redactor.hits() {
+            info!("redacted {} value(s) by rule {}", count, rule);
+        }
+        run.manifest.redactions = redactor.hits();
+        if let Err(err) = run.finish(status) {
+            error!("failed to write run manifest: {}", err);
+        }
+    }
+
+    if status == STATUS_FAILED {
+        process::exit(1);
+    }
+}
+
+async fn traces(tracer: &Tracer, run: &RunDir, tracing: &TracingConfig) {
+    if tracing.file {
+        if let Err(err) = tracer.write(run.root.join(TRACES_FILE)) {
+            error!("failed to write traces: {}", err);
+        }
+    }
+
+    if let Some(endpoint) = &tracing.endpoint {
+        if let Err(err) = tracer.export(endpoint).await {
+            error!("failed to export traces: {}", err);
+        }
+    }
+}
+
+// Reconfigure the application log, the previous config is kept on error
+fn logging(
+    handle: &Option<Handle>,
+    log_dir: Option<&Path>,
+    config: &LoggingConfig,
+    verbosity: i8,
+    redactor: &Redactor,
+) {
+    let Some(handle) = handle else {
+        return;
+    };
+
+    let log_dir = log_dir.map(|x| x.display().to_string());
+    if let Err(err) = AppLogger::reset_logger(
+        handle,
+        LOGGER_NAME,
+        log_dir.as_deref(),
+        config,
+        verbosity,
+        redactor,
+    ) {
+        error!("failed to set logger: {:#}", err);
+    }
+}
+
+async fn models(l: LLM, probe: bool, bench: Option<usize>) {
+    let m = Models::new(l);
+    let names = m.llm.list_model_type();
+
+    println!("{}", Models::render_list(&m.list()));
+
+    if probe {
+        let mut results = Vec::new();
+        for name in &names {
+            results.push(m.probe(name).await);
+        }
+        println!("\n{}", Models::render_probe(&results));
+    }
+
+    if let Some(calls) = bench {
+        let mut results = Vec::new();
+        for name in &names {
+            match m.bench(name, calls).await {
+                Ok(result) => results.push(result),
+                Err(err) => error!("failed to bench {}: {}", name, err),
+            }
+        }
+        println!("\n{}", Models::render_bench(&results));
+    }
+}
+
+async fn run_task(
+    l: LLM,
+    name: &str,
+    vars: &[String],
+    input: Option<String>,
+) -> Result<String, Box<dyn Error>> {
+    let input = match input.as_deref() {
+        Some("-") => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            Some(input)
+        }
+        Some(file) => Some(fs::read_to_string(file)?),
+        None => None,
+    };
+
+    let t = Tasks::new(l);
+    t.run(name, &variables(input.as_deref(), vars)?).await
+}
+
+fn logs(q: &LogsQuery) -> Result<(), Box<dyn Error>> {
+    let filter = Filter {
+        method: q.method.clone(),
+        since: q.since.as_deref().map(query::parse_time).transpose()?,
+        until: q.until.as_deref().map(query::parse_time).transpose()?,
+        id: q.id.clone(),
+        text: q.grep.clone(),
+    };
+
+    let records = query::query(&q.path, &filter)?;
+    let records = match q.slowest {
+        Some(n) => query::slowest(&records, n),
+        None => records,
+    };
+
+    if q.stats {
+        println!("{}", query::render_stats(&query::stats(&records)));
+    } else if q.format == "jsonl" {
+        for record in &records {
+            println!("{}", record);
+        }
+    } else {
+        println!("{}", query::render_records(&records));
+    }
+
+    Ok(())
+}
+
+fn report(
+    run_dir: &str,
+    output: Option<String>,
+    pricing: &BTreeMap<String, ModelPrice>,
+) -> Result<(), Box<dyn Error>> {
+    let r = Report::load(run_dir, pricing)?;
+
+    let output =
+        output.unwrap_or_else(|| Path::new(run_dir).join(OUTPUTS_DIR).display().to_string());
+    for path in r.write(output)? {
+        println!("created {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn config(c: &mut Config, command: ConfigCommands) -> i32 {
+    if let ConfigCommands::Convert { to, output } = command {
+        return match config_convert(c, to, output) {
+            Ok(()) => 0,
+            Err(err) => {
+                error!("failed to convert config: {}", err);
+                1
+            }
+        };
+    }
+
+    let diagnostics = c.validate();
+    for d in &diagnostics {
+        println!("{}", d);
+    }
+
+    if !diagnostics.is_empty() {
+        println!("{} problem(s) found", diagnostics.len());
+        return 1;
+    }
+
+    if let ConfigCommands::Show { origin } = command {
+        match c.layers.show(origin) {
+            Ok(s) => println!("{}", s.trim_end()),
+            Err(err) => {
+                error!("failed to show config: {}", err);
+                return 1;
+            }
+        }
+    } else {
+        println!("{}: ok", c.config_file);
+    }
+
+    0
+}
+
+fn config_convert(
+    c: &Config,
+    to: Option<String>,
+    output: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let to = match (&to, &output) {
+        (Some(to), _) => Format::from_name(to),
+        (None, Some(output)) => Format::from_path(output),
+        (None, None) => None,
+    }
+    .ok_or("missing target format, use --to or an output file with a known extension")?;
+
+    let converted = c.convert(to)?;
+    match output {
+        Some(output) => fs::write(output, converted)?,
+        None => print!("{}", converted),
+    }
+
+    Ok(())
+}
+
+fn init(dir: &str, interactive: bool) -> Result<(), Box<dyn Error>> {
+    let i = match interactive {
+        true => Init::ask(&mut io::stdin().lock(), &mut io::stdout())?,
+        false => Init::default(),
+    };
+
+    for path in i.write(dir)? {
+        println!("created {}", path.display());
+    }
+    println!(
+        "set {} and run: promptx -c {} models --probe",
+        i.key_env,
+        Path::new(dir).join(CONFIG_FILE).display()
+    );
+
+    Ok(())
+}
+
+fn secrets(command: SecretsCommands) -> Result<(), Box<dyn Error>> {
+    let s = Secrets::open()?;
+
+    match command {
+        // The value never goes on the command line, where it would end up in the shell history
+        SecretsCommands::Set { name } => {
+            let value = if io::stdin().is_terminal() {
+                rpassword::prompt_password(format!("Value of {}: ", name))?
+            } else {
+                let mut value = String::new();
+                io::stdin().read_to_string(&mut value)?;
+                value.trim_end_matches(['\r', '\n']).to_string()
+            };
+            if value.is_empty() {
+                return Err(format!("empty value for secret {}", name).into());
+            }
+            s.set(&name, &value)?;
+        }
+        SecretsCommands::Get { name } => match s.get(&name)? {
+            Some(value) => println!("{}", value),
+            None => return Err(format!("secret {} not found", name).into()),
+        },
+        SecretsCommands::List => {
+            for name in s.list()? {
+                println!("{}", name);
+            }
+        }
+        SecretsCommands::Rm { name } => {
+            if !s.remove(&name)? {
+                return Err(format!("secret {} not found", name).into());
+            }
+        }
+    }
+
+    Ok(())
 }
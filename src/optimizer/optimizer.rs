@@ -1,51 +1,130 @@
 use crate::config::config::ConfigData;
-use crate::llm::base::{SetupConfig, UniversalBase};
-use crate::llm::llm::LLM;
-use crate::llm::utils::Logger;
-use crate::logger::logger::Logger as IOLogger;
+use crate::config::watch::Shared;
+use crate::init::init::{OPTIMIZER_FILE, PROMPT_POOL_FILE};
+use crate::jsonl::jsonl;
+use crate::llm::base::{CritiqueNRefineParams, SetupConfig};
+use crate::llm::llm::{ChatOptions, ChatResponse, ExpectedToolCall, Message, ModelError, ToolCall};
+use crate::llm::schema::fields_match;
+use crate::llm::utils::FileUtils;
+use crate::logger::async_logger::AsyncLogger;
+use crate::logger::run_dir::RunDir;
+use crate::logger::tracer::{
+    ActiveSpan, SPAN_CRITIQUE, SPAN_EXAMPLE_SYNTHESIS, SPAN_LLM_CALL, SPAN_MUTATION, SPAN_REFINE,
+    SPAN_ROUND, SPAN_SCORING,
+};
+use crate::logger::utils::CallResult;
+use crate::redact::redact::Redactor;
 use log::{debug, info};
 use rand::prelude::SliceRandom;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::collections::HashMap;
-use std::fmt;
-
-#[derive(Clone, Default)]
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+// A dataset row with `question`, `answer` (reasoning) and `final_answer`
+pub type Example = Value;
+
+// Prompt, score and the examples it was scored on
+pub type PromptScore = (String, f64, Vec<Example>);
+
+const LOG_SEPARATOR: &str = "+++++++++++++++++++++++++++++++++++++++++++++++++";
+
+// Chained log in the io logs, candidate checkpoints and outputs of a run
+pub const CHAINED_LOG_NAME: &str = "best_prompt";
+pub const CANDIDATES_FILE: &str = "candidates.jsonl";
+pub const CRITIQUES_FILE: &str = "critiques.jsonl";
+pub const SYNTHETIC_FILE: &str = "train_synthetic.jsonl";
+pub const BEST_PROMPT_FILE: &str = "best_prompt.txt";
+pub const EXPERT_IDENTITY_FILE: &str = "expert_identity.txt";
+
+// Keys of the chained log and checkpoint rows, a row is a round best, a candidate, a critique
+// or the final prompt depending on which of them it holds
+pub const ROUND_NUM: &str = "round_num";
+pub const BEST_PROMPT: &str = "best_prompt";
+pub const CANDIDATE: &str = "candidate";
+pub const SCORE: &str = "score";
+pub const CRITIQUE: &str = "critique";
+pub const PROMPT: &str = "prompt";
+pub const FINAL_PROMPT: &str = "final_prompt";
+pub const EXPERT_IDENTITY: &str = "expert_identity";
+pub const FEW_SHOT_EXAMPLES: &str = "few_shot_examples";
+
+static TEXT_DELIMITER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(DatasetSpecificProcessing::TEXT_DELIMITER_PATTERN).unwrap());
+static ANSWER_DELIMITER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(DatasetSpecificProcessing::ANSWER_DELIMITER_PATTERN).unwrap());
+
+#[derive(Clone)]
 pub struct Optimizer {
     pub config: ConfigData,
+    // Directory of the config file, the prompt pool, the dataset and, without an
+    // `optimizer` section, the optimizer parameters are read from it
+    pub dir: PathBuf,
+    // IO logs, checkpoints and outputs are written into the run directory
+    pub run: RunDir,
+    pub redactor: Redactor,
 }
 
 impl Optimizer {
-    pub fn new(config: ConfigData) -> Self {
-        Optimizer { config }
+    pub fn new<P: AsRef<Path>>(config: ConfigData, dir: P, run: RunDir) -> Self {
+        Optimizer {
+            config,
+            dir: dir.as_ref().to_path_buf(),
+            run,
+            redactor: Redactor::default(),
+        }
     }
 
-    // TBD: FIXME
-}
+    // Applied to the io logs and checkpoints
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct PromptOptimizationParams {
-    prompt_technique_name: String,
-}
+    // Best prompt and expert identity for the dataset. Without a dataset, synthetic
+    // examples are generated when `num_train_examples` is set, otherwise prompt
+    // variations are only logged. Rounds, stages and LLM calls are traced under `span`.
+    pub async fn run(
+        &self,
+        llm: Shared,
+        span: &ActiveSpan,
+    ) -> Result<(String, String), Box<dyn Error>> {
+        let setup = self.config.setup.clone().ok_or("missing setup section")?;
+
+        let mut params = match &self.config.optimizer {
+            Some(params) => params.clone(),
+            None => FileUtils::yaml_to_class(Some(self.dir.join(OPTIMIZER_FILE)), None)?,
+        };
+        let prompt_pool: CritiqueNRefinePromptPool =
+            FileUtils::yaml_to_class(Some(self.dir.join(PROMPT_POOL_FILE)), None)?;
 
-impl fmt::Display for PromptOptimizationParams {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "[(\"prompt_technique_name\", \"{}\")]",
-            self.prompt_technique_name
-        )
-    }
-}
+        let dataset_file = self.dir.join(&setup.dataset);
+        let mut dataset: Vec<Example> = match dataset_file.exists() {
+            true => jsonl::read(&dataset_file)?,
+            false => Vec::new(),
+        };
+        dataset.truncate(params.seen_set_size.max(0) as usize);
 
-impl UniversalBase for PromptOptimizationParams {}
+        let iolog = AsyncLogger::new(self.run.io_logs())?.with_redactor(self.redactor.clone());
+        let synthetic = dataset.is_empty() && params.num_train_examples > 0;
+        let without_examples = dataset.is_empty() && !synthetic;
 
-impl PromptOptimizationParams {
-    pub fn new(prompt_technique_name: String) -> Self {
-        PromptOptimizationParams {
-            prompt_technique_name,
-        }
+        let mut technique = CritiqueNRefine::new(
+            llm,
+            dataset,
+            setup,
+            DatasetSpecificProcessing {},
+            prompt_pool,
+            self.run.clone(),
+            iolog,
+        )?;
+
+        technique
+            .get_best_prompt(&mut params, true, without_examples, synthetic, span)
+            .await
     }
 }
 
@@ -63,15 +142,22 @@ impl DatasetSpecificProcessing {
     const QUESTION_LITERAL: &'static str = "question";
     const ANSWER_WITH_REASON_LITERAL: &'static str = "answer";
     const FINAL_ANSWER_LITERAL: &'static str = "final_answer";
+    const EXPECTED_TOOL_CALL_LITERAL: &'static str = "expected_tool_call";
     const QUESTION_KEY_IN_PROMPT: &'static str = "[Question]";
     const ANSWER_KEY_IN_PROMPT: &'static str = "[Answer]";
-    const TEXT_DELIMITER_PATTERN: &'static str = r"(?s)(?<=<START>)(.*?)(?=<END>)";
-    const TEXT_DELIMITER_PATTERN_MUTATION: &'static str = r"(?s)(?<=<START>)(.*?)(?=<END>)";
+    const TEXT_DELIMITER_PATTERN: &'static str = r"(?s)<START>(.*?)<END>";
     const ANSWER_START: &'static str = "<ANS_START>";
     const ANSWER_END: &'static str = "<ANS_END>";
-    const ANSWER_DELIMITER_PATTERN: &'static str = r"(?s)(?<=<ANS_START>)(.*?)(?=<ANS_END>)";
-    const INVALID_ANS: &'static str = "[invalid]";
-    const FINAL_PROMPT: &'static str = "";
+    const ANSWER_DELIMITER_PATTERN: &'static str = r"(?s)<ANS_START>(.*?)<ANS_END>";
+
+    // A field of an example as text, numbers are written out
+    fn field(example: &Example, key: &str) -> String {
+        match &example[key] {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            other => other.to_string(),
+        }
+    }
 
     fn normalize_prediction(&self, prediction: &str, lowercase: bool) -> String {
         let mut normalized = prediction
@@ -103,40 +189,85 @@ impl DatasetSpecificProcessing {
 
     fn assess_answer(&self, llm_output: &str, gt_answer: &str) -> (bool, String) {
         let predicted_answer = self.extract_final_answer(llm_output);
-        let is_correct = predicted_answer.to_lowercase() == gt_answer.to_lowercase();
+        let is_correct = predicted_answer.to_lowercase() == gt_answer.trim().to_lowercase();
         (is_correct, predicted_answer)
     }
 
-    fn collate_to_str(examples: &[HashMap<String, String>], example_template: &str) -> String {
+    // Votes are compared in the form `assess_answer` checks against the ground truth
+    fn majority_vote(&self, answers: &[&str]) -> String {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+
+        for answer in answers {
+            let normalized = self.extract_final_answer(answer).to_lowercase();
+            match counts.iter_mut().find(|(x, _)| *x == normalized) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((normalized, 1)),
+            }
+        }
+
+        // Ties go to the answer seen first
+        counts
+            .into_iter()
+            .fold(
+                (String::new(), 0),
+                |best, x| if x.1 > best.1 { x } else { best },
+            )
+            .0
+    }
+
+    // `gt_answer` is a JSON value or the same encoded as a string
+    fn assess_json_answer(&self, predicted: &Value, gt_answer: &Value) -> bool {
+        let expected = match gt_answer {
+            Value::String(s) => serde_json::from_str(s).unwrap_or_else(|_| gt_answer.clone()),
+            other => other.clone(),
+        };
+        fields_match(&expected, predicted)
+    }
+
+    // `expected_tool_call` is an object with `name` and `arguments`, or the same as a string
+    fn assess_tool_call(&self, tool_calls: &[ToolCall], expected_tool_call: &Value) -> bool {
+        let expected = match expected_tool_call {
+            Value::String(s) => serde_json::from_str::<ExpectedToolCall>(s),
+            other => serde_json::from_value(other.clone()),
+        };
+
+        match expected {
+            Ok(expected) => tool_calls.iter().any(|x| x.matches(&expected)),
+            Err(_) => false,
+        }
+    }
+
+    fn collate_to_str(&self, examples: &[Example], example_template: &str) -> String {
         let mut example_string = String::new();
 
         for example in examples {
-            let answer = if example.contains_key(Self::ANSWER_WITH_REASON_LITERAL) {
-                &example[Self::ANSWER_WITH_REASON_LITERAL]
-            } else {
-                &example[Self::FINAL_ANSWER_LITERAL]
+            let answer = match Self::field(example, Self::ANSWER_WITH_REASON_LITERAL) {
+                answer if answer.is_empty() => Self::field(example, Self::FINAL_ANSWER_LITERAL),
+                answer => answer,
             };
 
             example_string.push_str(
                 &example_template
-                    .replace(
-                        "{question}",
-                        &example[DatasetSpecificProcessing::QUESTION_LITERAL],
-                    )
-                    .replace("{answer}", answer),
+                    .replace("{question}", &Self::field(example, Self::QUESTION_LITERAL))
+                    .replace("{answer}", &answer),
             );
         }
 
         example_string
     }
 
+    // The text between the answer tags, or the whole answer without them
     fn extract_final_answer(&self, answer: &str) -> String {
-        answer.to_string()
+        match ANSWER_DELIMITER.captures(answer) {
+            Some(caps) => caps[1].trim().to_string(),
+            None => answer.trim().to_string(),
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CritiqueNRefinePromptPool {
+    #[serde(flatten)]
     pub base: PromptPool,
     pub quest_reason_ans: String,
     pub expert_profile: String,
@@ -156,123 +287,132 @@ pub struct CritiqueNRefinePromptPool {
     pub examples_critique_template_zero_shot: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CritiqueNRefineParams {
-    pub base: PromptOptimizationParams,
-    pub unique_model_id: String,
-    // Number of candidate prompts to generate in given iteration
-    pub style_variation: i32,
-    // Number of questions to be asked to LLM in a single go
-    pub questions_batch_size: i32,
-    // Number of batches of questions to correctly answered, for a prompt to be considered as performing good
-    pub min_correct_count: i32,
-    // Max number of mini-batches on which we should evaluate our prompt
-    pub max_eval_batches: i32,
-    // Number of top best performing prompts to be considered for next iterations
-    pub top_n: i32,
-    // Number of rounds of mutation to be performed when generating different styles
-    pub mutation_rounds: i32,
-    // Refine instruction post mutation
-    pub refine_instruction: bool,
-    // Number of iterations for conducting <mutation_rounds> rounds of mutation of task description
-    // followed by refinement of instructions
-    pub mutate_refine_iterations: i32,
-    // Number of iterations for refining task description and in context examples for few-shot
-    pub refine_task_eg_iterations: i32,
-    // Description of task. This will be fed to prompt
-    pub task_description: String,
-    // Base instruction, in line with your dataset. This will be fed to prompt
-    pub base_instruction: String,
-    // Instruction for specifying answer format
-    pub answer_format: String,
-    // Number of samples from dataset, set aside as training data. In every iteration we would be drawing
-    // `questions_batch_size` examples from training data with replacement.
-    pub seen_set_size: i32,
-    // Number of examples to be given for few shots
-    pub few_shot_count: i32,
-    // Generate synthetic reasoning
-    pub generate_reasoning: bool,
-    // Generate description of an expert which can solve the task at hand
-    pub generate_expert_identity: bool,
-    // Generate keywords that describe the intent of the task
-    pub generate_intent_keywords: bool,
-    // number of synthetic training examples to be generated
-    pub num_train_examples: i32,
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum PromptScoreIndex {
-    PromptStr = 0,
-    Score = 1,
-    Dataset = 2,
-}
-
 pub struct CritiqueNRefine {
-    dataset: Vec<serde_json::Value>,
+    // Every call uses the LLM state current when it starts, a config reload applies to
+    // the next call
+    llm: Shared,
+    dataset: Vec<Example>,
     setup_config: SetupConfig,
     data_processor: DatasetSpecificProcessing,
-    logger: Logger,
     prompt_pool: CritiqueNRefinePromptPool,
-    base_path: String,
-    iolog: IOLogger,
+    run: RunDir,
+    // Shared by every call of the run, records of one method go to `<method>.jsonl`
+    iolog: AsyncLogger,
 }
 
 impl CritiqueNRefine {
     pub fn new(
-        dataset: Vec<serde_json::Value>,
+        llm: Shared,
+        dataset: Vec<Example>,
         setup_config: SetupConfig,
         data_processor: DatasetSpecificProcessing,
-        logger: Logger,
         prompt_pool: CritiqueNRefinePromptPool,
-        base_path: String,
-    ) -> Self {
-        let mut iolog = IOLogger::new(base_path.clone()).expect("failed to create iologger");
-        iolog
-            .reset_eval_glue(base_path.clone())
-            .expect("failed to reset eval glue");
-
-        Self {
+        run: RunDir,
+        iolog: AsyncLogger,
+    ) -> Result<Self, Box<dyn Error>> {
+        for dir in [run.io_logs(), run.checkpoints(), run.outputs()] {
+            fs::create_dir_all(dir)?;
+        }
+
+        Ok(Self {
+            llm,
             dataset,
             setup_config,
             data_processor,
-            logger,
             prompt_pool,
-            base_path,
+            run,
             iolog,
+        })
+    }
+
+    // Every call to the assistant LLM is traced as an `llm_call` span under `span`
+    async fn chat(
+        &self,
+        messages: Vec<Message>,
+        options: ChatOptions,
+        span: &ActiveSpan,
+    ) -> Result<ChatResponse, Box<dyn Error>> {
+        let model = &self.setup_config.assistant_llm.prompt_opt;
+        let mut call = span.child(SPAN_LLM_CALL);
+        let inputs = json!({"model": model, "messages": messages, "options": options});
+        let llm = self.llm.current();
+
+        let response = match options.n {
+            Some(n) if n > 1 => {
+                let future = llm.chat_choices(model.clone(), messages, options);
+                self.iolog
+                    .log_io_params_for_method("chat_choices", &inputs, async {
+                        CallResult(future.await)
+                    })
+                    .await?
+            }
+            _ => {
+                let future = llm.chat(model.clone(), messages, options);
+                self.iolog
+                    .log_io_params_for_method("chat", &inputs, async { CallResult(future.await) })
+                    .await?
+            }
         }
+        .0;
+        call.record_chat(model, &response);
+
+        response
     }
 
-    pub async fn chat_completion(&self, user_prompt: &str, system_prompt: Option<&str>) -> String {
+    pub async fn chat_completion(
+        &self,
+        user_prompt: &str,
+        system_prompt: Option<&str>,
+        span: &ActiveSpan,
+    ) -> Result<String, Box<dyn Error>> {
         let system_prompt = system_prompt.unwrap_or(&self.prompt_pool.base.system_prompt);
+        let messages = vec![Message::system(system_prompt), Message::user(user_prompt)];
+        let options = ChatOptions {
+            temperature: Some(0.0),
+            ..Default::default()
+        };
+
+        let response = self.chat(messages, options, span).await?;
+        Ok(response.content().unwrap_or_default().to_string())
+    }
 
-        let messages = vec![
-            json!({
-                "role": "system",
-                "content": system_prompt
-            }),
-            json!({
-                "role": "user",
-                "content": user_prompt
-            }),
-        ];
+    // `num_completions` answers sampled at `temperature`, a single one is asked for greedily
+    pub async fn chat_completions(
+        &self,
+        user_prompt: &str,
+        system_prompt: Option<&str>,
+        params: &CritiqueNRefineParams,
+        span: &ActiveSpan,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let system_prompt = system_prompt.unwrap_or(&self.prompt_pool.base.system_prompt);
+        let n = params.num_completions.max(1);
 
-        LLM.chat_completion(messages).await
+        let messages = vec![Message::system(system_prompt), Message::user(user_prompt)];
+        let options = ChatOptions {
+            temperature: Some(if n > 1 { params.temperature } else { 0.0 }),
+            n: Some(n as u32),
+            ..Default::default()
+        };
+
+        Ok(self.chat(messages, options, span).await?.contents())
     }
 
     pub async fn gen_different_styles(
         &self,
         base_instruction: &str,
-        task_description: &str,
-        mutation_rounds: usize,
-        thinking_styles_count: usize,
-    ) -> Vec<String> {
-        let mut candidate_prompts = vec![format!("{}\n{}", task_description, base_instruction)];
-
-        for mutation_round in 0..mutation_rounds {
+        params: &CritiqueNRefineParams,
+        span: &ActiveSpan,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut candidate_prompts =
+            vec![format!("{}\n{}", params.task_description, base_instruction)];
+        let thinking_styles_count =
+            (params.style_variation.max(0) as usize).min(self.prompt_pool.thinking_styles.len());
+
+        for mutation_round in 0..params.mutation_rounds + 1 {
             let mutated_sample_prompt = self
                 .prompt_pool
                 .meta_sample_template
-                .replace("{task_description}", task_description)
+                .replace("{task_description}", &params.task_description)
                 .replace(
                     "{meta_prompts}",
                     &self.prompt_pool.thinking_styles[..thinking_styles_count].join("\n"),
@@ -280,34 +420,31 @@ impl CritiqueNRefine {
                 .replace("{num_variations}", &thinking_styles_count.to_string())
                 .replace("{prompt_instruction}", base_instruction);
 
-            let generated_mutated_prompt = self.chat_completion(&mutated_sample_prompt, None).await;
-
-            let re = Regex::new(&self.data_processor.text_delimiter_pattern).unwrap();
-            let matches: Vec<String> = re
-                .find_iter(&generated_mutated_prompt)
-                .map(|m| m.as_str().to_string())
-                .collect();
+            let generated_mutated_prompt = self
+                .chat_completions(&mutated_sample_prompt, None, params, span)
+                .await?
+                .join("\n");
 
-            candidate_prompts.extend(matches);
+            candidate_prompts.extend(captures(&TEXT_DELIMITER, &generated_mutated_prompt));
 
             info!(
-                "{}",
-                &format!(
-                    "mutation_round={} mutated_sample_prompt={} mutated_prompt_generation={}",
-                    mutation_round, mutated_sample_prompt, generated_mutated_prompt
-                )
+                "mutation_round={} mutated_sample_prompt={} mutated_prompt_generation={}",
+                mutation_round, mutated_sample_prompt, generated_mutated_prompt
             );
         }
 
-        candidate_prompts
+        Ok(candidate_prompts)
     }
 
+    // Critique of `prompt` and the prompt refined from it. The critique and the refinement
+    // are traced as stages under `span`.
     pub async fn critique_and_refine(
         &self,
         prompt: &str,
-        critique_example_set: &[serde_json::Value],
+        critique_example_set: &[Example],
         further_enhance: bool,
-    ) -> String {
+        span: &ActiveSpan,
+    ) -> Result<(String, String), Box<dyn Error>> {
         let example_string = self
             .data_processor
             .collate_to_str(critique_example_set, &self.prompt_pool.quest_reason_ans);
@@ -326,8 +463,9 @@ impl CritiqueNRefine {
             .chat_completion(
                 &meta_critique_prompt,
                 Some(&self.prompt_pool.expert_profile),
+                &span.child(SPAN_CRITIQUE),
             )
-            .await;
+            .await?;
 
         let critique_refine_prompt = self
             .prompt_pool
@@ -341,143 +479,232 @@ impl CritiqueNRefine {
             .chat_completion(
                 &critique_refine_prompt,
                 Some(&self.prompt_pool.expert_profile),
+                &span.child(SPAN_REFINE),
             )
-            .await;
-
-        if let Ok(re) = Regex::new(&self.data_processor.text_delimiter_pattern.as_str()) {
-            if let Some(caps) = re.captures(&refined_prompts) {
-                info!(
-                    "{}",
-                    &format!(
-                        "Prompt to get critique: {}\n\
-                    Critique received from LLM: {}\n\
-                    Prompt to get Refinement after critique, from LLM: {}\n\
-                    Refined prompts received from LLM: {}",
-                        meta_critique_prompt,
-                        critique_text,
-                        critique_refine_prompt,
-                        caps[1].to_string()
-                    )
-                );
-                return Ok(caps[1].to_string());
-            }
-        }
+            .await?;
+
+        let refined_prompt = captures(&TEXT_DELIMITER, &refined_prompts)
+            .into_iter()
+            .next()
+            .ok_or("The LLM output is not in the expected format. Please rerun the code...")?;
+
+        info!(
+            "Prompt to get critique: {}\n\
+            Critique received from LLM: {}\n\
+            Prompt to get Refinement after critique, from LLM: {}\n\
+            Refined prompts received from LLM: {}",
+            meta_critique_prompt, critique_text, critique_refine_prompt, refined_prompt
+        );
 
-        Err("The LLM output is not in the expected format. Please rerun the code...".into())
+        Ok((critique_text, refined_prompt))
     }
 
-    fn get_prompt_score(
+    async fn get_prompt_score(
         &self,
-        instructions: Vec<String>,
-        params: PromptOptimizationParams,
-    ) -> Vec<(String, f64, Vec<HashMap<String, String>>)> {
+        instructions: &[String],
+        params: &CritiqueNRefineParams,
+        span: &ActiveSpan,
+    ) -> Result<Vec<PromptScore>, Box<dyn Error>> {
+        let batch_size = params.questions_batch_size.max(1) as usize;
         let mut prompt_score_list = Vec::new();
-        let mut rng = rand::thread_rng();
 
         for instruction in instructions {
             let mut correct_count = 0.0;
             let mut count = 0.0;
             let mut critique_example_set = Vec::new();
-
-            let mut dataset_subset: Vec<_> = self
-                .dataset
-                .choose_multiple(&mut rng, params.questions_batch_size)
-                .collect();
-            let mut questions_pool: Vec<_> = dataset_subset
-                .iter()
-                .map(|example| {
-                    example
-                        .get(DatasetSpecificProcessing::QUESTION_LITERAL)
-                        .unwrap()
-                })
-                .collect();
+            let mut dataset_subset = self.sample(batch_size);
 
             while critique_example_set.is_empty()
                 && correct_count < params.min_correct_count as f64
                 && count < params.max_eval_batches as f64
             {
                 count += 1.0;
-                let solve_prompt = self
-                    .prompt_pool
-                    .solve_template
-                    .replace(
-                        "{questions_batch_size}",
-                        &params.questions_batch_size.to_string(),
-                    )
-                    .replace("{answer_format}", &params.answer_format)
-                    .replace("{instruction}", &instruction)
-                    .replace("{questions}", &questions_pool.join("\n"));
-
-                let generated_text = self.chat_completion(&solve_prompt);
-                critique_example_set = self.evaluate(&generated_text, &dataset_subset);
+                critique_example_set = self
+                    .solve(instruction, &dataset_subset, params, span)
+                    .await?;
 
+                // Every question was answered correctly, go on with new ones
                 if critique_example_set.is_empty() {
-                    dataset_subset = self
-                        .dataset
-                        .choose_multiple(&mut rng, params.questions_batch_size)
-                        .collect();
-                    questions_pool = dataset_subset
-                        .iter()
-                        .map(|example| {
-                            example
-                                .get(DatasetSpecificProcessing::QUESTION_LITERAL)
-                                .unwrap()
-                        })
-                        .collect();
+                    dataset_subset = self.sample(batch_size);
                     correct_count += 1.0;
                 }
 
-                println!("critique_example_set: {:?}", critique_example_set);
-                println!("correct_count: {}", correct_count);
+                debug!("critique_example_set: {:?}", critique_example_set);
+                debug!("correct_count: {}", correct_count);
             }
-            println!("Loop completed");
-            prompt_score_list.push((instruction, correct_count / count, dataset_subset));
+            debug!("Loop completed");
+
+            let score = match count > 0.0 {
+                true => correct_count / count,
+                false => 0.0,
+            };
+            prompt_score_list.push((instruction.clone(), score, dataset_subset));
         }
 
         info!("prompt_score_list {:?}", prompt_score_list);
 
-        prompt_score_list
+        Ok(prompt_score_list)
     }
 
-    pub fn refine_prompts(
+    // Examples of `batch` the instruction gets wrong. With tools or a JSON schema, each question
+    // is asked on its own and examples carrying `expected_tool_call` are scored on the tool called.
+    pub async fn solve(
         &self,
-        prompt_score_list: Vec<(String, f64, Vec<Example>)>,
-        params: &PromptOptimizationParams,
-    ) -> Vec<String> {
+        instruction: &str,
+        batch: &[Example],
+        params: &CritiqueNRefineParams,
+        span: &ActiveSpan,
+    ) -> Result<Vec<Example>, Box<dyn Error>> {
+        if params.tools.is_empty() && params.json_schema.is_none() {
+            let solve_prompt = self.solve_prompt(instruction, batch, params);
+            let generated_texts = self
+                .chat_completions(&solve_prompt, None, params, span)
+                .await?;
+            return Ok(self.evaluate_self_consistent(&generated_texts, batch));
+        }
+
+        // Tool calls and JSON answers are scored one question at a time
+        let mut wrong_examples = Vec::new();
+        for example in batch {
+            let example = std::slice::from_ref(example);
+            let solve_prompt = self.solve_prompt(instruction, example, params);
+            let messages = vec![
+                Message::system(&self.prompt_pool.base.system_prompt),
+                Message::user(&solve_prompt),
+            ];
+
+            let is_correct = match &params.json_schema {
+                Some(schema) if params.tools.is_empty() => {
+                    self.solve_json(messages, &example[0], schema, span).await?
+                }
+                _ => {
+                    self.solve_tools(messages, &example[0], params, span)
+                        .await?
+                }
+            };
+            if !is_correct {
+                wrong_examples.extend_from_slice(example);
+            }
+        }
+
+        Ok(wrong_examples)
+    }
+
+    async fn solve_json(
+        &self,
+        messages: Vec<Message>,
+        example: &Example,
+        schema: &Value,
+        span: &ActiveSpan,
+    ) -> Result<bool, Box<dyn Error>> {
+        let model = &self.setup_config.assistant_llm.prompt_opt;
+        let mut call = span.child(SPAN_LLM_CALL);
+        let inputs = json!({"model": model, "messages": messages, "schema": schema});
+        let llm = self.llm.current();
+        let future = llm.chat_json(model.clone(), messages, Some(schema.clone()));
+        let answer = self
+            .iolog
+            .log_io_params_for_method("chat_json", &inputs, async { CallResult(future.await) })
+            .await?
+            .0;
+        call.record_chat(
+            model,
+            &answer.as_ref().map(|(_, response)| response.clone()),
+        );
+
+        match answer {
+            Ok((answer, _)) => Ok(example
+                .get(DatasetSpecificProcessing::FINAL_ANSWER_LITERAL)
+                .is_some_and(|x| self.data_processor.assess_json_answer(&answer, x))),
+            // An answer still invalid after the retry is wrong, not a failed run
+            Err(err) if matches!(err.downcast_ref(), Some(ModelError::ValidationError(_))) => {
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn solve_tools(
+        &self,
+        messages: Vec<Message>,
+        example: &Example,
+        params: &CritiqueNRefineParams,
+        span: &ActiveSpan,
+    ) -> Result<bool, Box<dyn Error>> {
+        let options = ChatOptions {
+            tools: params.tools.clone(),
+            ..Default::default()
+        };
+
+        let response = self.chat(messages, options, span).await?;
+
+        Ok(
+            match example.get(DatasetSpecificProcessing::EXPECTED_TOOL_CALL_LITERAL) {
+                Some(expected) => self
+                    .data_processor
+                    .assess_tool_call(response.tool_calls(), expected),
+                // No answer at all is wrong too
+                None => captures(&ANSWER_DELIMITER, response.content().unwrap_or_default())
+                    .first()
+                    .is_some_and(|x| {
+                        let actual_answer = DatasetSpecificProcessing::field(
+                            example,
+                            DatasetSpecificProcessing::FINAL_ANSWER_LITERAL,
+                        );
+                        self.data_processor.assess_answer(x, &actual_answer).0
+                    }),
+            },
+        )
+    }
+
+    fn solve_prompt(
+        &self,
+        instruction: &str,
+        batch: &[Example],
+        params: &CritiqueNRefineParams,
+    ) -> String {
+        let questions: Vec<String> = batch
+            .iter()
+            .map(|x| {
+                DatasetSpecificProcessing::field(x, DatasetSpecificProcessing::QUESTION_LITERAL)
+            })
+            .collect();
+
+        self.prompt_pool
+            .solve_template
+            .replace("{questions_batch_size}", &batch.len().to_string())
+            .replace("{answer_format}", &params.answer_format)
+            .replace("{instruction}", instruction)
+            .replace("{questions}", &questions.join("\n"))
+    }
+
+    // Critique and refined prompt for each prompt of `prompt_score_list`
+    pub async fn refine_prompts(
+        &self,
+        prompt_score_list: &[PromptScore],
+        params: &CritiqueNRefineParams,
+        span: &ActiveSpan,
+    ) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let threshold = params.min_correct_count as f64 / params.max_eval_batches.max(1) as f64;
         let mut refined_prompts = Vec::new();
 
         for (prompt, score, critique_example_set) in prompt_score_list {
-            let threshold = params.min_correct_count / params.max_eval_batches as f64;
-            if score >= threshold {
-                // Good prompt refinement
-                refined_prompts.push(self.critique_and_refine(
-                    &prompt,
-                    &critique_example_set,
-                    true,
-                ));
-            } else {
-                // Not good enough prompt refinement
-                refined_prompts.push(self.critique_and_refine(
-                    &prompt,
-                    &critique_example_set,
-                    false,
-                ));
-            }
+            // Good prompts are enhanced further, the others are corrected
+            let further_enhance = *score >= threshold;
+            refined_prompts.push(
+                self.critique_and_refine(prompt, critique_example_set, further_enhance, span)
+                    .await?,
+            );
         }
 
         info!("refined_prompts: {:?}", refined_prompts);
 
-        refined_prompts
+        Ok(refined_prompts)
     }
 
-    pub fn evaluate(
-        &self,
-        generated_text: &str,
-        dataset_subset: &[HashMap<String, String>],
-    ) -> Vec<HashMap<String, String>> {
-        let re = Regex::new(DatasetSpecificProcessing::ANSWER_DELIMITER_PATTERN).unwrap();
-        let mut answer_matches: Vec<&str> =
-            re.find_iter(generated_text).map(|m| m.as_str()).collect();
+    // Examples of `dataset_subset` answered wrongly in `generated_text`
+    pub fn evaluate(&self, generated_text: &str, dataset_subset: &[Example]) -> Vec<Example> {
+        let answer_matches = captures(&ANSWER_DELIMITER, generated_text);
 
         let answers_len = answer_matches.len();
         let dataset_len = dataset_subset.len();
@@ -487,67 +714,99 @@ impl CritiqueNRefine {
                 "Answers extracted from LLM output={}, Questions asked to LLM {}",
                 answers_len, dataset_len,
             );
-            if answers_len > dataset_len {
-                answer_matches.truncate(dataset_len);
+        }
+
+        let mut wrong_examples = Vec::new();
+        for (answer, example) in answer_matches.iter().zip(dataset_subset) {
+            debug!("dataset_subset: {:?}", dataset_subset);
+            let actual_answer = DatasetSpecificProcessing::field(
+                example,
+                DatasetSpecificProcessing::FINAL_ANSWER_LITERAL,
+            );
+            let (is_correct, _) = self.data_processor.assess_answer(answer, &actual_answer);
+            if !is_correct {
+                wrong_examples.push(example.clone());
             }
         }
 
+        wrong_examples
+    }
+
+    // Majority vote over the answers of several completions of the same solve prompt
+    pub fn evaluate_self_consistent(
+        &self,
+        generated_texts: &[String],
+        dataset_subset: &[Example],
+    ) -> Vec<Example> {
+        if generated_texts.len() <= 1 {
+            let generated_text = generated_texts.first().map(|x| x.as_str()).unwrap_or("");
+            return self.evaluate(generated_text, dataset_subset);
+        }
+
+        let answers: Vec<Vec<String>> = generated_texts
+            .iter()
+            .map(|x| captures(&ANSWER_DELIMITER, x))
+            .collect();
+
         let mut wrong_examples = Vec::new();
-        for i in 0..std::cmp::min(answers_len, dataset_len) {
-            println!("dataset_subset: {:?}", dataset_subset);
-            let actual_answer = &dataset_subset[i][DatasetSpecificProcessing::FINAL_ANSWER_LITERAL];
-            let question = &dataset_subset[i][DatasetSpecificProcessing::QUESTION_LITERAL];
+        for (i, example) in dataset_subset.iter().enumerate() {
+            let votes: Vec<&str> = answers
+                .iter()
+                .filter_map(|x| x.get(i).map(|x| x.as_str()))
+                .collect();
+            if votes.is_empty() {
+                continue;
+            }
+
+            let voted_answer = self.data_processor.majority_vote(&votes);
+            let actual_answer = DatasetSpecificProcessing::field(
+                example,
+                DatasetSpecificProcessing::FINAL_ANSWER_LITERAL,
+            );
             let (is_correct, _) = self
                 .data_processor
-                .access_answer(answer_matches[i], actual_answer);
+                .assess_answer(&voted_answer, &actual_answer);
             if !is_correct {
-                wrong_examples.push(dataset_subset[i].clone());
+                wrong_examples.push(example.clone());
             }
         }
 
         wrong_examples
     }
 
+    // Best scores first, longer prompts first on a tie
     pub fn select_top_prompts(
         &self,
-        prompt_score_list: Vec<(String, f64, Vec<Example>)>,
+        prompt_score_list: Vec<PromptScore>,
         top_n: usize,
-    ) -> Vec<(String, f64, Vec<Example>)> {
+    ) -> Vec<PromptScore> {
         let mut sorted_prompts = prompt_score_list;
-        sorted_prompts.sort_by(|a, b| {
-            b.1.partial_cmp(&a.1)
-                .unwrap()
-                .then_with(|| b.0.len().cmp(&a.0.len()))
-        });
+        sorted_prompts.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| b.0.len().cmp(&a.0.len())));
 
-        let sorted_top_n_prompts = sorted_prompts.into_iter().take(top_n).collect();
+        let sorted_top_n_prompts: Vec<PromptScore> =
+            sorted_prompts.into_iter().take(top_n.max(1)).collect();
 
         debug!("Sorted top n prompts: {:?}", sorted_top_n_prompts);
 
         sorted_top_n_prompts
     }
 
-    pub fn extract_examples_from_response(
-        &self,
-        response_with_examples: &str,
-    ) -> Vec<HashMap<String, String>> {
+    pub fn extract_examples_from_response(&self, response_with_examples: &str) -> Vec<Example> {
         let mut synthetic_examples = Vec::new();
-        let re = Regex::new(DatasetSpecificProcessing::TEXT_DELIMITER_PATTERN).unwrap();
 
-        for cap in re.find_iter(response_with_examples) {
-            let text = cap.as_str().trim();
+        for text in captures(&TEXT_DELIMITER, response_with_examples) {
+            let text = text.trim();
 
             if text.contains(DatasetSpecificProcessing::QUESTION_KEY_IN_PROMPT)
                 && text.contains(DatasetSpecificProcessing::ANSWER_KEY_IN_PROMPT)
             {
-                let question = self
-                    .extract_between(
-                        DatasetSpecificProcessing::QUESTION_KEY_IN_PROMPT,
-                        DatasetSpecificProcessing::ANSWER_KEY_IN_PROMPT,
-                        text,
-                    )
-                    .trim()
-                    .to_string();
+                let question = Self::extract_between(
+                    DatasetSpecificProcessing::QUESTION_KEY_IN_PROMPT,
+                    DatasetSpecificProcessing::ANSWER_KEY_IN_PROMPT,
+                    text,
+                )
+                .trim()
+                .to_string();
 
                 let answer_with_reason = text[text
                     .find(DatasetSpecificProcessing::ANSWER_KEY_IN_PROMPT)
@@ -556,43 +815,29 @@ impl CritiqueNRefine {
                     .trim()
                     .to_string();
 
-                let final_answer = match &self.data_processor {
-                    Some(processor) => processor.extract_final_answer(&answer_with_reason),
-                    None => self.extract_between(
-                        DatasetSpecificProcessing::ANSWER_START,
-                        DatasetSpecificProcessing::ANSWER_END,
-                        &answer_with_reason,
-                    ),
-                };
-
-                let mut formatted_data = HashMap::new();
-                formatted_data.insert(
-                    DatasetSpecificProcessing::QUESTION_LITERAL.to_string(),
-                    question,
-                );
-                formatted_data.insert(
-                    DatasetSpecificProcessing::ANSWER_WITH_REASON_LITERAL.to_string(),
-                    answer_with_reason,
-                );
-                formatted_data.insert(
-                    DatasetSpecificProcessing::FINAL_ANSWER_LITERAL.to_string(),
-                    final_answer,
-                );
+                let final_answer = self
+                    .data_processor
+                    .extract_final_answer(&answer_with_reason);
 
-                synthetic_examples.push(formatted_data);
+                synthetic_examples.push(json!({
+                    DatasetSpecificProcessing::QUESTION_LITERAL: question,
+                    DatasetSpecificProcessing::ANSWER_WITH_REASON_LITERAL: answer_with_reason,
+                    DatasetSpecificProcessing::FINAL_ANSWER_LITERAL: final_answer,
+                }));
             }
         }
 
         synthetic_examples
     }
 
-    pub fn generate_reasoning(
+    pub async fn generate_reasoning(
         &self,
         task_description: &str,
         instruction: &str,
         question: &str,
         answer: &str,
-    ) -> String {
+        span: &ActiveSpan,
+    ) -> Result<String, Box<dyn Error>> {
         let prompt_template = self
             .prompt_pool
             .generate_reason_template
@@ -601,33 +846,43 @@ impl CritiqueNRefine {
             .replace("{question}", question)
             .replace("{answer}", answer);
 
-        self.llm_mgr.chat_completion(&prompt_template)
+        self.chat_completion(&prompt_template, None, span).await
     }
 
-    pub fn generate_expert_identity(&self, task_description: &str) -> String {
+    pub async fn generate_expert_identity(
+        &self,
+        task_description: &str,
+        span: &ActiveSpan,
+    ) -> Result<String, Box<dyn Error>> {
         let expert_prompt = self
             .prompt_pool
             .expert_template
             .replace("{task_description}", task_description);
 
-        self.llm_mgr.chat_completion(&expert_prompt)
+        self.chat_completion(&expert_prompt, None, span).await
     }
 
-    pub fn generate_intent_keywords(&self, task_description: &str, instruction: &str) -> String {
+    pub async fn generate_intent_keywords(
+        &self,
+        task_description: &str,
+        instruction: &str,
+        span: &ActiveSpan,
+    ) -> Result<String, Box<dyn Error>> {
         let prompt_template = self
             .prompt_pool
             .intent_template
             .replace("{task_description}", task_description)
             .replace("{instruction}", instruction);
 
-        self.llm_mgr.chat_completion(&prompt_template)
+        self.chat_completion(&prompt_template, None, span).await
     }
 
-    pub fn generate_best_examples(
+    pub async fn generate_best_examples(
         &self,
-        examples: &[HashMap<String, String>],
+        examples: &[Example],
         params: &CritiqueNRefineParams,
-    ) -> Vec<HashMap<String, String>> {
+        span: &ActiveSpan,
+    ) -> Result<Vec<Example>, Box<dyn Error>> {
         let example_string = self
             .data_processor
             .collate_to_str(examples, &self.prompt_pool.quest_reason_ans);
@@ -640,18 +895,17 @@ impl CritiqueNRefine {
             .replace("{task_description}", &params.task_description)
             .replace("{num_examples}", &params.few_shot_count.to_string());
 
-        let critique = self.llm_mgr.chat_completion_with_system(
-            &few_shot_critique_prompt,
-            &self.prompt_pool.expert_profile,
-        );
+        let critique = self
+            .chat_completion(
+                &few_shot_critique_prompt,
+                Some(&self.prompt_pool.expert_profile),
+                span,
+            )
+            .await?;
 
-        let gt_eg = self
-            .dataset
-            .choose(&mut rand::thread_rng())
-            .expect("Dataset should not be empty");
         let gt_eg_string = self
             .data_processor
-            .collate_to_str(&[gt_eg.clone()], &self.prompt_pool.quest_reason_ans);
+            .collate_to_str(&self.sample(1), &self.prompt_pool.quest_reason_ans);
 
         let few_shot_opt_prompt = self
             .prompt_pool
@@ -664,16 +918,21 @@ impl CritiqueNRefine {
             .replace("{num_examples}", &params.few_shot_count.to_string());
 
         let synthetic_examples = self
-            .llm
-            .chat_completion(&few_shot_opt_prompt, &self.prompt_pool.expert_profile);
+            .chat_completion(
+                &few_shot_opt_prompt,
+                Some(&self.prompt_pool.expert_profile),
+                span,
+            )
+            .await?;
 
-        self.extract_examples_from_response(&synthetic_examples)
+        Ok(self.extract_examples_from_response(&synthetic_examples))
     }
 
     pub async fn generate_best_examples_zero_shot(
         &self,
         params: &CritiqueNRefineParams,
-    ) -> Vec<Example> {
+        span: &ActiveSpan,
+    ) -> Result<Vec<Example>, Box<dyn Error>> {
         let few_shot_critique_prompt = self
             .prompt_pool
             .examples_critique_template_zero_shot
@@ -684,9 +943,10 @@ impl CritiqueNRefine {
         let critique = self
             .chat_completion(
                 &few_shot_critique_prompt,
-                self.prompt_pool.expert_profile.as_deref(),
+                Some(&self.prompt_pool.expert_profile),
+                span,
             )
-            .await;
+            .await?;
 
         let few_shot_opt_prompt = self
             .prompt_pool
@@ -699,75 +959,92 @@ impl CritiqueNRefine {
             .replace("{num_examples}", &params.num_train_examples.to_string());
 
         let synthetic_examples = self
-            .chat_completion(
+            .chat_completions(
                 &few_shot_opt_prompt,
-                self.prompt_pool.expert_profile.as_deref(),
+                Some(&self.prompt_pool.expert_profile),
+                params,
+                span,
             )
-            .await;
+            .await?;
 
-        self.extract_examples_from_response(&synthetic_examples)
+        Ok(synthetic_examples
+            .iter()
+            .flat_map(|x| self.extract_examples_from_response(x))
+            .collect())
     }
 
+    // Critique of the instruction and the instruction refined from it, if the LLM gave one.
+    // The critique and the refinement are traced as stages under `span`.
     pub async fn get_best_instr_by_critique(
         &self,
-        examples: Vec<HashMap<String, String>>,
+        examples: &[Example],
         params: &CritiqueNRefineParams,
-    ) -> Option<String> {
-        let example_string = if let Some(ref data_processor) = self.data_processor {
-            // Assuming collate_to_str is a method of DataProcessor
-            data_processor.collate_to_str(&examples, &self.prompt_pool.quest_reason_ans)
-        } else {
-            examples
-                .iter()
-                .map(|example| {
-                    let answer = example
-                        .get(DatasetSpecificProcessing::FINAL_ANSWER_LITERAL)
-                        .or_else(|| {
-                            example.get(DatasetSpecificProcessing::ANSWER_WITH_REASON_LITERAL)
-                        })
-                        .unwrap_or(&DatasetSpecificProcessing::INVALID_ANS.to_string());
-                    format!(
-                        self.prompt_pool.quest_reason_ans,
-                        question = example
-                            .get(DatasetSpecificProcessing::QUESTION_LITERAL)
-                            .unwrap_or(&String::new()),
-                        answer = answer
-                    )
-                })
-                .collect::<String>()
-        };
+        span: &ActiveSpan,
+    ) -> Result<(String, Option<String>), Box<dyn Error>> {
+        let example_string = self
+            .data_processor
+            .collate_to_str(examples, &self.prompt_pool.quest_reason_ans);
 
-        let meta_critique_prompt = format!(
-            self.prompt_pool.meta_critique_template,
-            instruction = params.base_instruction,
-            examples = example_string
-        );
-        let critique_text =
-            self.chat_completion(&meta_critique_prompt, &self.prompt_pool.expert_profile);
-        let critique_refine_prompt = format!(
-            self.prompt_pool.critique_refine_template,
-            instruction = params.base_instruction,
-            examples = example_string,
-            critique = critique_text,
-            steps_per_sample = 1
-        );
-        let refined_prompts = self.chat_completion(&critique_refine_prompt);
-
-        let refined_instructions = if let Some(ref data_processor) = self.data_processor {
-            data_processor
-                .TEXT_DELIMITER_PATTERN
-                .find_iter(&refined_prompts)
-                .map(|mat| mat.as_str().to_string())
-                .collect::<Vec<String>>()
-        } else {
-            Regex::new(DatasetSpecificProcessing::TEXT_DELIMITER_PATTERN)
-                .unwrap()
-                .find_iter(&refined_prompts)
-                .map(|mat| mat.as_str().to_string())
-                .collect::<Vec<String>>()
-        };
+        let meta_critique_prompt = self
+            .prompt_pool
+            .meta_critique_template
+            .replace("{instruction}", &params.base_instruction)
+            .replace("{examples}", &example_string);
+        let critique_text = self
+            .chat_completion(
+                &meta_critique_prompt,
+                Some(&self.prompt_pool.expert_profile),
+                &span.child(SPAN_CRITIQUE),
+            )
+            .await?;
+
+        let critique_refine_prompt = self
+            .prompt_pool
+            .critique_refine_template
+            .replace("{instruction}", &params.base_instruction)
+            .replace("{examples}", &example_string)
+            .replace("{critique}", &critique_text)
+            .replace("{steps_per_sample}", "1");
+        let refined_prompts = self
+            .chat_completion(&critique_refine_prompt, None, &span.child(SPAN_REFINE))
+            .await?;
+
+        let refined_prompt = captures(&TEXT_DELIMITER, &refined_prompts)
+            .into_iter()
+            .next();
+
+        Ok((critique_text, refined_prompt))
+    }
+
+    // Append rows to a JSONL file of the checkpoints, redacted like the io logs
+    fn checkpoint(&self, file_name: &str, rows: &[Value]) -> Result<(), Box<dyn Error>> {
+        let rows: Vec<Value> = rows
+            .iter()
+            .map(|x| self.iolog.redactor.redact_value(x))
+            .collect();
+        jsonl::append(self.run.checkpoints().join(file_name), &rows)?;
 
-        refined_instructions.get(0).cloned()
+        Ok(())
+    }
+
+    // Every scored candidate of a round, for the leaderboard of the report
+    fn checkpoint_candidates(
+        &self,
+        round_num: i32,
+        prompt_score_list: &[PromptScore],
+    ) -> Result<(), Box<dyn Error>> {
+        let rows: Vec<Value> = prompt_score_list
+            .iter()
+            .map(|(prompt, score, _)| {
+                json!({
+                    ROUND_NUM: round_num,
+                    CANDIDATE: prompt,
+                    SCORE: score
+                })
+            })
+            .collect();
+
+        self.checkpoint(CANDIDATES_FILE, &rows)
     }
 
     pub async fn get_best_prompt(
@@ -776,204 +1053,262 @@ impl CritiqueNRefine {
         use_examples: bool,
         run_without_train_examples: bool,
         generate_synthetic_examples: bool,
-    ) -> (String, String) {
+        span: &ActiveSpan,
+    ) -> Result<(String, String), Box<dyn Error>> {
         let mut current_base_instruction = params.base_instruction.clone();
+        let few_shot_count = params.few_shot_count.max(0) as usize;
+
+        if generate_synthetic_examples {
+            info!("Generating Synthetic Examples");
+            let train_examples = self
+                .generate_best_examples_zero_shot(params, &span.child(SPAN_EXAMPLE_SYNTHESIS))
+                .await?;
+            let path = self.run.outputs().join(SYNTHETIC_FILE);
+            jsonl::write(&path, &train_examples)?;
+            info!("Synthetic examples saved at {}", path.display());
+            return Ok((String::new(), String::new()));
+        }
 
-        if !generate_synthetic_examples {
-            println!("\nMutating Task Description....");
-            for round_num in 1..=params.mutate_refine_iterations {
-                info!(&format!(
-                    "{} + Starting iteration: {} \n current_base_instruction: {}",
-                    CommonLogsStr::LOG_SEPERATOR,
-                    round_num,
-                    current_base_instruction
-                ));
-                let candidate_prompts = self.gen_different_styles(
-                    &current_base_instruction,
-                    &params.task_description,
-                    params.mutation_rounds + 1,
-                    params.style_variation,
-                );
+        info!("Mutating Task Description");
+        for round_num in 1..=params.mutate_refine_iterations {
+            info!(
+                "{} Starting iteration: {} \n current_base_instruction: {}",
+                LOG_SEPARATOR, round_num, current_base_instruction
+            );
+            let mut round = span.child(SPAN_ROUND);
+            round.set("round.num", round_num);
 
-                if run_without_train_examples {
-                    let mut prompt_index = 1;
-                    println!("\nOptimization Finished...");
-                    println!("\nPossible prompt variations:");
-                    for candidate in &candidate_prompts[..params.mutation_rounds] {
-                        let final_best_prompt = self
-                            .prompt_pool
-                            .base
-                            .final_prompt
-                            .replace("{instruction}", candidate)
-                            .replace("{answer_format}", &params.answer_format)
-                            .replace("{few_shot_examples}", "");
-                        let mut expert_identity = self.prompt_pool.base.system_prompt.clone();
-                        if params.generate_expert_identity {
-                            expert_identity =
-                                self.generate_expert_identity(&params.task_description);
+            let candidate_prompts = self
+                .gen_different_styles(
+                    &current_base_instruction,
+                    params,
+                    &round.child(SPAN_MUTATION),
+                )
+                .await?;
+
+            if run_without_train_examples {
+                info!("Optimization Finished");
+                info!("Possible prompt variations:");
+                let variations =
+                    (params.mutation_rounds.max(0) as usize).min(candidate_prompts.len());
+                for (prompt_index, candidate) in candidate_prompts[..variations].iter().enumerate()
+                {
+                    let final_best_prompt = self
+                        .prompt_pool
+                        .base
+                        .final_prompt
+                        .replace("{instruction}", candidate)
+                        .replace("{answer_format}", &params.answer_format)
+                        .replace("{few_shot_examples}", "");
+                    let expert_identity = match params.generate_expert_identity {
+                        true => {
+                            self.generate_expert_identity(&params.task_description, &round)
+                                .await?
                         }
-                        let intent_keywords = self.generate_intent_keywords(
+                        false => self.prompt_pool.base.system_prompt.clone(),
+                    };
+                    let intent_keywords = self
+                        .generate_intent_keywords(
                             &params.task_description,
                             &params.base_instruction,
-                        );
-                        let final_best_prompt =
-                            format!("{}Keywords: {}", final_best_prompt, intent_keywords);
-                        println!("_______________________________________________________________________");
-                        println!(
-                            "\nVariations {}:\nExpert Profile:\n{}:\nPrompt:\n{}",
-                            prompt_index, expert_identity, final_best_prompt
-                        );
-                        prompt_index += 1;
-                    }
-                    return ("".to_string(), "".to_string());
-                }
-                let mut prompt_score_list = self.get_prompt_score(&candidate_prompts, params);
-                prompt_score_list = self.select_top_prompts(&prompt_score_list, params.top_n);
-
-                if params.refine_instruction {
-                    let refined_prompts = self.refine_prompts(&prompt_score_list, params);
-                    let refined_prompt_score_list = self.get_prompt_score(&refined_prompts, params);
-                    prompt_score_list = self.select_top_prompts(
-                        &(refined_prompt_score_list + prompt_score_list),
-                        params.top_n,
+                            &round,
+                        )
+                        .await?;
+                    info!(
+                        "Variations {}:\nExpert Profile:\n{}:\nPrompt:\n{}Keywords: {}",
+                        prompt_index + 1,
+                        expert_identity,
+                        final_best_prompt,
+                        intent_keywords
                     );
                 }
-
-                current_base_instruction =
-                    prompt_score_list[0][self.GetPromptScoreIndex.PROMPT_STR].clone();
-                self.iolog.append_dict_to_chained_logs(&json!({"round_num": round_num, "best_prompt": current_base_instruction, "score": prompt_score_list[0][self.GetPromptScoreIndex.SCORE]}));
+                return Ok((String::new(), String::new()));
             }
 
-            let mut examples = Vec::new();
-            params.base_instruction = current_base_instruction.clone();
-            for example in &self.dataset {
-                let solve_prompt = self
-                    .prompt_pool
-                    .solve_template
-                    .replace("{questions_batch_size}", "1")
-                    .replace("{instruction}", &params.base_instruction)
-                    .replace("{answer_format}", &params.answer_format)
-                    .replace(
-                        "{questions}",
-                        &example[DatasetSpecificProcessing::QUESTION_LITERAL],
-                    );
-                let generated_text = self.chat_completion(&solve_prompt);
-                examples.extend(self.evaluate(&generated_text, &[example]));
-                if examples.len() >= params.few_shot_count {
-                    break;
-                }
+            let prompt_score_list = self
+                .get_prompt_score(&candidate_prompts, params, &round.child(SPAN_SCORING))
+                .await?;
+            self.checkpoint_candidates(round_num, &prompt_score_list)?;
+            let mut prompt_score_list =
+                self.select_top_prompts(prompt_score_list, params.top_n.max(1) as usize);
+
+            if params.refine_instruction {
+                let refinements = self
+                    .refine_prompts(&prompt_score_list, params, &round)
+                    .await?;
+                let critiques: Vec<Value> = prompt_score_list
+                    .iter()
+                    .zip(&refinements)
+                    .map(|((prompt, _, _), (critique, _))| {
+                        json!({
+                            ROUND_NUM: round_num,
+                            PROMPT: prompt,
+                            CRITIQUE: critique
+                        })
+                    })
+                    .collect();
+                self.checkpoint(CRITIQUES_FILE, &critiques)?;
+
+                let refined_prompts: Vec<String> =
+                    refinements.into_iter().map(|(_, x)| x).collect();
+                let mut refined_prompt_score_list = self
+                    .get_prompt_score(&refined_prompts, params, &round.child(SPAN_SCORING))
+                    .await?;
+                self.checkpoint_candidates(round_num, &refined_prompt_score_list)?;
+                refined_prompt_score_list.extend(prompt_score_list);
+                prompt_score_list = self
+                    .select_top_prompts(refined_prompt_score_list, params.top_n.max(1) as usize);
             }
 
-            if examples.len() < params.few_shot_count {
-                examples.extend(self.dataset.choose_multiple(
-                    &mut rand::thread_rng(),
-                    params.few_shot_count - examples.len(),
-                ));
+            let (best_prompt, score, _) = &prompt_score_list[0];
+            round.set("round.score", *score);
+            round.end();
+            current_base_instruction = best_prompt.clone();
+            self.iolog.append_dict_to_chained_logs(json!({
+                ROUND_NUM: round_num,
+                BEST_PROMPT: current_base_instruction,
+                SCORE: score
+            }));
+            self.iolog.dump_chained_log_to_file(CHAINED_LOG_NAME)?;
+        }
+
+        // Examples the best prompt gets wrong make the best few-shot examples
+        let mut examples = Vec::new();
+        params.base_instruction = current_base_instruction.clone();
+        let scoring = span.child(SPAN_SCORING);
+        for example in &self.dataset {
+            if examples.len() >= few_shot_count {
+                break;
             }
+            examples.extend(
+                self.solve(
+                    &params.base_instruction,
+                    std::slice::from_ref(example),
+                    params,
+                    &scoring,
+                )
+                .await?,
+            );
+        }
+        scoring.end();
+
+        if examples.len() < few_shot_count {
+            examples.extend(self.sample(few_shot_count - examples.len()));
+        }
 
-            println!("\nRefining Task description and Examples iteratively....");
-            for _ in 0..params.refine_task_eg_iterations {
-                let refine_task_desc = rand::random::<bool>();
-                if refine_task_desc {
-                    if let Some(refined_instruction) =
-                        self.get_best_instr_by_critique(&examples, params).await
-                    {
-                        params.base_instruction = refined_instruction;
-                    }
-                } else if use_examples {
-                    examples = self.generate_best_examples(&examples, params);
+        info!("Refining Task description and Examples iteratively");
+        for _ in 0..params.refine_task_eg_iterations {
+            let refine_task_desc = rand::random::<bool>();
+            if refine_task_desc {
+                let (critique, refined_instruction) = self
+                    .get_best_instr_by_critique(&examples, params, span)
+                    .await?;
+                self.checkpoint(
+                    CRITIQUES_FILE,
+                    &[json!({
+                        PROMPT: params.base_instruction,
+                        CRITIQUE: critique
+                    })],
+                )?;
+                if let Some(refined_instruction) = refined_instruction {
+                    params.base_instruction = refined_instruction;
                 }
+            } else if use_examples {
+                examples = self
+                    .generate_best_examples(&examples, params, &span.child(SPAN_EXAMPLE_SYNTHESIS))
+                    .await?;
             }
-        } else {
-            println!("Generating Synthetic Examples....");
-            let train_examples = self.generate_best_examples_zero_shot(params);
-            let mut file = File::create("train_synthetic.jsonl").unwrap();
-            for record in train_examples {
-                writeln!(file, "{}", json!(record)).unwrap();
-            }
-            println!("Synthetic examples saved at train.jsonl....");
-            return ("".to_string(), "".to_string());
         }
 
         if params.generate_reasoning {
-            println!("\nGenerating CoT Reasoning for In-Context Examples....");
+            info!("Generating CoT Reasoning for In-Context Examples");
+            let synthesis = span.child(SPAN_EXAMPLE_SYNTHESIS);
             for example in &mut examples {
-                let reason = self.generate_reasoning(
-                    &params.task_description,
-                    &params.base_instruction,
-                    &example[DatasetSpecificProcessing::QUESTION_LITERAL],
-                    &example[DatasetSpecificProcessing::FINAL_ANSWER_LITERAL],
-                );
-                example.insert(
-                    DatasetSpecificProcessing::ANSWER_WITH_REASON_LITERAL.to_string(),
-                    format!(
-                        "{} {}{}{}",
-                        reason,
-                        DatasetSpecificProcessing::ANSWER_START,
-                        example[DatasetSpecificProcessing::FINAL_ANSWER_LITERAL],
-                        DatasetSpecificProcessing::ANSWER_END
-                    ),
+                let final_answer = DatasetSpecificProcessing::field(
+                    example,
+                    DatasetSpecificProcessing::FINAL_ANSWER_LITERAL,
                 );
+                let reason = self
+                    .generate_reasoning(
+                        &params.task_description,
+                        &params.base_instruction,
+                        &DatasetSpecificProcessing::field(
+                            example,
+                            DatasetSpecificProcessing::QUESTION_LITERAL,
+                        ),
+                        &final_answer,
+                        &synthesis,
+                    )
+                    .await?;
+                example[DatasetSpecificProcessing::ANSWER_WITH_REASON_LITERAL] = json!(format!(
+                    "{} {}{}{}",
+                    reason,
+                    DatasetSpecificProcessing::ANSWER_START,
+                    final_answer,
+                    DatasetSpecificProcessing::ANSWER_END
+                ));
             }
+            synthesis.end();
         }
 
-        let example_string = if let Some(data_processor) = &self.data_processor {
-            data_processor.collate_to_str(&examples, &self.prompt_pool.quest_reason_ans)
-        } else {
-            examples
-                .iter()
-                .map(|example| {
-                    let answer = example
-                        .get(DatasetSpecificProcessing::ANSWER_WITH_REASON_LITERAL)
-                        .unwrap_or(&example[DatasetSpecificProcessing::FINAL_ANSWER_LITERAL]);
-                    self.prompt_pool
-                        .quest_reason_ans
-                        .replace(
-                            "{question}",
-                            &example[DatasetSpecificProcessing::QUESTION_LITERAL],
-                        )
-                        .replace("{answer}", answer)
-                })
-                .collect::<Vec<_>>()
-                .join("")
+        let example_string = match few_shot_count {
+            0 => String::new(),
+            _ => self
+                .data_processor
+                .collate_to_str(&examples, &self.prompt_pool.quest_reason_ans),
         };
 
-        let mut final_best_prompt = if params.few_shot_count == 0 {
-            self.prompt_pool
-                .base
-                .final_prompt
-                .replace("{instruction}", &params.base_instruction)
-                .replace("{answer_format}", &params.answer_format)
-                .replace("{few_shot_examples}", "")
-        } else {
-            self.prompt_pool
-                .base
-                .final_prompt
-                .replace("{instruction}", &params.base_instruction)
-                .replace("{answer_format}", &params.answer_format)
-                .replace("{few_shot_examples}", &example_string)
-        };
+        let mut final_best_prompt = self
+            .prompt_pool
+            .base
+            .final_prompt
+            .replace("{instruction}", &params.base_instruction)
+            .replace("{answer_format}", &params.answer_format)
+            .replace("{few_shot_examples}", &example_string);
 
         let mut expert_identity = self.prompt_pool.base.system_prompt.clone();
         if params.generate_expert_identity {
-            println!("\nGenerating Expert Identity....");
-            expert_identity = self.generate_expert_identity(&params.task_description);
-            info!("{}", &format!("Expert Identity: {}", expert_identity));
+            info!("Generating Expert Identity");
+            expert_identity = self
+                .generate_expert_identity(&params.task_description, span)
+                .await?;
+            info!("Expert Identity: {}", expert_identity);
         }
 
         if params.generate_intent_keywords {
-            println!("\nGenerating Intent Keywords....");
-            let intent_keywords =
-                self.generate_intent_keywords(&params.task_description, &params.base_instruction);
+            info!("Generating Intent Keywords");
+            let intent_keywords = self
+                .generate_intent_keywords(&params.task_description, &params.base_instruction, span)
+                .await?;
             final_best_prompt.push_str(&format!("Keywords: {}", intent_keywords));
         }
 
-        self.iolog
-            .dump_chained_log_to_file("best_prompt")
-            .expect("failed to dump chained log");
-        info!("{}", &format!("Final best prompt: {}", final_best_prompt));
+        self.iolog.append_dict_to_chained_logs(json!({
+            FINAL_PROMPT: final_best_prompt,
+            EXPERT_IDENTITY: expert_identity,
+            FEW_SHOT_EXAMPLES: examples
+        }));
+        self.iolog.dump_chained_log_to_file(CHAINED_LOG_NAME)?;
+        self.iolog.flush().await?;
+        fs::write(
+            self.run.outputs().join(BEST_PROMPT_FILE),
+            &final_best_prompt,
+        )?;
+        fs::write(
+            self.run.outputs().join(EXPERT_IDENTITY_FILE),
+            &expert_identity,
+        )?;
+        info!("Final best prompt: {}", final_best_prompt);
+
+        Ok((final_best_prompt, expert_identity))
+    }
 
-        (final_best_prompt, expert_identity)
+    // Up to `n` distinct examples of the dataset, drawn at random
+    fn sample(&self, n: usize) -> Vec<Example> {
+        self.dataset
+            .choose_multiple(&mut rand::thread_rng(), n)
+            .cloned()
+            .collect()
     }
 
     fn extract_between(start: &str, end: &str, text: &str) -> String {
@@ -990,3 +1325,8 @@ impl CritiqueNRefine {
         text[start_idx..end_idx].to_string()
     }
 }
+
+// The first group of every match
+fn captures(re: &Regex, text: &str) -> Vec<String> {
+    re.captures_iter(text).map(|x| x[1].to_string()).collect()
+}
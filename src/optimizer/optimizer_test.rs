@@ -1,4 +1,357 @@
-#[test]
-fn test_optimizer() {
-    assert_eq!(true, true);
+use super::optimizer::*;
+use crate::config::config::ConfigData;
+use crate::config::watch::Shared;
+use crate::init::init::{OPTIMIZER_TEMPLATE, PROMPT_POOL_TEMPLATE};
+use crate::jsonl::jsonl;
+use crate::llm::base::CritiqueNRefineParams;
+use crate::llm::llm::{Tool, LLM};
+use crate::logger::async_logger::AsyncLogger;
+use crate::logger::query::{self, Filter};
+use crate::logger::run_dir::RunDir;
+use crate::logger::tracer::{
+    Tracer, SPAN_LLM_CALL, SPAN_MUTATION, SPAN_ROUND, SPAN_RUN, SPAN_SCORING,
+};
+use crate::report::report::Report;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use wiremock::matchers::{body_partial_json, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const DATASET: &str = r#"{"question": "What is 6 x 12?", "answer": "6 x 12 = 72. <ANS_START>72<ANS_END>", "final_answer": "72"}
+{"question": "What is 8 x 9?", "answer": "8 x 9 = 72. <ANS_START>72<ANS_END>", "final_answer": 72}
+"#;
+
+fn config_data(api: &str, base_dir: &Path) -> ConfigData {
+    serde_yaml::from_str(&format!(
+        "llm:
+  - name: openai
+    api: {}/v1/chat/completions
+    key: test_key
+    endpoint: gpt-4o
+setup:
+  assistant_llm:
+    prompt_opt: openai
+  dir_info:
+    base_dir: {}
+    log_dir_name: glue_logs
+  experiment_name: test
+  mode: offline
+  description: test
+",
+        api,
+        base_dir.display()
+    ))
+    .unwrap()
+}
+
+fn params() -> CritiqueNRefineParams {
+    let mut params: CritiqueNRefineParams =
+        serde_yaml::from_str(&OPTIMIZER_TEMPLATE.replace("{provider}", "openai")).unwrap();
+    params.style_variation = 2;
+    params.mutation_rounds = 1;
+    params.mutate_refine_iterations = 1;
+    params.refine_task_eg_iterations = 1;
+    params.max_eval_batches = 2;
+    params.few_shot_count = 1;
+    params.generate_reasoning = false;
+    params
+}
+
+fn run_dir(config: &ConfigData) -> RunDir {
+    RunDir::create(config.setup.as_ref().unwrap(), None, "v0.1.0", "config.yml").unwrap()
+}
+
+fn technique(api: &str, base_dir: &Path, dataset: Vec<Value>) -> CritiqueNRefine {
+    let config = config_data(api, base_dir);
+    let run = run_dir(&config);
+    let iolog = AsyncLogger::new(run.io_logs()).unwrap();
+    CritiqueNRefine::new(
+        Shared::new(LLM::new(config.clone()).unwrap()),
+        dataset,
+        config.setup.unwrap(),
+        DatasetSpecificProcessing {},
+        serde_yaml::from_str(PROMPT_POOL_TEMPLATE).unwrap(),
+        run,
+        iolog,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_evaluate() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dataset: Vec<Value> = DATASET
+        .lines()
+        .map(|x| serde_json::from_str(x).unwrap())
+        .collect();
+    let t = technique("http://localhost", temp_dir.path(), dataset.clone());
+
+    let wrong = t.evaluate("<ANS_START> 72 <ANS_END>\n<ANS_START>71<ANS_END>", &dataset);
+    assert_eq!(wrong, vec![dataset[1].clone()]);
+
+    let texts = vec![
+        "<ANS_START>72<ANS_END>".to_string(),
+        "<ANS_START>70<ANS_END>".to_string(),
+        "<ANS_START>72.<ANS_END>".to_string(),
+    ];
+    assert!(t.evaluate_self_consistent(&texts, &dataset[..1]).is_empty());
+}
+
+#[tokio::test]
+async fn test_evaluate_self_consistent_keeps_signs_and_decimals() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dataset = vec![
+        json!({"question": "What is 7 / 2?", "final_answer": "3.5"}),
+        json!({"question": "What is 3 - 5?", "final_answer": "-2"}),
+    ];
+    let t = technique("http://localhost", temp_dir.path(), dataset.clone());
+
+    let texts = vec![
+        "<ANS_START>3.5<ANS_END>\n<ANS_START>-2<ANS_END>".to_string(),
+        "<ANS_START>3<ANS_END>\n<ANS_START>2<ANS_END>".to_string(),
+        "<ANS_START>3.5<ANS_END>\n<ANS_START>-2<ANS_END>".to_string(),
+    ];
+    assert!(t.evaluate_self_consistent(&texts, &dataset).is_empty());
+
+    let texts = vec![
+        "<ANS_START>3<ANS_END>\n<ANS_START>2<ANS_END>".to_string(),
+        "<ANS_START>3<ANS_END>\n<ANS_START>2<ANS_END>".to_string(),
+        "<ANS_START>3.5<ANS_END>\n<ANS_START>-2<ANS_END>".to_string(),
+    ];
+    assert_eq!(t.evaluate_self_consistent(&texts, &dataset), dataset);
+}
+
+#[tokio::test]
+async fn test_select_top_prompts() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let t = technique("http://localhost", temp_dir.path(), vec![]);
+
+    let scores = vec![
+        ("a".to_string(), 0.5, vec![]),
+        ("bb".to_string(), 1.0, vec![]),
+        ("ccc".to_string(), 0.5, vec![]),
+    ];
+    let top: Vec<String> = t
+        .select_top_prompts(scores, 2)
+        .into_iter()
+        .map(|x| x.0)
+        .collect();
+    assert_eq!(top, vec!["bb", "ccc"]);
+}
+
+#[tokio::test]
+async fn test_extract_examples_from_response() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let t = technique("http://localhost", temp_dir.path(), vec![]);
+
+    let response = "<START>[Question] What is 2 + 2?\n[Answer] 2 + 2 = 4 <ANS_START>4<ANS_END><END>
+<START>no question here<END>";
+    assert_eq!(
+        t.extract_examples_from_response(response),
+        vec![json!({
+            "question": "What is 2 + 2?",
+            "answer": "2 + 2 = 4 <ANS_START>4<ANS_END>",
+            "final_answer": "4"
+        })]
+    );
+}
+
+#[tokio::test]
+async fn test_optimizer_run() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "<START>Multiply carefully.<END> <ANS_START>72<ANS_END>"
+                }
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path();
+    fs::write(dir.join("prompt_pool.yml"), PROMPT_POOL_TEMPLATE).unwrap();
+    fs::create_dir_all(dir.join("data")).unwrap();
+    fs::write(dir.join("data/train.jsonl"), DATASET).unwrap();
+
+    let mut config = config_data(&mock_server.uri(), dir);
+    config.optimizer = Some(params());
+    let llm = Shared::new(LLM::new(config.clone()).unwrap());
+
+    let run = run_dir(&config);
+
+    let tracer = Tracer::new("promptx");
+    let span = tracer.start(SPAN_RUN);
+
+    let (prompt, expert) = Optimizer::new(config, dir, run.clone())
+        .run(llm, &span)
+        .await
+        .unwrap();
+    assert!(prompt.contains("<ANS_START> and <ANS_END>"));
+    assert!(expert.contains("Multiply carefully."));
+
+    let best = fs::read_to_string(run.io_logs().join("best_prompt.jsonl")).unwrap();
+    assert!(best.contains("\"round_num\":1"));
+    assert!(best.contains("\"final_prompt\""));
+    let candidates = fs::read_to_string(run.checkpoints().join(CANDIDATES_FILE)).unwrap();
+    assert!(candidates.contains("\"candidate\""));
+    assert_eq!(
+        fs::read_to_string(run.outputs().join(BEST_PROMPT_FILE)).unwrap(),
+        prompt
+    );
+    assert_eq!(
+        fs::read_to_string(run.outputs().join(EXPERT_IDENTITY_FILE)).unwrap(),
+        expert
+    );
+
+    // Every call is logged with its messages and options
+    let calls: Vec<Value> = jsonl::read(run.io_logs().join("chat.jsonl")).unwrap();
+    assert!(!calls.is_empty());
+    for call in &calls {
+        assert_eq!(call["inputs"]["model"], "openai");
+        assert_eq!(call["inputs"]["messages"][0]["role"], "system");
+        assert!(call["inputs"]["options"].is_object());
+        assert!(call["outputs"]["choices"].is_array());
+        assert_eq!(call["meta"]["method_name"], "chat");
+    }
+
+    // `promptx logs <run> --stats` on the run directory
+    let records = query::query(&run.root, &Filter::default()).unwrap();
+    let stats = query::stats(&records);
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].method_name, "chat");
+    assert_eq!(stats[0].calls, calls.len());
+    assert!(query::render_stats(&stats).contains("chat"));
+
+    let report = Report::load(&run.root, &BTreeMap::new()).unwrap();
+    assert_eq!(report.final_prompt.as_deref(), Some(prompt.as_str()));
+    assert_eq!(report.rounds.len(), 1);
+    assert!(!report.leaderboard.is_empty());
+    assert!(!report.critiques.is_empty());
+    // The random refinement may replace the examples, the mocked reply holds none
+    assert!(best.contains("\"few_shot_examples\":["));
+
+    span.end();
+    let spans = tracer.spans();
+    let round = spans.iter().find(|x| x.name == SPAN_ROUND).unwrap();
+    assert_eq!(round.attributes["round.num"], 1);
+    for stage in [SPAN_MUTATION, SPAN_SCORING] {
+        let stage = spans.iter().find(|x| x.name == stage).unwrap();
+        assert_eq!(stage.parent_span_id.as_ref(), Some(&round.span_id));
+    }
+    let calls: Vec<_> = spans.iter().filter(|x| x.name == SPAN_LLM_CALL).collect();
+    assert!(!calls.is_empty());
+    assert!(calls.iter().all(|x| x.attributes["llm.model"] == "openai"));
+
+    // Failed calls end the run
+    mock_server.reset().await;
+    let mut config = config_data(&mock_server.uri(), dir);
+    config.optimizer = Some(params());
+    let llm = Shared::new(LLM::new(config.clone()).unwrap());
+    let run = run_dir(&config);
+    let span = Tracer::new("promptx").start(SPAN_RUN);
+    assert!(Optimizer::new(config, dir, run)
+        .run(llm, &span)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_solve_with_tools() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({
+            "tools": [{"type": "function", "function": {"name": "get_weather"}}]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                    }]
+                }
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let t = technique(&mock_server.uri(), temp_dir.path(), vec![]);
+
+    let mut params = params();
+    params.tools = vec![Tool::function(
+        "get_weather",
+        "Get the current weather",
+        json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+    )];
+
+    let batch = vec![
+        json!({
+            "question": "Weather in Paris?",
+            "expected_tool_call": {"name": "get_weather", "arguments": {"city": "Paris"}}
+        }),
+        json!({
+            "question": "Weather in London?",
+            "expected_tool_call": "{\"name\": \"get_weather\", \"arguments\": {\"city\": \"London\"}}"
+        }),
+        json!({"question": "What is 6 x 12?", "final_answer": "72"}),
+    ];
+
+    let span = Tracer::new("promptx").start(SPAN_SCORING);
+    let wrong = t.solve("Answer", &batch, &params, &span).await.unwrap();
+    assert_eq!(wrong, batch[1..].to_vec());
+}
+
+#[tokio::test]
+async fn test_solve_with_json_schema() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({
+            "response_format": {"type": "json_schema"}
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{
+                "index": 0,
+                "message": {
+                    "role": "assistant",
+                    "content": "{\"city\": \"Paris\", \"population\": 2102650}"
+                }
+            }]
+        })))
+        .mount(&mock_server)
+        .await;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let t = technique(&mock_server.uri(), temp_dir.path(), vec![]);
+
+    let mut params = params();
+    params.json_schema = Some(json!({
+        "type": "object",
+        "properties": {"city": {"type": "string"}},
+        "required": ["city"]
+    }));
+
+    let batch = vec![
+        json!({"question": "Capital of France?", "final_answer": {"city": "paris"}}),
+        json!({"question": "Capital of England?", "final_answer": "{\"city\": \"London\"}"}),
+        json!({"question": "Capital of nowhere?"}),
+    ];
+
+    let span = Tracer::new("promptx").start(SPAN_SCORING);
+    let wrong = t.solve("Answer", &batch, &params, &span).await.unwrap();
+    assert_eq!(wrong, batch[1..].to_vec());
 }
@@ -0,0 +1,3 @@
+pub mod redact;
+#[cfg(test)]
+pub mod redact_test;
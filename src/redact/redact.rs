@@ -0,0 +1,202 @@
+use crate::config::config::ConfigData;
+use crate::llm::base::RedactConfig;
+use log4rs::encode::{writer::simple::SimpleWriter, Encode, Write};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+pub const KEYS_RULE: &str = "api_key";
+
+// Built-in rules by name
+pub const RULES: [(&str, &str); 5] = [
+    (
+        "email",
+        r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}",
+    ),
+    (
+        "phone",
+        r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{3}\)\s?|\b\d{3}[\s.-])\d{3}[\s.-]\d{4}\b",
+    ),
+    ("ssn", r"\b\d{3}-\d{2}-\d{4}\b"),
+    ("credit_card", r"\b\d{4}[\s-]\d{4}[\s-]\d{4}[\s-]\d{1,7}\b"),
+    ("token", r"\b(?:sk|pk|rk)-[A-Za-z0-9_-]{16,}"),
+];
+
+// Keys shorter than this are too likely to match ordinary text
+const MIN_KEY_LEN: usize = 8;
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    regex: Regex,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // Placeholder of each redacted value, per rule
+    placeholders: HashMap<(usize, String), String>,
+    // Number of distinct values per rule, used to number placeholders
+    distinct: HashMap<usize, usize>,
+    hits: BTreeMap<String, usize>,
+}
+
+// Replaces secrets and PII with placeholders such as [EMAIL_1]. The same value gets the
+// same placeholder for the lifetime of the redactor, clones share it. The default
+// redactor has no rules and leaves text unchanged.
+#[derive(Clone, Debug, Default)]
+pub struct Redactor {
+    rules: Arc<Vec<Rule>>,
+    state: Arc<Mutex<State>>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactConfig, keys: &[String]) -> Result<Self, String> {
+        let mut rules = Vec::new();
+
+        let mut keys: Vec<&String> = keys.iter().filter(|x| x.len() >= MIN_KEY_LEN).collect();
+        if config.keys && !keys.is_empty() {
+            // Longest first, a key containing another is replaced whole
+            keys.sort_by_key(|x| std::cmp::Reverse(x.len()));
+            let alternatives: Vec<String> = keys.iter().map(|x| regex::escape(x)).collect();
+            rules.push(Rule {
+                name: KEYS_RULE.to_string(),
+                regex: Regex::new(&alternatives.join("|")).map_err(|e| e.to_string())?,
+            });
+        }
+
+        for pattern in &config.patterns {
+            rules.push(Rule {
+                name: pattern.name.clone(),
+                regex: Regex::new(&pattern.regex)
+                    .map_err(|e| format!("invalid pattern '{}': {}", pattern.name, e))?,
+            });
+        }
+
+        for name in &config.rules {
+            let (_, regex) = RULES
+                .iter()
+                .find(|(x, _)| x == name)
+                .ok_or_else(|| format!("unknown rule '{}'", name))?;
+            rules.push(Rule {
+                name: name.clone(),
+                regex: Regex::new(regex).unwrap(),
+            });
+        }
+
+        Ok(Redactor {
+            rules: Arc::new(rules),
+            state: Arc::default(),
+        })
+    }
+
+    // Without a redact section the keys and the built-in rules are replaced, `rules: []` turns
+    // the built-in rules off
+    pub fn from_config(config: &ConfigData) -> Result<Self, String> {
+        let redact = config.redact.clone().unwrap_or_default();
+
+        let keys: Vec<String> = config
+            .llm
+            .iter()
+            .flat_map(|x| std::iter::once(x.key.clone()).chain(x.headers.values().cloned()))
+            .collect();
+
+        Self::new(&redact, &keys)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if self.rules.is_empty() {
+            return text;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !rule.regex.is_match(&text) {
+                continue;
+            }
+
+            let mut hits = 0;
+            text = rule
+                .regex
+                .replace_all(&text, |caps: &regex::Captures| {
+                    hits += 1;
+                    state.placeholder(index, &rule.name, &caps[0])
+                })
+                .into_owned();
+            *state.hits.entry(rule.name.clone()).or_default() += hits;
+        }
+
+        text
+    }
+
+    // Redact every string of a JSON value, keys included
+    pub fn redact_value(&self, value: &Value) -> Value {
+        if self.rules.is_empty() {
+            return value.clone();
+        }
+
+        match value {
+            Value::String(s) => Value::String(self.redact(s)),
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|x| self.redact_value(x)).collect())
+            }
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(k, v)| (self.redact(k), self.redact_value(v)))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    // Number of replacements per rule
+    pub fn hits(&self) -> BTreeMap<String, usize> {
+        self.state.lock().unwrap().hits.clone()
+    }
+}
+
+impl State {
+    fn placeholder(&mut self, index: usize, name: &str, value: &str) -> String {
+        let key = (index, value.to_string());
+        if let Some(placeholder) = self.placeholders.get(&key) {
+            return placeholder.clone();
+        }
+
+        let n = self.distinct.entry(index).or_default();
+        *n += 1;
+        let placeholder = format!("[{}_{}]", name.to_uppercase(), n);
+        self.placeholders.insert(key, placeholder.clone());
+
+        placeholder
+    }
+}
+
+// Log4rs encoder redacting the output of the wrapped encoder
+#[derive(Debug)]
+pub struct RedactEncoder {
+    pub inner: Box<dyn Encode>,
+    pub redactor: Redactor,
+}
+
+impl Encode for RedactEncoder {
+    fn encode(&self, w: &mut dyn Write, record: &log::Record) -> anyhow::Result<()> {
+        if self.redactor.is_empty() {
+            return self.inner.encode(w, record);
+        }
+
+        let mut buf = SimpleWriter(Vec::new());
+        self.inner.encode(&mut buf, record)?;
+        w.write_all(
+            self.redactor
+                .redact(&String::from_utf8_lossy(&buf.0))
+                .as_bytes(),
+        )?;
+
+        Ok(())
+    }
+}
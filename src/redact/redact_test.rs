@@ -0,0 +1,137 @@
+use super::redact::*;
+use crate::config::config::{ConfigData, ConfigLLM};
+use crate::llm::base::{RedactConfig, RedactPattern};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_redact() {
+    let r = Redactor::new(&RedactConfig::default(), &[]).unwrap();
+
+    assert_eq!(
+        r.redact("mail jane.doe@example.co.uk or call (555) 123-4567"),
+        "mail [EMAIL_1] or call [PHONE_1]"
+    );
+    assert_eq!(
+        r.redact("ssn 123-45-6789, card 4111 1111 1111 1111, token sk-abcdefghijklmnop1234"),
+        "ssn [SSN_1], card [CREDIT_CARD_1], token [TOKEN_1]"
+    );
+
+    // Plain numbers and dates are left alone
+    assert_eq!(r.redact("42 rows on 2024-01-31"), "42 rows on 2024-01-31");
+}
+
+#[test]
+fn test_placeholders() {
+    let r = Redactor::new(&RedactConfig::default(), &[]).unwrap();
+
+    assert_eq!(
+        r.redact("a@example.com b@example.com a@example.com"),
+        "[EMAIL_1] [EMAIL_2] [EMAIL_1]"
+    );
+
+    // Stable across calls and clones
+    let clone = r.clone();
+    assert_eq!(clone.redact("to b@example.com"), "to [EMAIL_2]");
+    assert_eq!(r.hits(), BTreeMap::from([("email".to_string(), 4)]));
+}
+
+#[test]
+fn test_keys() {
+    let keys = vec!["sk-proj-0123456789".to_string(), "short".to_string()];
+    let r = Redactor::new(&RedactConfig::default(), &keys).unwrap();
+
+    assert_eq!(
+        r.redact("Bearer sk-proj-0123456789, short"),
+        "Bearer [API_KEY_1], short"
+    );
+
+    let config = RedactConfig {
+        keys: false,
+        rules: Vec::new(),
+        ..Default::default()
+    };
+    let r = Redactor::new(&config, &keys).unwrap();
+    assert!(r.is_empty());
+    assert_eq!(r.redact("sk-proj-0123456789"), "sk-proj-0123456789");
+}
+
+#[test]
+fn test_patterns() {
+    let config = RedactConfig {
+        rules: vec!["email".to_string()],
+        patterns: vec![RedactPattern {
+            name: "employee_id".to_string(),
+            regex: r"EMP-\d{6}".to_string(),
+        }],
+        ..Default::default()
+    };
+    let r = Redactor::new(&config, &[]).unwrap();
+
+    assert_eq!(
+        r.redact("EMP-004211 <emp@corp.com>"),
+        "[EMPLOYEE_ID_1] <[EMAIL_1]>"
+    );
+
+    let config = RedactConfig {
+        rules: vec!["address".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        Redactor::new(&config, &[]).unwrap_err(),
+        "unknown rule 'address'"
+    );
+}
+
+#[test]
+fn test_redact_value() {
+    let r = Redactor::new(&RedactConfig::default(), &[]).unwrap();
+
+    let value = json!({
+        "inputs": {"messages": [{"role": "user", "content": "I am bob@example.com"}]},
+        "outputs": "Hi bob@example.com",
+        "meta": {"exec_sec": 0.5}
+    });
+    assert_eq!(
+        r.redact_value(&value),
+        json!({
+            "inputs": {"messages": [{"role": "user", "content": "I am [EMAIL_1]"}]},
+            "outputs": "Hi [EMAIL_1]",
+            "meta": {"exec_sec": 0.5}
+        })
+    );
+}
+
+#[test]
+fn test_from_config() {
+    let config = ConfigData {
+        llm: vec![ConfigLLM {
+            name: "openai".to_string(),
+            key: "sk-proj-0123456789".to_string(),
+            headers: BTreeMap::from([(
+                "OpenAI-Organization".to_string(),
+                "org-abcdefgh".to_string(),
+            )]),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    // Without a redact section the built-in rules apply too
+    let r = Redactor::from_config(&config).unwrap();
+    assert_eq!(
+        r.redact("sk-proj-0123456789 org-abcdefgh a@example.com"),
+        "[API_KEY_1] [API_KEY_2] [EMAIL_1]"
+    );
+
+    // No rules opt out of them, keys are still replaced
+    let config = ConfigData {
+        redact: Some(serde_yaml::from_str("rules: []").unwrap()),
+        ..config
+    };
+    let r = Redactor::from_config(&config).unwrap();
+    assert_eq!(
+        r.redact("sk-proj-0123456789 a@example.com"),
+        "[API_KEY_1] a@example.com"
+    );
+}
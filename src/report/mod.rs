@@ -0,0 +1,3 @@
+pub mod report;
+#[cfg(test)]
+pub mod report_test;
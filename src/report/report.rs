@@ -0,0 +1,572 @@
+use crate::config::config::ConfigData;
+use crate::jsonl::jsonl::{self, OnError};
+use crate::llm::base::ModelPrice;
+use crate::logger::constants::Constants;
+use crate::logger::run_dir::{Manifest, RunDir, CHECKPOINTS_DIR};
+use crate::logger::tracer::{SPAN_LLM_CALL, TRACES_FILE};
+use crate::optimizer::optimizer::{
+    BEST_PROMPT, CANDIDATE, CHAINED_LOG_NAME, CRITIQUE, EXPERT_IDENTITY, FEW_SHOT_EXAMPLES,
+    FINAL_PROMPT, PROMPT, ROUND_NUM, SCORE,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const HTML_FILE: &str = "report.html";
+pub const MARKDOWN_FILE: &str = "report.md";
+
+// Title, headers and rows
+type Table = (&'static str, Vec<&'static str>, Vec<Vec<String>>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scored {
+    pub round_num: Option<i64>,
+    pub prompt: String,
+    pub score: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Critique {
+    pub round_num: Option<i64>,
+    pub prompt: Option<String>,
+    pub critique: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModelUsage {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // None when the model has no price
+    pub cost: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub manifest: Option<Manifest>,
+    pub rounds: Vec<Scored>,
+    // Best score first, each prompt once
+    pub leaderboard: Vec<Scored>,
+    pub critiques: Vec<Critique>,
+    pub final_prompt: Option<String>,
+    pub expert_identity: Option<String>,
+    pub examples: Vec<Value>,
+    pub usage: BTreeMap<String, ModelUsage>,
+}
+
+impl Report {
+    // Read the chained log and checkpoints of a run. Tokens are taken from the LLM call
+    // spans of traces.json, or from the usage of the io logs when there are none, and
+    // priced by the name of the LLM, see `prices`.
+    pub fn load<P: AsRef<Path>>(
+        root: P,
+        pricing: &BTreeMap<String, ModelPrice>,
+    ) -> io::Result<Self> {
+        let root = root.as_ref();
+        let run = RunDir::open(root).ok();
+
+        let mut rows = Vec::new();
+        for path in row_files(root)? {
+            let records = jsonl::read_with::<Value, _>(&path, OnError::Skip)?;
+            rows.extend(records);
+        }
+
+        let mut report = Report::from_rows(&rows);
+        report.manifest = run.map(|x| x.manifest);
+
+        let traces = root.join(TRACES_FILE);
+        let calls = match traces.exists() {
+            true => trace_calls(&serde_json::from_str(&fs::read_to_string(traces)?)?),
+            false => Vec::new(),
+        };
+        let calls = match calls.is_empty() {
+            true => io_log_calls(&root.join(Constants::DIR_NAME))?,
+            false => calls,
+        };
+        report.usage = usage(&calls, pricing);
+
+        Ok(report)
+    }
+
+    pub fn from_rows(rows: &[Value]) -> Self {
+        let mut report = Report::default();
+        let mut candidates = Vec::new();
+
+        for row in rows {
+            let round_num = row[ROUND_NUM].as_i64();
+            let score = row[SCORE].as_f64();
+
+            if let Some(prompt) = row[BEST_PROMPT].as_str() {
+                let scored = Scored {
+                    round_num,
+                    prompt: prompt.to_string(),
+                    score,
+                };
+                report.rounds.push(scored.clone());
+                candidates.push(scored);
+            }
+            if let Some(prompt) = row[CANDIDATE].as_str() {
+                candidates.push(Scored {
+                    round_num,
+                    prompt: prompt.to_string(),
+                    score,
+                });
+            }
+            if let Some(critique) = row[CRITIQUE].as_str() {
+                report.critiques.push(Critique {
+                    round_num,
+                    prompt: row[PROMPT].as_str().map(|x| x.to_string()),
+                    critique: critique.to_string(),
+                });
+            }
+            if let Some(prompt) = row[FINAL_PROMPT].as_str() {
+                report.final_prompt = Some(prompt.to_string());
+                report.expert_identity = row[EXPERT_IDENTITY].as_str().map(|x| x.to_string());
+                report.examples = row[FEW_SHOT_EXAMPLES]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+            }
+        }
+
+        // Stable sort, ties keep the earliest candidate
+        candidates.sort_by(|a, b| {
+            b.score
+                .unwrap_or(f64::MIN)
+                .total_cmp(&a.score.unwrap_or(f64::MIN))
+        });
+        for candidate in candidates {
+            if !report
+                .leaderboard
+                .iter()
+                .any(|x| x.prompt == candidate.prompt)
+            {
+                report.leaderboard.push(candidate);
+            }
+        }
+
+        report
+    }
+
+    pub fn totals(&self) -> ModelUsage {
+        let mut totals = ModelUsage {
+            cost: Some(0.0),
+            ..Default::default()
+        };
+        for usage in self.usage.values() {
+            totals.calls += usage.calls;
+            totals.prompt_tokens += usage.prompt_tokens;
+            totals.completion_tokens += usage.completion_tokens;
+            totals.cost = totals.cost.zip(usage.cost).map(|(a, b)| a + b);
+        }
+
+        totals
+    }
+
+    // Write report.html and report.md into `dir`
+    pub fn write<P: AsRef<Path>>(&self, dir: P) -> io::Result<Vec<PathBuf>> {
+        fs::create_dir_all(&dir)?;
+
+        let html = dir.as_ref().join(HTML_FILE);
+        fs::write(&html, self.to_html())?;
+        let markdown = dir.as_ref().join(MARKDOWN_FILE);
+        fs::write(&markdown, self.to_markdown())?;
+
+        Ok(vec![html, markdown])
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# {}\n", self.title());
+        for (name, value) in self.summary() {
+            let _ = writeln!(out, "- **{}**: {}", name, value);
+        }
+
+        for (title, headers, rows) in self.tables() {
+            let _ = writeln!(out, "\n## {}\n", title);
+            if rows.is_empty() {
+                let _ = writeln!(out, "None recorded.");
+                continue;
+            }
+            let _ = writeln!(out, "| {} |", headers.join(" | "));
+            let _ = writeln!(out, "|{}", "---|".repeat(headers.len()));
+            for row in rows {
+                let cells: Vec<String> = row.iter().map(|x| markdown_cell(x)).collect();
+                let _ = writeln!(out, "| {} |", cells.join(" | "));
+            }
+        }
+
+        for (title, text) in self.texts() {
+            let _ = writeln!(out, "\n## {}\n", title);
+            match text {
+                Some(text) => {
+                    let _ = writeln!(out, "```\n{}\n```", text.replace("```", "'''"));
+                }
+                None => {
+                    let _ = writeln!(out, "None recorded.");
+                }
+            }
+        }
+
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+
+        let _ = write!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+             <style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n<ul>\n",
+            html_escape(&self.title()),
+            STYLE,
+            html_escape(&self.title())
+        );
+        for (name, value) in self.summary() {
+            let _ = writeln!(
+                out,
+                "<li><b>{}</b>: {}</li>",
+                html_escape(name),
+                html_escape(&value)
+            );
+        }
+        out.push_str("</ul>\n");
+
+        for (title, headers, rows) in self.tables() {
+            let _ = writeln!(out, "<h2>{}</h2>", html_escape(title));
+            if rows.is_empty() {
+                out.push_str("<p>None recorded.</p>\n");
+                continue;
+            }
+            out.push_str("<table>\n<tr>");
+            for header in headers {
+                let _ = write!(out, "<th>{}</th>", html_escape(header));
+            }
+            out.push_str("</tr>\n");
+            for row in rows {
+                out.push_str("<tr>");
+                for cell in row {
+                    let _ = write!(out, "<td>{}</td>", html_escape(&cell));
+                }
+                out.push_str("</tr>\n");
+            }
+            out.push_str("</table>\n");
+        }
+
+        for (title, text) in self.texts() {
+            let _ = writeln!(out, "<h2>{}</h2>", html_escape(title));
+            match text {
+                Some(text) => {
+                    let _ = writeln!(out, "<pre>{}</pre>", html_escape(&text));
+                }
+                None => out.push_str("<p>None recorded.</p>\n"),
+            }
+        }
+
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+
+    fn title(&self) -> String {
+        match &self.manifest {
+            Some(m) => format!("Run {} of {}", m.run_id, m.experiment_name),
+            None => "Run report".to_string(),
+        }
+    }
+
+    fn summary(&self) -> Vec<(&'static str, String)> {
+        let mut summary = Vec::new();
+        if let Some(m) = &self.manifest {
+            summary.push(("Description", m.description.clone()));
+            summary.push(("Status", m.status.clone()));
+            summary.push(("Started", m.started_at.clone()));
+            summary.push(("Finished", m.finished_at.clone().unwrap_or("-".to_string())));
+            summary.push(("Version", m.version.clone()));
+        }
+
+        let totals = self.totals();
+        summary.push(("LLM calls", totals.calls.to_string()));
+        summary.push((
+            "Tokens",
+            format!(
+                "{} prompt, {} completion",
+                totals.prompt_tokens, totals.completion_tokens
+            ),
+        ));
+        summary.push(("Cost", cost(totals.cost)));
+
+        summary
+    }
+
+    fn tables(&self) -> Vec<Table> {
+        let scored = |list: &[Scored]| -> Vec<Vec<String>> {
+            list.iter()
+                .map(|x| vec![round(x.round_num), x.prompt.clone(), score(x.score)])
+                .collect()
+        };
+
+        let critiques = self
+            .critiques
+            .iter()
+            .map(|x| {
+                vec![
+                    round(x.round_num),
+                    x.prompt.clone().unwrap_or("-".to_string()),
+                    x.critique.clone(),
+                ]
+            })
+            .collect();
+
+        let examples = self
+            .examples
+            .iter()
+            .map(|x| match x {
+                Value::Object(map) => vec![
+                    text(&map.get("question").cloned().unwrap_or_default()),
+                    text(
+                        &map.get("final_answer")
+                            .or(map.get("answer"))
+                            .cloned()
+                            .unwrap_or_default(),
+                    ),
+                ],
+                other => vec![text(other), String::new()],
+            })
+            .collect();
+
+        let mut usage: Vec<Vec<String>> = self
+            .usage
+            .iter()
+            .map(|(model, x)| usage_row(model, x))
+            .collect();
+        if !usage.is_empty() {
+            usage.push(usage_row("Total", &self.totals()));
+        }
+
+        vec![
+            (
+                "Best prompt per round",
+                vec!["Round", "Prompt", "Score"],
+                scored(&self.rounds),
+            ),
+            (
+                "Leaderboard",
+                vec!["Round", "Prompt", "Score"],
+                scored(&self.leaderboard),
+            ),
+            ("Critiques", vec!["Round", "Prompt", "Critique"], critiques),
+            ("Few-shot examples", vec!["Question", "Answer"], examples),
+            (
+                "Tokens and cost",
+                vec![
+                    "Model",
+                    "Calls",
+                    "Prompt tokens",
+                    "Completion tokens",
+                    "Cost",
+                ],
+                usage,
+            ),
+        ]
+    }
+
+    fn texts(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("Final prompt", self.final_prompt.clone()),
+            ("Expert identity", self.expert_identity.clone()),
+        ]
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;max-width:72em}\
+table{border-collapse:collapse;margin-bottom:1em}\
+th,td{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top;white-space:pre-wrap}\
+th{background:#f0f0f0}pre{background:#f6f6f6;padding:1em;white-space:pre-wrap}";
+
+// The chained log and every JSONL file of the checkpoints, checkpoints in name order
+fn row_files(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    let chained = root
+        .join(Constants::DIR_NAME)
+        .join(format!("{}.jsonl", CHAINED_LOG_NAME));
+    if chained.exists() {
+        files.push(chained);
+    }
+
+    let dir = root.join(CHECKPOINTS_DIR);
+    if dir.is_dir() {
+        let mut checkpoints: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|x| x.ok().map(|x| x.path()))
+            .filter(|x| jsonl::is_jsonl(x))
+            .collect();
+        checkpoints.sort();
+        files.extend(checkpoints);
+    }
+
+    Ok(files)
+}
+
+// (model, prompt tokens, completion tokens) of every LLM call span
+fn trace_calls(otlp: &Value) -> Vec<(String, u64, u64)> {
+    let spans = otlp["resourceSpans"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|x| x["scopeSpans"].as_array().into_iter().flatten())
+        .flat_map(|x| x["spans"].as_array().into_iter().flatten());
+
+    spans
+        .filter(|x| x["name"] == SPAN_LLM_CALL)
+        .map(|span| {
+            let attribute = |key: &str| -> Value {
+                span["attributes"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|x| x["key"] == key)
+                    .map(|x| x["value"].clone())
+                    .unwrap_or_default()
+            };
+            let tokens = |key: &str| -> u64 {
+                attribute(key)["intValue"]
+                    .as_str()
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or(0)
+            };
+            (
+                attribute("llm.model")["stringValue"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                tokens("llm.prompt_tokens"),
+                tokens("llm.completion_tokens"),
+            )
+        })
+        .collect()
+}
+
+// (model, prompt tokens, completion tokens) of every io log record with a usage
+fn io_log_calls(dir: &Path) -> io::Result<Vec<(String, u64, u64)>> {
+    let mut calls = Vec::new();
+    if !dir.is_dir() {
+        return Ok(calls);
+    }
+
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|x| x.ok().map(|x| x.path()))
+        .filter(|x| jsonl::is_jsonl(x))
+        .collect();
+    files.sort();
+
+    for file in files {
+        let records = jsonl::read_with::<Value, _>(&file, OnError::Skip)?;
+        for record in records {
+            let outputs = &record[Constants::OUTPUTS];
+            let usage = &outputs["usage"];
+            // The LLM name of the call, or the model of the response for older logs
+            let model = record[Constants::INPUTS]["model"]
+                .as_str()
+                .or(outputs["model"].as_str());
+            if usage.is_object() {
+                calls.push((
+                    model.unwrap_or_default().to_string(),
+                    usage["prompt_tokens"].as_u64().unwrap_or(0),
+                    usage["completion_tokens"].as_u64().unwrap_or(0),
+                ));
+            }
+        }
+    }
+
+    Ok(calls)
+}
+
+// Price of each LLM by name, `pricing` is keyed by the model id of its endpoint
+pub fn prices(config: &ConfigData) -> BTreeMap<String, ModelPrice> {
+    let pricing = config.pricing.clone().unwrap_or_default();
+
+    config
+        .llm
+        .iter()
+        .filter_map(|x| {
+            pricing
+                .get(&x.endpoint)
+                .map(|price| (x.name.clone(), *price))
+        })
+        .collect()
+}
+
+fn usage(
+    calls: &[(String, u64, u64)],
+    pricing: &BTreeMap<String, ModelPrice>,
+) -> BTreeMap<String, ModelUsage> {
+    let mut usage: BTreeMap<String, ModelUsage> = BTreeMap::new();
+    for (model, prompt_tokens, completion_tokens) in calls {
+        let entry = usage.entry(model.clone()).or_default();
+        entry.calls += 1;
+        entry.prompt_tokens += prompt_tokens;
+        entry.completion_tokens += completion_tokens;
+    }
+
+    for (model, entry) in usage.iter_mut() {
+        entry.cost = pricing.get(model).map(|price| {
+            (entry.prompt_tokens as f64 * price.input
+                + entry.completion_tokens as f64 * price.output)
+                / 1_000_000.0
+        });
+    }
+
+    usage
+}
+
+fn usage_row(model: &str, usage: &ModelUsage) -> Vec<String> {
+    vec![
+        match model.is_empty() {
+            true => "-".to_string(),
+            false => model.to_string(),
+        },
+        usage.calls.to_string(),
+        usage.prompt_tokens.to_string(),
+        usage.completion_tokens.to_string(),
+        cost(usage.cost),
+    ]
+}
+
+fn round(round_num: Option<i64>) -> String {
+    round_num.map(|x| x.to_string()).unwrap_or("-".to_string())
+}
+
+fn score(score: Option<f64>) -> String {
+    score
+        .map(|x| format!("{:.3}", x))
+        .unwrap_or("-".to_string())
+}
+
+// Unknown when a model has no price
+fn cost(cost: Option<f64>) -> String {
+    cost.map(|x| format!("${:.4}", x))
+        .unwrap_or("-".to_string())
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn markdown_cell(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', "<br>")
+}
+
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,181 @@
+use super::report::*;
+use crate::config::config::ConfigData;
+use crate::jsonl::jsonl;
+use crate::llm::base::ModelPrice;
+use crate::llm::llm::{ChatResponse, Usage};
+use crate::logger::tracer::{Tracer, SPAN_LLM_CALL, TRACES_FILE};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use tempfile::tempdir;
+
+fn rows() -> Vec<Value> {
+    vec![
+        json!({"round_num": 0, "candidate": "Solve it.", "score": 0.4}),
+        json!({"round_num": 0, "best_prompt": "Think step by step.", "score": 0.6}),
+        json!({"round_num": 1, "critique": "Mention units.", "prompt": "Think step by step."}),
+        json!({"round_num": 1, "best_prompt": "Think step by step, <keep units>.", "score": 0.8}),
+        json!({"round_num": 1, "candidate": "Think step by step.", "score": 0.5}),
+        json!({
+            "final_prompt": "Think step by step, <keep units>.",
+            "expert_identity": "You are a math teacher.",
+            "few_shot_examples": [{"question": "1+1", "final_answer": "2"}]
+        }),
+    ]
+}
+
+#[test]
+fn test_from_rows() {
+    let r = Report::from_rows(&rows());
+
+    assert_eq!(r.rounds.len(), 2);
+    assert_eq!(r.rounds[1].score, Some(0.8));
+
+    // Each prompt once with its best score
+    let leaderboard: Vec<(&str, Option<f64>)> = r
+        .leaderboard
+        .iter()
+        .map(|x| (x.prompt.as_str(), x.score))
+        .collect();
+    assert_eq!(
+        leaderboard,
+        vec![
+            ("Think step by step, <keep units>.", Some(0.8)),
+            ("Think step by step.", Some(0.6)),
+            ("Solve it.", Some(0.4)),
+        ]
+    );
+
+    assert_eq!(r.critiques[0].critique, "Mention units.");
+    assert_eq!(
+        r.expert_identity.as_deref(),
+        Some("You are a math teacher.")
+    );
+    assert_eq!(r.examples.len(), 1);
+}
+
+#[test]
+fn test_load() {
+    let temp_dir = tempdir().unwrap();
+    let root = temp_dir.path();
+
+    fs::create_dir_all(root.join("io_logs")).unwrap();
+    fs::create_dir_all(root.join("checkpoints")).unwrap();
+    let rows = rows();
+    jsonl::write(root.join("io_logs").join("best_prompt.jsonl"), &rows[..3]).unwrap();
+    jsonl::write(
+        root.join("checkpoints").join("round_1.jsonl.gz"),
+        &rows[3..],
+    )
+    .unwrap();
+
+    let tracer = Tracer::new("promptx");
+    for (model, prompt_tokens) in [("gpt-4o", 1000), ("gpt-4o", 3000), ("local", 10)] {
+        let response: Result<ChatResponse, String> = Ok(ChatResponse {
+            usage: Some(Usage {
+                prompt_tokens,
+                completion_tokens: 500,
+                total_tokens: prompt_tokens + 500,
+            }),
+            ..Default::default()
+        });
+        tracer.start(SPAN_LLM_CALL).record_chat(model, &response);
+    }
+    tracer.write(root.join(TRACES_FILE)).unwrap();
+
+    let pricing = BTreeMap::from([(
+        "gpt-4o".to_string(),
+        ModelPrice {
+            input: 2.5,
+            output: 10.0,
+        },
+    )]);
+    let r = Report::load(root, &pricing).unwrap();
+
+    assert!(r.manifest.is_none());
+    assert_eq!(r.rounds.len(), 2);
+    assert!(r.final_prompt.is_some());
+
+    let gpt = &r.usage["gpt-4o"];
+    assert_eq!(
+        (gpt.calls, gpt.prompt_tokens, gpt.completion_tokens),
+        (2, 4000, 1000)
+    );
+    assert_eq!(gpt.cost, Some(0.02));
+    assert_eq!(r.usage["local"].cost, None);
+
+    // The total is unknown while a model has no price
+    let totals = r.totals();
+    assert_eq!((totals.calls, totals.prompt_tokens), (3, 4010));
+    assert_eq!(totals.cost, None);
+}
+
+#[test]
+fn test_load_io_logs() {
+    let temp_dir = tempdir().unwrap();
+    let root = temp_dir.path();
+
+    fs::create_dir_all(root.join("io_logs")).unwrap();
+    let record = json!({
+        "inputs": {"prompt": "1+1"},
+        "outputs": {"model": "gpt-4o", "usage": {"prompt_tokens": 12, "completion_tokens": 3}}
+    });
+    // Calls logged by the optimizer name their LLM in the inputs
+    let call = json!({
+        "inputs": {"model": "openai", "messages": []},
+        "outputs": {"model": "gpt-4o-2024-08-06", "usage": {"prompt_tokens": 5, "completion_tokens": 1}}
+    });
+    jsonl::write(root.join("io_logs").join("calls.jsonl"), &[record, call]).unwrap();
+
+    let r = Report::load(root, &BTreeMap::new()).unwrap();
+    assert_eq!(r.usage["gpt-4o"].prompt_tokens, 12);
+    assert_eq!(r.usage["openai"].prompt_tokens, 5);
+    assert!(r.rounds.is_empty());
+}
+
+#[test]
+fn test_prices() {
+    let config: ConfigData = serde_yaml::from_str(
+        "llm:
+  - name: openai
+    api: https://api.openai.com/v1/chat/completions
+    key: test_key
+    endpoint: gpt-4o
+  - name: local
+    api: http://localhost:8080/v1/chat/completions
+    key: test_key
+    endpoint: llama
+pricing:
+  gpt-4o:
+    input: 2.5
+    output: 10
+",
+    )
+    .unwrap();
+
+    let prices = prices(&config);
+    assert_eq!(prices.len(), 1);
+    assert_eq!(prices["openai"].input, 2.5);
+}
+
+#[test]
+fn test_render() {
+    let r = Report::from_rows(&rows());
+
+    let markdown = r.to_markdown();
+    assert!(markdown.starts_with("# Run report\n"));
+    assert!(markdown.contains("| 1 | Think step by step, <keep units>. | 0.800 |"));
+    assert!(markdown.contains("## Final prompt\n\n```\nThink step by step, <keep units>.\n```"));
+    assert!(markdown.contains("## Tokens and cost\n\nNone recorded."));
+
+    let html = r.to_html();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<td>Think step by step, &lt;keep units&gt;.</td>"));
+    assert!(html.contains("<pre>You are a math teacher.</pre>"));
+    assert!(!html.contains("<keep units>"));
+
+    let temp_dir = tempdir().unwrap();
+    let files = r.write(temp_dir.path().join("outputs")).unwrap();
+    assert_eq!(fs::read_to_string(&files[0]).unwrap(), html);
+    assert_eq!(fs::read_to_string(&files[1]).unwrap(), markdown);
+}
@@ -0,0 +1,3 @@
+pub mod secrets;
+#[cfg(test)]
+pub mod secrets_test;
@@ -0,0 +1,232 @@
+use openssl::base64::{decode_block, encode_block};
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+pub const SECRET_PREFIX: &str = "secret:";
+
+const ENV_FILE: &str = "PROMPTX_SECRETS_FILE";
+const ENV_PASSPHRASE: &str = "PROMPTX_SECRETS_PASSPHRASE";
+const KDF: &str = "pbkdf2-sha256";
+const ITERATIONS: u32 = 600_000;
+// Upper bound for the iterations read from a store file, so that a tampered file
+// cannot stall the key derivation
+const MAX_ITERATIONS: u32 = 10 * ITERATIONS;
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+// On-disk layout, the whole name to value map is encrypted at once so that
+// secret names do not leak either
+#[derive(Debug, Deserialize, Serialize)]
+struct SecretsFile {
+    version: u32,
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    tag: String,
+    ciphertext: String,
+}
+
+#[derive(Clone)]
+pub struct Secrets {
+    pub path: PathBuf,
+    pub iterations: u32,
+    passphrase: String,
+}
+
+impl Secrets {
+    pub fn new<P: AsRef<Path>>(path: P, passphrase: &str) -> Self {
+        Secrets {
+            path: path.as_ref().to_path_buf(),
+            iterations: ITERATIONS,
+            passphrase: passphrase.to_string(),
+        }
+    }
+
+    // $PROMPTX_SECRETS_FILE, or ~/.promptx/secrets.json
+    pub fn default_path() -> PathBuf {
+        if let Ok(path) = std::env::var(ENV_FILE) {
+            return PathBuf::from(path);
+        }
+
+        let home = std::env::var("HOME")
+            .or_else(|_| std::env::var("USERPROFILE"))
+            .unwrap_or_default();
+
+        Path::new(&home).join(".promptx").join("secrets.json")
+    }
+
+    // $PROMPTX_SECRETS_PASSPHRASE, or ask on the terminal without echo. A new
+    // passphrase is asked twice.
+    pub fn passphrase(confirm: bool) -> Result<String, Box<dyn Error>> {
+        if let Ok(passphrase) = std::env::var(ENV_PASSPHRASE) {
+            if passphrase.is_empty() {
+                return Err(format!("empty passphrase in ${}", ENV_PASSPHRASE).into());
+            }
+            return Ok(passphrase);
+        }
+
+        let passphrase = rpassword::prompt_password("Secrets passphrase: ")?;
+        if passphrase.is_empty() {
+            return Err("empty passphrase".into());
+        }
+
+        if confirm && rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+            return Err("passphrases do not match".into());
+        }
+
+        Ok(passphrase)
+    }
+
+    // The store at the default path, a missing store is created with a confirmed passphrase
+    pub fn open() -> Result<Self, Box<dyn Error>> {
+        let path = Self::default_path();
+        let passphrase = Self::passphrase(!path.exists())?;
+
+        Ok(Secrets::new(path, &passphrase))
+    }
+
+    pub fn load(&self) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let file: SecretsFile = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+        if file.version != 1 || file.kdf != KDF {
+            return Err(format!("unsupported secrets file {}", self.path.display()).into());
+        }
+        if file.iterations == 0 || file.iterations > MAX_ITERATIONS {
+            return Err(format!(
+                "invalid iterations {} in secrets file {}, expected 1 to {}",
+                file.iterations,
+                self.path.display(),
+                MAX_ITERATIONS
+            )
+            .into());
+        }
+
+        let key = self.derive_key(&decode_block(&file.salt)?, file.iterations)?;
+        let plaintext = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(&decode_block(&file.nonce)?),
+            &[],
+            &decode_block(&file.ciphertext)?,
+            &decode_block(&file.tag)?,
+        )
+        .map_err(|_| "failed to decrypt secrets, wrong passphrase or corrupted file")?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    pub fn save(&self, entries: &BTreeMap<String, String>) -> Result<(), Box<dyn Error>> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        let mut tag = [0u8; TAG_LEN];
+        rand_bytes(&mut salt)?;
+        rand_bytes(&mut nonce)?;
+
+        let key = self.derive_key(&salt, self.iterations)?;
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &key,
+            Some(&nonce),
+            &[],
+            &serde_json::to_vec(entries)?,
+            &mut tag,
+        )?;
+
+        let file = SecretsFile {
+            version: 1,
+            kdf: KDF.to_string(),
+            iterations: self.iterations,
+            salt: encode_block(&salt),
+            nonce: encode_block(&nonce),
+            tag: encode_block(&tag),
+            ciphertext: encode_block(&ciphertext),
+        };
+
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir)?;
+
+        let mut tmp = NamedTempFile::new_in(dir)?;
+        tmp.write_all(serde_json::to_string_pretty(&file)?.as_bytes())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            tmp.as_file()
+                .set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        tmp.persist(&self.path)?;
+
+        Ok(())
+    }
+
+    pub fn set(&self, name: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        if name.is_empty() {
+            return Err("invalid name".into());
+        }
+
+        let mut entries = self.load()?;
+        entries.insert(name.to_string(), value.to_string());
+        self.save(&entries)
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        Ok(self.load()?.remove(name))
+    }
+
+    pub fn list(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        Ok(self.load()?.into_keys().collect())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<bool, Box<dyn Error>> {
+        let mut entries = self.load()?;
+        if entries.remove(name).is_none() {
+            return Ok(false);
+        }
+
+        self.save(&entries)?;
+        Ok(true)
+    }
+
+    // Replace a `secret:<name>` reference with the stored value, other values are left as is
+    pub fn resolve(
+        &self,
+        entries: &BTreeMap<String, String>,
+        value: &str,
+    ) -> Result<String, Box<dyn Error>> {
+        match value.strip_prefix(SECRET_PREFIX) {
+            Some(name) => entries.get(name).cloned().ok_or_else(|| {
+                format!("secret {} not found in {}", name, self.path.display()).into()
+            }),
+            None => Ok(value.to_string()),
+        }
+    }
+
+    fn derive_key(&self, salt: &[u8], iterations: u32) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut key = vec![0u8; KEY_LEN];
+        pbkdf2_hmac(
+            self.passphrase.as_bytes(),
+            salt,
+            iterations as usize,
+            MessageDigest::sha256(),
+            &mut key,
+        )?;
+        Ok(key)
+    }
+}
@@ -0,0 +1,51 @@
+use super::secrets::*;
+use std::collections::BTreeMap;
+use tempfile::tempdir;
+
+fn store(path: &std::path::Path, passphrase: &str) -> Secrets {
+    let mut s = Secrets::new(path, passphrase);
+    s.iterations = 1000;
+    s
+}
+
+#[test]
+fn test_secrets() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("nested").join("secrets.json");
+    let s = store(&path, "passphrase");
+
+    assert_eq!(s.list().unwrap().len(), 0);
+    assert_eq!(s.get("openai_prod").unwrap(), None);
+
+    s.set("openai_prod", "sk-abc").unwrap();
+    s.set("doubao", "dk-xyz").unwrap();
+    assert_eq!(s.get("openai_prod").unwrap(), Some("sk-abc".to_string()));
+    assert_eq!(s.list().unwrap(), vec!["doubao", "openai_prod"]);
+
+    let raw = std::fs::read_to_string(&path).unwrap();
+    assert!(!raw.contains("sk-abc"));
+    assert!(!raw.contains("openai_prod"));
+
+    assert!(s.remove("doubao").unwrap());
+    assert!(!s.remove("doubao").unwrap());
+    assert_eq!(s.list().unwrap(), vec!["openai_prod"]);
+
+    assert!(store(&path, "wrong").load().is_err());
+
+    // A tampered iteration count is refused before deriving the key
+    let tampered = raw.replace("\"iterations\": 1000", "\"iterations\": 4294967295");
+    std::fs::write(&path, tampered).unwrap();
+    let err = s.load().unwrap_err().to_string();
+    assert!(err.starts_with("invalid iterations 4294967295"));
+}
+
+#[test]
+fn test_resolve() {
+    let s = store(std::path::Path::new("secrets.json"), "passphrase");
+    let mut entries = BTreeMap::new();
+    entries.insert("openai_prod".to_string(), "sk-abc".to_string());
+
+    assert_eq!(s.resolve(&entries, "secret:openai_prod").unwrap(), "sk-abc");
+    assert_eq!(s.resolve(&entries, "plain").unwrap(), "plain");
+    assert!(s.resolve(&entries, "secret:missing").is_err());
+}
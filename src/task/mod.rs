@@ -0,0 +1,3 @@
+pub mod task;
+#[cfg(test)]
+pub mod task_test;
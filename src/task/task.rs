@@ -0,0 +1,170 @@
+use crate::llm::base::{PromptLibraryConfig, TaskConfig};
+use crate::llm::llm::{ChatOptions, Message, LLM};
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+use std::error::Error;
+use std::sync::LazyLock;
+
+pub const CHAT_MODE: &str = "chat";
+pub const GENERATION_MODE: &str = "generation";
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{|\}\}|\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+// Executes the tasks of the prompt library in `prompt_library`
+#[derive(Clone, Default)]
+pub struct Tasks {
+    pub llm: LLM,
+}
+
+impl Tasks {
+    pub fn new(llm: LLM) -> Self {
+        Tasks { llm }
+    }
+
+    pub fn library(&self) -> Result<&PromptLibraryConfig, Box<dyn Error>> {
+        self.llm
+            .config
+            .prompt_library
+            .as_ref()
+            .ok_or_else(|| "no prompt_library in config".into())
+    }
+
+    // Task `name` and the mode it is listed under, chat tasks first
+    pub fn find(&self, name: &str) -> Result<(&'static str, &TaskConfig), Box<dyn Error>> {
+        let library = self.library()?;
+
+        for (mode, tasks) in [
+            (CHAT_MODE, &library.mode.chat),
+            (GENERATION_MODE, &library.mode.generation),
+        ] {
+            if let Some(task) = tasks.iter().find(|x| x.name == name) {
+                return Ok((mode, task));
+            }
+        }
+
+        let names: Vec<&str> = library
+            .mode
+            .chat
+            .iter()
+            .chain(library.mode.generation.iter())
+            .map(|x| x.name.as_str())
+            .collect();
+
+        Err(format!(
+            "unknown task '{}', expected one of {}",
+            name,
+            names.join(", ")
+        )
+        .into())
+    }
+
+    // Chat tasks get the library prompts and guidelines as a system message, generation
+    // tasks get them prepended to the prompt
+    pub fn messages(
+        &self,
+        name: &str,
+        vars: &Map<String, Value>,
+    ) -> Result<Vec<Message>, Box<dyn Error>> {
+        let library = self.library()?;
+        let (mode, task) = self.find(name)?;
+
+        let prompt = render(&task.prompt_template, vars)?;
+
+        let mut system = Vec::new();
+        if task.prepend_system_prompts {
+            system.extend(library.system_prompts.as_deref());
+        }
+        if task.prepend_system_guidelines {
+            system.extend(library.system_guidelines.as_deref());
+        }
+        let system = system.join("\n\n");
+
+        let messages = match (mode, system.is_empty()) {
+            (_, true) => vec![Message::user(&prompt)],
+            (CHAT_MODE, false) => vec![Message::system(&system), Message::user(&prompt)],
+            _ => vec![Message::user(&format!("{}\n\n{}", system, prompt))],
+        };
+
+        Ok(messages)
+    }
+
+    // LLM of the task, or the assistant LLM of the setup
+    pub fn model(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        let (_, task) = self.find(name)?;
+
+        task.llm_model_id
+            .clone()
+            .or_else(|| {
+                self.llm
+                    .config
+                    .setup
+                    .as_ref()
+                    .map(|x| x.assistant_llm.prompt_opt.clone())
+            })
+            .ok_or_else(|| format!("task '{}' has no llm_model_id", name).into())
+    }
+
+    pub async fn run(
+        &self,
+        name: &str,
+        vars: &Map<String, Value>,
+    ) -> Result<String, Box<dyn Error>> {
+        let messages = self.messages(name, vars)?;
+        let model = self.model(name)?;
+
+        let response = self
+            .llm
+            .chat(model, messages, ChatOptions::default())
+            .await?;
+
+        Ok(response.content().unwrap_or_default().to_string())
+    }
+}
+
+// Replace `{name}` placeholders, `{{` and `}}` are literal braces. Strings are inserted
+// as is, other values as JSON.
+pub fn render(template: &str, vars: &Map<String, Value>) -> Result<String, Box<dyn Error>> {
+    let mut missing = Vec::new();
+
+    let rendered = PLACEHOLDER.replace_all(template, |caps: &Captures| match caps.get(1) {
+        None => caps[0][..1].to_string(),
+        Some(name) => match vars.get(name.as_str()) {
+            Some(Value::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+            None => {
+                missing.push(name.as_str().to_string());
+                caps[0].to_string()
+            }
+        },
+    });
+
+    if !missing.is_empty() {
+        return Err(format!("missing variables: {}", missing.join(", ")).into());
+    }
+
+    Ok(rendered.into_owned())
+}
+
+// Variables from a JSON object, overridden by `key=value` pairs
+pub fn variables(
+    input: Option<&str>,
+    pairs: &[String],
+) -> Result<Map<String, Value>, Box<dyn Error>> {
+    let mut vars = match input {
+        Some(input) => match serde_json::from_str(input)? {
+            Value::Object(map) => map,
+            _ => return Err("input must be a JSON object".into()),
+        },
+        None => Map::new(),
+    };
+
+    for pair in pairs {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("invalid variable '{}', expected KEY=VALUE", pair))?;
+        vars.insert(key.to_string(), Value::String(value.to_string()));
+    }
+
+    Ok(vars)
+}
@@ -0,0 +1,129 @@
+use super::task::*;
+use crate::config::config::{ConfigData, ConfigLLM};
+use crate::llm::base::{Mode, PromptLibraryConfig, TaskConfig};
+use crate::llm::llm::{Message, LLM};
+use serde_json::{json, Map, Value};
+use wiremock::matchers::{body_partial_json, method};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn task(name: &str, template: &str, prompts: bool, guidelines: bool) -> TaskConfig {
+    TaskConfig {
+        name: name.to_string(),
+        prompt_template: template.to_string(),
+        llm_request_type: "chat".to_string(),
+        prepend_system_prompts: prompts,
+        prepend_system_guidelines: guidelines,
+        emb_model_id: None,
+        llm_model_id: Some("openai".to_string()),
+    }
+}
+
+fn tasks(api: &str) -> Tasks {
+    let config = ConfigData {
+        llm: vec![ConfigLLM {
+            name: "openai".to_string(),
+            api: api.to_string(),
+            key: "sk-proj".to_string(),
+            endpoint: "gpt-4o".to_string(),
+            ..Default::default()
+        }],
+        prompt_library: Some(PromptLibraryConfig {
+            mode: Mode {
+                chat: vec![
+                    task("math", "Solve {question}", true, true),
+                    task("plain", "Say {word}", false, false),
+                ],
+                generation: vec![task("story", "Write about {topic}", true, false)],
+            },
+            system_prompts: Some("You are helpful.".to_string()),
+            system_guidelines: Some("Be brief.".to_string()),
+        }),
+        ..Default::default()
+    };
+
+    Tasks::new(LLM::new(config).unwrap())
+}
+
+fn vars(value: Value) -> Map<String, Value> {
+    value.as_object().unwrap().clone()
+}
+
+#[test]
+fn test_render() {
+    let v = vars(json!({"name": "x", "n": 3, "list": [1, 2]}));
+
+    assert_eq!(
+        render("{name} {n} {list} {{name}}", &v).unwrap(),
+        "x 3 [1,2] {name}"
+    );
+    assert_eq!(
+        render("{name} {missing} {other}", &v)
+            .unwrap_err()
+            .to_string(),
+        "missing variables: missing, other"
+    );
+}
+
+#[test]
+fn test_variables() {
+    let v = variables(
+        Some(r#"{"a": "1", "b": 2}"#),
+        &["b=3".to_string(), "c=x=y".to_string()],
+    )
+    .unwrap();
+    assert_eq!(Value::Object(v), json!({"a": "1", "b": "3", "c": "x=y"}));
+
+    assert!(variables(Some("[1]"), &[]).is_err());
+    assert!(variables(None, &["a".to_string()]).is_err());
+}
+
+#[test]
+fn test_messages() {
+    let t = tasks("http://127.0.0.1:1");
+
+    assert_eq!(
+        t.messages("math", &vars(json!({"question": "1+1"})))
+            .unwrap(),
+        vec![
+            Message::system("You are helpful.\n\nBe brief."),
+            Message::user("Solve 1+1"),
+        ]
+    );
+    assert_eq!(
+        t.messages("plain", &vars(json!({"word": "hi"}))).unwrap(),
+        vec![Message::user("Say hi")]
+    );
+    assert_eq!(
+        t.messages("story", &vars(json!({"topic": "cats"})))
+            .unwrap(),
+        vec![Message::user("You are helpful.\n\nWrite about cats")]
+    );
+
+    assert_eq!(
+        t.find("missing").unwrap_err().to_string(),
+        "unknown task 'missing', expected one of math, plain, story"
+    );
+    assert!(Tasks::default().find("math").is_err());
+}
+
+#[tokio::test]
+async fn test_run() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "system", "content": "You are helpful.\n\nBe brief."},
+                {"role": "user", "content": "Solve 1+1"}
+            ]
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "choices": [{"message": {"role": "assistant", "content": "2"}}]
+        })))
+        .mount(&server)
+        .await;
+
+    let t = tasks(&server.uri());
+    let output = t.run("math", &vars(json!({"question": "1+1"}))).await;
+    assert_eq!(output.unwrap(), "2");
+}